@@ -1,12 +1,135 @@
-use super::super::language::Language;
+use std::collections::{HashMap, HashSet};
 
-use crate::vm::{instruction::Instruction, opcodes::Opcode, operand::Operand};
+use super::super::language::Language;
 
+use crate::vm::{
+    breakpoint::{self, Comparison},
+    instruction::Instruction,
+    opcodes::Opcode,
+    operand::Operand,
+    profile::{MachineProfile, DEFAULT_STACK_DEPTH},
+};
+
+use super::diagnostic::{Diagnostic, Severity};
+use super::disassembler::Radix;
+use super::expression::Expression;
+use super::macros::Macro;
 use super::parsing_err::ParsingError;
+use super::resolver::SourceResolver;
+
+/// Maximal depth of macros invoked in bodies of other macros
+const MAX_MACRO_DEPTH: usize = 16;
+
+/// Maximal depth of included files
+const MAX_INCLUDE_DEPTH: usize = 16;
+
+/// Built-in symbol, 1 if assembler was created with stack, 0 otherwise
+///
+/// It is defined for `.ifdef` only if assembler has stack
+pub const STACK_SYMBOL: &str = "__STACK__";
+
+/// Line of code to be assembled
+#[derive(Clone)]
+struct SourceLine {
+    /// Name of included file line comes from, None for main program
+    file: Option<String>,
+    /// Line number in its file, for expanded macro it is the line macro was invoked at
+    line: usize,
+    text: String,
+    /// Text of line in its file, for expanded macro it is the line macro was invoked at
+    source: String,
+    /// Name of macro and line number in its body, if line comes from macro expansion
+    expansion: Option<(String, usize)>,
+}
+
+impl SourceLine {
+    fn new(file: Option<&str>, line: usize, text: &str) -> SourceLine {
+        SourceLine {
+            file: file.map(|file| file.to_string()),
+            line,
+            text: text.to_string(),
+            source: text.to_string(),
+            expansion: None,
+        }
+    }
+
+    /// Creates diagnostic for error found in this line
+    ///
+    /// Errors in macro expansions point at line macro was invoked at and name line in macro body,
+    /// errors in included files name the file
+    fn diagnostic(&self, severity: Severity, error: ParsingError) -> Diagnostic {
+        let error = match &self.file {
+            Some(file) => error.in_file(file),
+            None => error,
+        };
+        let error = match &self.expansion {
+            Some((name, body_line)) => error.in_macro(name, *body_line),
+            None => error,
+        };
+        Diagnostic::new(severity, error, &self.source)
+    }
+}
+
+/// Block of conditional assembly, started with .if, .ifdef or .ifndef
+struct Condition {
+    /// Line block was started at
+    source_line: SourceLine,
+    /// Directive block was started with, with its column
+    directive: (usize, String),
+    /// Whether block containing this one is assembled
+    parent_active: bool,
+    /// Whether current branch of block is assembled
+    active: bool,
+    else_seen: bool,
+}
+
+/// State of preprocessing - processing directives, expanding includes and macros
+#[derive(Default)]
+struct Preprocessing {
+    lines: Vec<SourceLine>,
+    diagnostics: Vec<Diagnostic>,
+    /// Canonical names of files being included, used to detect cycles
+    include_stack: Vec<String>,
+    /// Number of macro expansions so far, used to make labels unique
+    expansions: usize,
+    /// Conditional blocks current line is in, innermost last
+    conditions: Vec<Condition>,
+}
+
+impl Preprocessing {
+    /// Checks if current line is assembled, i.e. it is not in skipped conditional block
+    fn is_active(&self) -> bool {
+        self.conditions
+            .last()
+            .map(|condition| condition.active)
+            .unwrap_or(true)
+    }
+}
 
 #[derive(serde::Deserialize, serde::Serialize)]
 pub struct Assembler {
-    stack_present: bool,
+    /// Number of registers and ports and whether stack instructions are allowed
+    profile: MachineProfile,
+    /// Symbol table built in the second pass, maps label name to its index in program
+    labels: HashMap<String, usize>,
+    /// Radixes of integer literals for each assembled instruction, used by disassembler
+    literal_radixes: Vec<Vec<Option<Radix>>>,
+    /// Names of aliases operands were written with, for each assembled instruction
+    operand_aliases: Vec<Vec<Option<String>>>,
+    /// Named integer constants defined with .equ or .define directives, and built-in symbols
+    constants: HashMap<String, i32>,
+    /// Register aliases defined with .alias directive before first label
+    aliases: HashMap<String, Operand>,
+    /// Register aliases defined with .alias directive after the most recent global label,
+    /// None before first label
+    scoped_aliases: Option<HashMap<String, Operand>>,
+    /// Macros defined with .macro directive
+    macros: HashMap<String, Macro>,
+    /// Provides files included with .include directive
+    #[serde(skip)]
+    resolver: Option<Box<dyn SourceResolver + Send + Sync>>,
+    /// Warnings produced while assembling current line, e.g. overflow in constant expression
+    warnings: Vec<ParsingError>,
 }
 impl Default for Assembler {
     fn default() -> Self {
@@ -16,13 +139,48 @@ impl Default for Assembler {
 
 impl Assembler {
     pub fn new() -> Assembler {
+        Assembler::new_with_profile(MachineProfile::new())
+    }
+
+    /// Creates assembler accepting registers, ports and stack instructions described by profile
+    ///
+    /// ### Arguments
+    /// * 'profile' MachineProfile - parameters of machine, the same as used by vm
+    pub fn new_with_profile(profile: MachineProfile) -> Assembler {
         Assembler {
-            stack_present: false,
+            profile,
+            labels: HashMap::new(),
+            literal_radixes: Vec::new(),
+            operand_aliases: Vec::new(),
+            constants: HashMap::new(),
+            aliases: HashMap::new(),
+            scoped_aliases: None,
+            macros: HashMap::new(),
+            resolver: None,
+            warnings: Vec::new(),
         }
     }
 
+    /// Allows stack instructions, built-in symbol __STACK__ is 1 so `.if __STACK__` blocks are assembled
     pub fn with_stack(mut self) -> Assembler {
-        self.stack_present = true;
+        if !self.profile.has_stack() {
+            self.profile = self.profile.with_stack(DEFAULT_STACK_DEPTH);
+        }
+        self
+    }
+
+    pub fn get_profile(&self) -> MachineProfile {
+        self.profile
+    }
+
+    /// Sets resolver providing files included with .include directive
+    ///
+    /// Without resolver every .include is an error
+    pub fn with_resolver(
+        mut self,
+        resolver: impl SourceResolver + Send + Sync + 'static,
+    ) -> Assembler {
+        self.resolver = Some(Box::new(resolver));
         self
     }
 
@@ -33,700 +191,2725 @@ impl Assembler {
         register_only: bool,
     ) -> Result<Operand, ParsingError> {
         if operand_text.is_empty() {
-            return Err(ParsingError::new(
-                ParsingError::EmptyOperand,
-                line,
-                "".to_string(),
-            ));
+            return Err(
+                ParsingError::new(ParsingError::EmptyOperand, line, "".to_string())
+                    .with_expected(t!("hint.operand")),
+            );
+        }
+        if let Some(register) = self.get_alias(operand_text) {
+            return Ok(register.clone());
         }
         let remaining_text = &operand_text[1..];
 
         match operand_text {
             "acc" => Ok(Operand::ACC),
             "pc" => Ok(Operand::PC),
-            r if r.starts_with('r') => {
+            memory if memory.starts_with('[') => self.parse_memory_operand(memory, line),
+            r if Assembler::is_register_name(r) => {
                 if let Ok(index) = remaining_text.parse::<usize>() {
-                    if index > 3 {
-                        return Err(ParsingError::new(
-                            ParsingError::InvalidPortNumber,
-                            line,
-                            "".to_string(),
-                        ));
+                    if index < self.profile.get_registers() {
+                        return Ok(Operand::GeneralRegister(index));
                     }
-                    return Ok(Operand::GeneralRegister(index));
                 }
-                Err(ParsingError::new(
-                    ParsingError::InvalidPortNumber,
-                    line,
-                    "".to_string(),
-                ))
+                Err(
+                    ParsingError::new(ParsingError::InvalidRegisterNumber, line, "".to_string())
+                        .with_expected(t!(
+                            "hint.register",
+                            max = self.profile.get_registers().saturating_sub(1)
+                        )),
+                )
             }
-            p if p.starts_with('p') => {
+            p if Assembler::is_port_name(p) => {
                 if let Ok(index) = remaining_text.parse::<usize>() {
-                    if index > 5 {
-                        return Err(ParsingError::new(
-                            ParsingError::InvalidPortNumber,
-                            line,
-                            "".to_string(),
-                        ));
+                    if index < self.profile.get_ports() {
+                        return Ok(Operand::PortRegister(index));
                     }
-                    return Ok(Operand::PortRegister(index));
                 }
-                Err(ParsingError::new(
-                    ParsingError::InvalidPortNumber,
-                    line,
-                    "".to_string(),
-                ))
+                Err(
+                    ParsingError::new(ParsingError::InvalidPortNumber, line, "".to_string())
+                        .with_expected(t!(
+                            "hint.port",
+                            max = self.profile.get_ports().saturating_sub(1)
+                        )),
+                )
             }
 
-            decimal
-                if decimal
-                    .chars()
-                    .all(|c| c.is_numeric() || (c == '-' && decimal.starts_with('-'))) =>
-            {
-                if register_only {
-                    return Err(ParsingError::new(
-                        ParsingError::InvalidOperandType,
-                        line,
-                        "".to_string(),
-                    ));
-                }
-                if let Ok(decimal) = decimal.parse::<i32>() {
-                    return Ok(Operand::IntegerValue(decimal));
-                }
-                Err(ParsingError::new(
-                    ParsingError::InvalidNumericLiteral,
-                    line,
-                    "".to_string(),
-                ))
-            }
-            binary if binary.starts_with("0b") => {
-                if register_only {
-                    return Err(ParsingError::new(
-                        ParsingError::InvalidBinaryLiteral,
-                        line,
-                        "".to_string(),
-                    ));
-                }
-                if let Ok(binary) = i32::from_str_radix(&binary[2..], 2) {
-                    return Ok(Operand::IntegerValue(binary));
-                }
-                Err(ParsingError::new(
-                    ParsingError::InvalidBinaryLiteral,
-                    line,
-                    "".to_string(),
-                ))
+            _ if register_only => {
+                Err(
+                    ParsingError::new(ParsingError::InvalidOperandType, line, "".to_string())
+                        .with_expected(t!("hint.destination")),
+                )
             }
 
-            hex if hex.starts_with("0x") => {
-                if register_only {
-                    return Err(ParsingError::new(
-                        ParsingError::InvalidHexLiteral,
-                        line,
-                        "".to_string(),
-                    ));
-                }
-                if let Ok(hex) = i32::from_str_radix(&hex[2..], 16) {
-                    return Ok(Operand::IntegerValue(hex));
-                }
-                Err(ParsingError::new(
-                    ParsingError::InvalidHexLiteral,
-                    line,
-                    "".to_string(),
-                ))
-            }
-            c if c.len() == 3 && c.starts_with('\'') && c.ends_with('\'') => {
-                if register_only {
-                    return Err(ParsingError::new(
-                        ParsingError::InvalidCharLiteral,
-                        line,
-                        "".to_string(),
-                    ));
-                }
-                if let Some(value) = &c.chars().nth(1) {
-                    return Ok(Operand::IntegerValue(*value as i32));
-                }
-                Err(ParsingError::new(
-                    ParsingError::InvalidCharLiteral,
-                    line,
-                    "".to_string(),
-                ))
+            // literals, constants and expressions made of them
+            expression => {
+                let (value, warnings) = Expression::evaluate(expression, &self.constants, line)?;
+                self.warnings.extend(warnings);
+                Ok(Operand::IntegerValue(value))
             }
-
-            _ => Err(ParsingError::new(
-                ParsingError::InvalidOperandType,
-                line,
-                "".to_string(),
-            )),
         }
     }
 
-    pub fn parse(&mut self, program_text: &str) -> Result<Vec<Instruction>, ParsingError> {
-        let program_text = program_text.trim();
-
-        if program_text.is_empty() {
-            return Err(ParsingError::new(ParsingError::Empty, 0, "".to_string()));
+    /// Parses memory operand: general register or its alias in brackets, followed by optional
+    /// offset, which may be an expression, e.g. `[r1]`, `[r1+4]` or `[index - SIZE]`
+    fn parse_memory_operand(&mut self, text: &str, line: usize) -> Result<Operand, ParsingError> {
+        let invalid = |token: &str, column: usize| {
+            ParsingError::new(ParsingError::InvalidMemoryOperand, line, "".to_string())
+                .with_token(token, column)
+                .with_expected(t!("hint.memory"))
+        };
+        let inner = match text
+            .strip_prefix('[')
+            .and_then(|inner| inner.strip_suffix(']'))
+        {
+            Some(inner) => inner,
+            None => return Err(invalid(text, 0)),
+        };
+
+        let base_start = 1 + inner.len() - inner.trim_start().len();
+        let base_end = text[base_start..]
+            .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+            .map_or(text.len(), |end| base_start + end);
+        let base = &text[base_start..base_end];
+        if base.is_empty() {
+            return Err(invalid(text, 0));
         }
-
-        let program: Result<Vec<Instruction>, ParsingError> = program_text
-            .lines()
-            .enumerate()
-            .filter(|(_, line)| !line.trim().is_empty())
-            .filter(|(_, line)| !line.trim().starts_with('#'))
-            .map(|(current_line_number, line)| self.parse_instruction(line, current_line_number))
-            .collect();
-
-        program
+        let register = match self.parse_operand(base, line, true) {
+            Ok(Operand::GeneralRegister(register)) => register,
+            Ok(_) => return Err(invalid(base, base_start)),
+            Err(error) => return Err(error.with_token(base, base_start)),
+        };
+
+        let offset_text = text[base_end..text.len() - 1].trim();
+        let offset_start = base_end + text[base_end..].len() - text[base_end..].trim_start().len();
+        let (expression, expression_start) = match offset_text.chars().next() {
+            None => return Ok(Operand::Memory(register, 0)),
+            Some('+') => (&offset_text[1..], offset_start + 1),
+            Some('-') => (offset_text, offset_start),
+            Some(_) => return Err(invalid(offset_text, offset_start)),
+        };
+        let (offset, warnings) = Expression::evaluate(expression, &self.constants, line)
+            .map_err(|error| error.with_offset(expression_start))?;
+        self.warnings.extend(
+            warnings
+                .into_iter()
+                .map(|warning| warning.with_offset(expression_start)),
+        );
+        Ok(Operand::Memory(register, offset))
     }
-    // TODO: return error if label is already in use
-    /// Parses label
+
+    /// Parses operand, attaching its position in line to error and warnings
     ///
     /// ### Arguments
-    /// * 'name' &str - name of label
+    /// * 'token' (usize, &str) - byte offset of operand in line and its text
     /// * 'line' usize - line number
-    fn parse_label(&mut self, name: &str, line: usize) -> Result<Instruction, ParsingError> {
-        Ok(Instruction::new_label(
-            name[0..name.len() - 1].to_string(),
-            line,
-        ))
+    /// * 'register_only' bool - whether operand has to be a register
+    fn parse_operand_token(
+        &mut self,
+        token: (usize, &str),
+        line: usize,
+        register_only: bool,
+    ) -> Result<Operand, ParsingError> {
+        let (column, text) = token;
+        let first_warning = self.warnings.len();
+        let result = self
+            .parse_operand(text, line, register_only)
+            .map_err(|error| error.with_offset(column).with_token(text, column));
+        for warning in self.warnings[first_warning..].iter_mut() {
+            *warning = warning.clone().with_offset(column);
+        }
+        result
     }
 
-    fn parse_instruction(
-        &mut self,
-        line: &str,
-        current_line_number: usize,
-    ) -> Result<Instruction, ParsingError> {
-        let line_without_comments: &str = line.split('#').next().unwrap_or("").trim();
-        let words: Vec<&str> = line_without_comments.split_whitespace().collect();
-        if let Some(_instruction_word) = words.first() {
-            let operands = &words[1..];
-            let instruction_word = words[0];
+    /// Assembles program, stops at first error
+    pub fn parse(&mut self, program_text: &str) -> Result<Vec<Instruction>, ParsingError> {
+        let (program, diagnostics) = self.parse_with_diagnostics(program_text);
+
+        match diagnostics
+            .into_iter()
+            .find(|diagnostic| diagnostic.is_error())
+        {
+            Some(diagnostic) => Err(diagnostic.into_error()),
+            None => Ok(program),
+        }
+    }
 
-            match instruction_word {
-                "MOV" | "mov" => self.parse_binary_instruction(
-                    Opcode::MOV,
-                    operands,
-                    current_line_number,
-                    (false, true),
-                ),
+    /// Assembles program, collecting all errors and warnings instead of stopping at first one
+    ///
+    /// Lines that could not be assembled are skipped, so returned program is complete
+    /// only if there are no diagnostics with error severity
+    ///
+    /// ### Returns
+    /// * partially assembled program and list of diagnostics
+    pub fn parse_with_diagnostics(
+        &mut self,
+        program_text: &str,
+    ) -> (Vec<Instruction>, Vec<Diagnostic>) {
+        self.labels.clear();
+        self.literal_radixes.clear();
+        self.operand_aliases.clear();
+        self.constants.clear();
+        self.aliases.clear();
+        self.scoped_aliases = None;
+        self.macros.clear();
+        self.warnings.clear();
+
+        if program_text.trim().is_empty() {
+            let error = ParsingError::new(ParsingError::Empty, 0, "".to_string());
+            return (Vec::new(), vec![Diagnostic::error(error, "")]);
+        }
 
-                "ADD" | "add" => {
-                    self.parse_unary_instruction(Opcode::ADD, operands, current_line_number)
-                }
-                "SUB" | "sub" => {
-                    self.parse_unary_instruction(Opcode::SUB, operands, current_line_number)
-                }
-                "MUL" | "mul" => {
-                    self.parse_unary_instruction(Opcode::MUL, operands, current_line_number)
-                }
-                "DIV" | "div" => {
-                    self.parse_unary_instruction(Opcode::DIV, operands, current_line_number)
+        // built-in symbols, 1 if feature is present, 0 otherwise
+        self.constants
+            .insert(STACK_SYMBOL.to_string(), self.profile.has_stack() as i32);
+
+        let source_lines: Vec<&str> = program_text.lines().collect();
+
+        // Directives, includes and macros are processed before assembling instructions,
+        // so that labels in included files and macros are declared like any other label
+        let mut preprocessing = Preprocessing::default();
+        self.preprocess(None, &source_lines, &mut preprocessing);
+        let Preprocessing {
+            lines,
+            mut diagnostics,
+            ..
+        } = preprocessing;
+
+        // First pass - assembling instructions, each one paired with index of its line
+        //
+        // Line numbers are counted in untrimmed text so they match the source,
+        // index is position of instruction in assembled program
+        let mut program: Vec<(usize, Instruction)> = Vec::new();
+        for (line_index, source_line) in lines.iter().enumerate() {
+            let line = source_line.text.as_str();
+            let current_line_number = source_line.line;
+            if line.trim().is_empty() || line.trim().starts_with('#') {
+                continue;
+            }
+            // aliases are scoped by labels, so they are defined in order with instructions
+            if Assembler::is_directive(line) {
+                if let Err(error) = self.parse_directive(line, current_line_number) {
+                    diagnostics.push(source_line.diagnostic(Severity::Error, error));
                 }
-                "MOD" | "mod" => {
-                    self.parse_unary_instruction(Opcode::MOD, operands, current_line_number)
+                continue;
+            }
+            match self.parse_instruction(line, current_line_number, program.len()) {
+                Ok(instruction) => {
+                    if let Instruction::Label(name, _, _) = &instruction {
+                        if Assembler::starts_alias_scope(name) {
+                            self.scoped_aliases = Some(HashMap::new());
+                        }
+                    }
+                    self.literal_radixes.push(Assembler::literal_radixes(line));
+                    self.operand_aliases.push(self.aliases_in_line(line));
+                    program.push((line_index, instruction));
                 }
+                Err(error) => diagnostics.push(source_line.diagnostic(Severity::Error, error)),
+            }
+            Assembler::drain_warnings(&mut self.warnings, source_line, &mut diagnostics);
+        }
 
-                "INC" | "inc" => Ok(Instruction::new(Opcode::INC)),
+        // Second pass - building symbol table and checking jump targets, jumps keep label names
+        diagnostics.extend(self.resolve_labels(&mut program, &lines));
 
-                "DEC" | "dec" => Ok(Instruction::new(Opcode::DEC)),
+        let program = program
+            .into_iter()
+            .map(|(_, instruction)| instruction)
+            .collect();
+        (program, diagnostics)
+    }
 
-                "AND" | "and" => {
-                    self.parse_unary_instruction(Opcode::AND, operands, current_line_number)
-                }
-                "OR" | "or" => {
-                    self.parse_unary_instruction(Opcode::OR, operands, current_line_number)
-                }
-                "XOR" | "xor" => {
-                    self.parse_unary_instruction(Opcode::XOR, operands, current_line_number)
-                }
-                "NOT" | "not" => Ok(Instruction::new(Opcode::NOT)),
+    /// Turns warnings produced for line into diagnostics
+    fn drain_warnings(
+        warnings: &mut Vec<ParsingError>,
+        source_line: &SourceLine,
+        diagnostics: &mut Vec<Diagnostic>,
+    ) {
+        diagnostics.extend(
+            warnings
+                .drain(..)
+                .map(|warning| source_line.diagnostic(Severity::Warning, warning)),
+        );
+    }
 
-                "SHL" | "shl" => {
-                    self.parse_unary_instruction(Opcode::SHL, operands, current_line_number)
-                }
-                "SHR" | "shr" => {
-                    self.parse_unary_instruction(Opcode::SHR, operands, current_line_number)
+    /// Processes directives, includes and macros in source file, collecting macro definitions
+    ///
+    /// Macro has to be defined before it is invoked
+    ///
+    /// ### Arguments
+    /// * 'file' Option<&str> - name of included file, None for main program
+    /// * 'source_lines' &[&str] - lines of source code
+    /// * 'state' &mut Preprocessing - lines of code to be assembled and diagnostics are appended to it
+    fn preprocess(&mut self, file: Option<&str>, source_lines: &[&str], state: &mut Preprocessing) {
+        let first_word = |line: &str| {
+            Assembler::split_words(line)
+                .first()
+                .map(|(_, word)| word.to_string())
+        };
+        let conditions_depth = state.conditions.len();
+
+        let mut i = 0;
+        while i < source_lines.len() {
+            let source_line = SourceLine::new(file, i, source_lines[i]);
+            let words = Assembler::split_operands(source_lines[i]);
+            if let Some(&(column, ".macro")) = words.first() {
+                // nested definitions are not allowed, so body ends at first .endm or .macro
+                let end = (i + 1..source_lines.len()).find(|&j| {
+                    matches!(
+                        first_word(source_lines[j]).as_deref(),
+                        Some(".endm" | ".macro")
+                    )
+                });
+                let result = match end {
+                    Some(end) if first_word(source_lines[end]).as_deref() == Some(".endm") => {
+                        let body = (i + 1..end)
+                            .map(|j| (j, source_lines[j].to_string()))
+                            .collect();
+                        i = end + 1;
+                        match state.is_active() {
+                            true => self.define_macro(&words[1..], source_line.line, body),
+                            false => Ok(()),
+                        }
+                    }
+                    end => {
+                        i = end.unwrap_or(source_lines.len());
+                        Err(ParsingError::new(
+                            ParsingError::UnterminatedMacro,
+                            source_line.line,
+                            "".to_string(),
+                        )
+                        .with_token(".macro", column)
+                        .with_expected(".endm".to_string()))
+                    }
+                };
+                if let Err(error) = result {
+                    state
+                        .diagnostics
+                        .push(source_line.diagnostic(Severity::Error, error));
                 }
+                continue;
+            }
 
-                "CMP" | "cmp" => self.parse_binary_instruction(
-                    Opcode::CMP,
-                    operands,
-                    current_line_number,
-                    (false, false),
-                ),
+            if let Err(error) = self.preprocess_line(&source_line, 0, state) {
+                state
+                    .diagnostics
+                    .push(source_line.diagnostic(Severity::Error, error));
+            }
+            i += 1;
+        }
 
-                "JE" | "je" => self.parse_jump(Opcode::JE, operands, current_line_number),
-                "JNE" | "jne" => self.parse_jump(Opcode::JNE, operands, current_line_number),
-                "JL" | "jl" => self.parse_jump(Opcode::JL, operands, current_line_number),
-                "JG" | "jg" => self.parse_jump(Opcode::JG, operands, current_line_number),
-                "JMP" | "jmp" => self.parse_jump(Opcode::JMP, operands, current_line_number),
+        // conditional blocks have to end in file they were started in
+        for condition in state.conditions.split_off(conditions_depth) {
+            let (column, directive) = condition.directive;
+            let error = ParsingError::new(
+                ParsingError::UnterminatedCondition,
+                condition.source_line.line,
+                "".to_string(),
+            )
+            .with_token(&directive, column)
+            .with_expected(".endif".to_string());
+            state
+                .diagnostics
+                .push(condition.source_line.diagnostic(Severity::Error, error));
+        }
+    }
 
-                "HLT" | "hlt" => Ok(Instruction::new(Opcode::HLT)),
-                "NOP" | "nop" => Ok(Instruction::new(Opcode::NOP)),
+    /// Processes single line: conditional directive, directive, include or macro invocation,
+    /// other lines are appended to lines to be assembled
+    ///
+    /// ### Arguments
+    /// * 'source_line' &SourceLine - line of source code or macro expansion
+    /// * 'depth' usize - number of macros line is nested in
+    /// * 'state' &mut Preprocessing - state of preprocessing
+    fn preprocess_line(
+        &mut self,
+        source_line: &SourceLine,
+        depth: usize,
+        state: &mut Preprocessing,
+    ) -> Result<(), ParsingError> {
+        let line = source_line.line;
+        let words = Assembler::split_operands(&source_line.text);
+
+        let result = match words.first() {
+            Some(&(column, directive @ (".if" | ".ifdef" | ".ifndef" | ".else" | ".endif"))) => {
+                self.parse_condition((column, directive), &words[1..], source_line, state)
+            }
+            // lines in conditional blocks that are not assembled are skipped
+            _ if !state.is_active() => Ok(()),
+            Some(&(column, ".endm")) => {
+                Err(
+                    ParsingError::new(ParsingError::UnmatchedEndMacro, line, "".to_string())
+                        .with_token(".endm", column),
+                )
+            }
+            Some(&(_, ".include")) => self.include(&words[1..], source_line, state),
+            Some(&(_, ".alias")) => {
+                state.lines.push(source_line.clone());
+                Ok(())
+            }
+            Some(&(_, name)) if self.macros.contains_key(name) => {
+                self.expand_macro(name, &words[1..], source_line, depth, state)
+            }
+            _ if Assembler::is_directive(&source_line.text) => {
+                self.parse_directive(&source_line.text, line)
+            }
+            _ => {
+                state.lines.push(source_line.clone());
+                Ok(())
+            }
+        };
+        Assembler::drain_warnings(&mut self.warnings, source_line, &mut state.diagnostics);
+        result
+    }
 
-                "PHS" | "psh" => {
-                    if self.stack_present {
-                        self.parse_unary_instruction(Opcode::PSH, operands, current_line_number)
-                    } else {
-                        Err(ParsingError::new(
-                            ParsingError::StackNotPresent,
-                            current_line_number,
-                            "stack not present".to_owned(),
-                        ))
-                    }
-                }
-                "POP" | "pop" => {
-                    if self.stack_present {
-                        self.parse_unary_instruction(Opcode::POP, operands, current_line_number)
-                    } else {
-                        Err(ParsingError::new(
-                            ParsingError::StackNotPresent,
-                            current_line_number,
-                            "stack not present".to_owned(),
-                        ))
+    /// Parses conditional directive: .if expression, .ifdef NAME, .ifndef NAME, .else or .endif
+    ///
+    /// Expression is true if it is not 0, condition is evaluated only if block it is in is assembled
+    ///
+    /// ### Arguments
+    /// * 'directive' (usize, &str) - directive with its column
+    /// * 'arguments' &[(usize, &str)] - arguments of directive, with their columns
+    /// * 'source_line' &SourceLine - line of directive
+    /// * 'state' &mut Preprocessing - state of preprocessing
+    fn parse_condition(
+        &mut self,
+        directive: (usize, &str),
+        arguments: &[(usize, &str)],
+        source_line: &SourceLine,
+        state: &mut Preprocessing,
+    ) -> Result<(), ParsingError> {
+        let line = source_line.line;
+        let (column, name) = directive;
+        let unmatched = || {
+            ParsingError::new(ParsingError::UnmatchedCondition, line, "".to_string())
+                .with_token(name, column)
+        };
+
+        match name {
+            ".else" => {
+                Assembler::check_operand_count(arguments, 0, line)?;
+                match state.conditions.last_mut() {
+                    Some(condition) if !condition.else_seen => {
+                        condition.else_seen = true;
+                        condition.active = condition.parent_active && !condition.active;
+                        Ok(())
                     }
+                    _ => Err(unmatched()),
                 }
-
-                label if label.ends_with(':') => self.parse_label(label, current_line_number),
-                _ => Err(ParsingError::new(
-                    ParsingError::NoSuchInstruction,
-                    current_line_number,
-                    "".to_string(),
-                )),
             }
-        } else {
-            Err(ParsingError::new(
-                ParsingError::Empty,
-                current_line_number,
-                "FIXME".to_string(),
-            ))
+            ".endif" => {
+                Assembler::check_operand_count(arguments, 0, line)?;
+                state.conditions.pop().map(|_| ()).ok_or_else(unmatched)
+            }
+            _ => {
+                let parent_active = state.is_active();
+                let mut condition = Condition {
+                    source_line: source_line.clone(),
+                    directive: (column, name.to_string()),
+                    parent_active,
+                    active: false,
+                    else_seen: false,
+                };
+                let result = match parent_active {
+                    true => self.evaluate_condition(name, arguments, line),
+                    false => Ok(false),
+                };
+                // block is opened even if condition is invalid, so that .else and .endif match
+                condition.active = parent_active && *result.as_ref().unwrap_or(&false);
+                state.conditions.push(condition);
+                result.map(|_| ())
+            }
         }
     }
 
-    fn parse_binary_instruction(
+    /// Evaluates condition of .if, .ifdef or .ifndef directive
+    fn evaluate_condition(
         &mut self,
-        opcode: fn(Operand, Operand) -> Opcode,
-        operands: &[&str],
+        directive: &str,
+        arguments: &[(usize, &str)],
         line: usize,
-        register_only: (bool, bool),
-    ) -> Result<Instruction, ParsingError> {
-        if operands.len() != 2 {
-            return Err(ParsingError::new(
-                ParsingError::NotEnoughOperands,
-                line,
-                "".to_string(),
-            ));
+    ) -> Result<bool, ParsingError> {
+        Assembler::check_operand_count(arguments, 1, line)?;
+        let (column, text) = arguments[0];
+        match directive {
+            ".ifdef" => Ok(self.is_defined(text)),
+            ".ifndef" => Ok(!self.is_defined(text)),
+            _ => match self.parse_operand_token(arguments[0], line, false)? {
+                Operand::IntegerValue(value) => Ok(value != 0),
+                _ => Err(
+                    ParsingError::new(ParsingError::InvalidOperandType, line, "".to_string())
+                        .with_token(text, column)
+                        .with_expected(t!("hint.integer")),
+                ),
+            },
         }
-        let (ro1, ro2) = register_only;
-        let operand1 = self.parse_operand(operands[0], line, ro1)?;
-        let operand2 = self.parse_operand(operands[1], line, ro2)?;
-        Ok(Instruction::new(opcode(operand1, operand2)))
     }
 
-    fn parse_unary_instruction(
+    /// Includes file: .include name
+    ///
+    /// ### Arguments
+    /// * 'arguments' &[(usize, &str)] - name of file, optionally in quotes, with its column
+    /// * 'source_line' &SourceLine - line of .include directive
+    /// * 'state' &mut Preprocessing - state of preprocessing
+    fn include(
         &mut self,
-        opcode: fn(Operand) -> Opcode,
-        operands: &[&str],
-        line: usize,
-    ) -> Result<Instruction, ParsingError> {
-        if operands.len() > 1 {
-            return Err(ParsingError::new(
-                ParsingError::TooManyOperands,
-                line,
-                "".to_string(),
-            ));
+        arguments: &[(usize, &str)],
+        source_line: &SourceLine,
+        state: &mut Preprocessing,
+    ) -> Result<(), ParsingError> {
+        let line = source_line.line;
+        Assembler::check_operand_count(arguments, 1, line)?;
+        let (column, argument) = arguments[0];
+        let name = argument.trim_matches('"');
+
+        let not_found = || {
+            ParsingError::new(ParsingError::IncludeNotFound, line, name.to_string())
+                .with_token(argument, column)
+        };
+        let resolver = self.resolver.as_ref().ok_or_else(not_found)?;
+        // nested includes are resolved relative to file including them
+        let including = state
+            .include_stack
+            .last()
+            .map(|including| including.as_str());
+        let canonical_name = resolver
+            .canonical_name(name, including)
+            .ok_or_else(not_found)?;
+        let text = resolver.resolve(&canonical_name).ok_or_else(not_found)?;
+
+        if state.include_stack.contains(&canonical_name) {
+            return Err(
+                ParsingError::new(ParsingError::IncludeCycle, line, name.to_string())
+                    .with_token(argument, column),
+            );
         }
-        if operands.is_empty() {
-            return Err(ParsingError::new(
-                ParsingError::NotEnoughOperands,
-                line,
-                "".to_string(),
-            ));
+        if state.include_stack.len() >= MAX_INCLUDE_DEPTH {
+            return Err(
+                ParsingError::new(ParsingError::IncludeTooDeep, line, name.to_string())
+                    .with_token(argument, column),
+            );
         }
 
-        let operand = self.parse_operand(operands[0], line, false)?;
-        Ok(Instruction::new(opcode(operand)))
+        state.include_stack.push(canonical_name);
+        let source_lines: Vec<&str> = text.lines().collect();
+        self.preprocess(Some(name), &source_lines, state);
+        state.include_stack.pop();
+        Ok(())
     }
 
-    fn parse_jump(
+    /// Defines macro: .macro name a, b
+    ///
+    /// ### Arguments
+    /// * 'arguments' &[(usize, &str)] - name and parameters of macro, with their columns
+    /// * 'line' usize - line number
+    /// * 'body' Vec<(usize, String)> - lines of macro body paired with their line numbers
+    fn define_macro(
         &mut self,
-        opcode: fn(String) -> Opcode,
-        operands: &[&str],
+        arguments: &[(usize, &str)],
         line: usize,
-    ) -> Result<Instruction, ParsingError> {
-        if operands.is_empty() {
-            return Err(ParsingError::new(
-                ParsingError::NotEnoughOperands,
-                line,
-                "".to_string(),
-            ));
-        } else if operands.len() > 1 {
-            return Err(ParsingError::new(
-                ParsingError::TooManyOperands,
-                line,
-                "".to_string(),
-            ));
+        body: Vec<(usize, String)>,
+    ) -> Result<(), ParsingError> {
+        let (name_column, name) = match arguments.first() {
+            Some(&argument) => argument,
+            None => {
+                return Err(ParsingError::new(
+                    ParsingError::NotEnoughOperands,
+                    line,
+                    "".to_string(),
+                )
+                .with_expected(t!("hint.macro_name")))
+            }
+        };
+        if self.macros.contains_key(name) {
+            return Err(
+                ParsingError::new(ParsingError::DuplicateMacro, line, name.to_string())
+                    .with_token(name, name_column),
+            );
         }
-        let label = operands[0];
-        Ok(Instruction::new(opcode(label.to_string())))
-    }
-    /// Sets language for parsing error messages
-    pub fn set_language(&mut self, language: Language) {
-        rust_i18n::set_locale(language.string_code());
-    }
+
+        let mut parameters: Vec<String> = Vec::new();
+        for &(column, text) in arguments {
+            if !Assembler::is_identifier(text)
+                || Assembler::is_reserved_name(text)
+                || parameters.iter().any(|parameter| parameter == text)
+            {
+                return Err(ParsingError::new(
+                    ParsingError::InvalidMacroName,
+                    line,
+                    "".to_string(),
+                )
+                .with_token(text, column)
+                .with_expected(t!("hint.macro_name")));
+            }
+            parameters.push(text.to_string());
+        }
+        parameters.remove(0);
+
+        self.macros
+            .insert(name.to_string(), Macro::new(name, parameters, body));
+        Ok(())
+    }
+
+    /// Expands macro invocation, macros invoked in its body are expanded too
+    ///
+    /// ### Arguments
+    /// * 'name' &str - name of macro
+    /// * 'arguments' &[(usize, &str)] - arguments of invocation, with their columns
+    /// * 'call' &SourceLine - line of invocation
+    /// * 'depth' usize - number of macros this invocation is nested in
+    /// * 'state' &mut Preprocessing - state of preprocessing
+    fn expand_macro(
+        &mut self,
+        name: &str,
+        arguments: &[(usize, &str)],
+        call: &SourceLine,
+        depth: usize,
+        state: &mut Preprocessing,
+    ) -> Result<(), ParsingError> {
+        if depth >= MAX_MACRO_DEPTH {
+            return Err(ParsingError::new(
+                ParsingError::MacroRecursion,
+                call.line,
+                name.to_string(),
+            ));
+        }
+        let definition = &self.macros[name];
+        Assembler::check_operand_count(arguments, definition.get_parameters().len(), call.line)?;
+        state.expansions += 1;
+
+        let arguments: Vec<&str> = arguments.iter().map(|(_, argument)| *argument).collect();
+        let suffix = format!("@{}", state.expansions);
+        for (body_line, text) in definition.expand(&arguments, &suffix) {
+            // expanded line points at outermost invocation, like the line of invocation
+            let expanded_line = SourceLine {
+                text,
+                expansion: Some((name.to_string(), body_line)),
+                ..call.clone()
+            };
+            if let Err(error) = self.preprocess_line(&expanded_line, depth + 1, state) {
+                state
+                    .diagnostics
+                    .push(expanded_line.diagnostic(Severity::Error, error));
+            }
+        }
+        Ok(())
+    }
+
+    /// Builds symbol table and checks that every jump targets declared label
+    ///
+    /// Jump opcodes are not rewritten to resolved indices, vm finds target through
+    /// index stored in label instruction
+    ///
+    /// ### Arguments
+    /// * 'program' &[(usize, Instruction)] - assembled instructions paired with indexes of their lines
+    /// * 'lines' &[SourceLine] - lines of code after preprocessing
+    ///
+    /// ### Returns
+    /// * diagnostics for duplicate, undefined and unused labels
+    fn resolve_labels(
+        &mut self,
+        program: &mut [(usize, Instruction)],
+        lines: &[SourceLine],
+    ) -> Vec<Diagnostic> {
+        let mut diagnostics = Vec::new();
+        let written_names = Assembler::qualify_local_labels(program);
+        let program = &*program;
+
+        for ((line_index, instruction), written_name) in program.iter().zip(&written_names) {
+            let source_line = &lines[*line_index];
+            if let Instruction::Label(name, _, index) = instruction {
+                if self.labels.contains_key(name) {
+                    let error = ParsingError::new(
+                        ParsingError::DuplicateLabel,
+                        source_line.line,
+                        name.clone(),
+                    );
+                    let error = Assembler::locate_word(
+                        error,
+                        &source_line.text,
+                        &format!("{}:", written_name),
+                    );
+                    diagnostics.push(source_line.diagnostic(Severity::Error, error));
+                    continue;
+                }
+                self.labels.insert(name.clone(), *index);
+            }
+        }
+
+        let mut used_labels: HashSet<String> = HashSet::new();
+        for ((line_index, instruction), written_name) in program.iter().zip(&written_names) {
+            let source_line = &lines[*line_index];
+            if let Some(label) = instruction.get_opcode().and_then(|o| o.get_label()) {
+                if !self.labels.contains_key(&label) {
+                    let error = ParsingError::new(
+                        ParsingError::UndefinedLabel,
+                        source_line.line,
+                        label.clone(),
+                    );
+                    let error = Assembler::locate_word(error, &source_line.text, written_name);
+                    diagnostics.push(source_line.diagnostic(Severity::Error, error));
+                    continue;
+                }
+                used_labels.insert(label);
+            }
+        }
+
+        for ((line_index, instruction), written_name) in program.iter().zip(&written_names) {
+            let source_line = &lines[*line_index];
+            if let Instruction::Label(name, _, _) = instruction {
+                if !used_labels.contains(name) {
+                    let error = ParsingError::new(
+                        ParsingError::UnusedLabel,
+                        source_line.line,
+                        name.clone(),
+                    );
+                    let error = Assembler::locate_word(
+                        error,
+                        &source_line.text,
+                        &format!("{}:", written_name),
+                    );
+                    diagnostics.push(source_line.diagnostic(Severity::Warning, error));
+                }
+            }
+        }
+        diagnostics
+    }
+
+    /// Qualifies local labels (starting with '.') and jumps to them with the most recent
+    /// global label, so `.loop` following `main:` becomes `main.loop`
+    ///
+    /// Labels already qualified, like `main.loop`, do not start new scope,
+    /// so disassembled program assembles back to the same program.
+    /// Neither do labels created by macro expansion, like `again@1`
+    ///
+    /// ### Returns
+    /// * label names as written in source, for each instruction (empty if it has no label)
+    fn qualify_local_labels(program: &mut [(usize, Instruction)]) -> Vec<String> {
+        let mut scope: Option<String> = None;
+        let qualify = |name: &mut String, scope: &Option<String>| {
+            let written_name = name.clone();
+            if let (true, Some(scope)) = (name.starts_with('.'), scope) {
+                *name = format!("{}{}", scope, name);
+            }
+            written_name
+        };
+
+        program
+            .iter_mut()
+            .map(|(_, instruction)| match instruction {
+                Instruction::Label(name, _, _) => {
+                    if Assembler::is_global_label(name) {
+                        scope = Some(name.clone());
+                    }
+                    qualify(name, &scope)
+                }
+                Instruction::Opcode(opcode) => match opcode.get_label_mut() {
+                    Some(label) => qualify(label, &scope),
+                    None => String::new(),
+                },
+            })
+            .collect()
+    }
+
+    /// Attaches position of word in source line to error, if word is found
+    fn locate_word(error: ParsingError, source_line: &str, word: &str) -> ParsingError {
+        match Assembler::split_words(source_line)
+            .into_iter()
+            .find(|(_, text)| *text == word)
+        {
+            Some((column, text)) => error.with_token(text, column),
+            None => error,
+        }
+    }
+
+    /// Recognizes radixes of operands in line, None for operands that are not integer literals
+    fn literal_radixes(line: &str) -> Vec<Option<Radix>> {
+        Assembler::split_operands(line)
+            .iter()
+            .skip(1)
+            .map(|(_, word)| Radix::of_literal(word))
+            .collect()
+    }
+
+    /// Gets radixes of operands, for each instruction assembled during last parsing
+    ///
+    /// Used by disassembler to print literals the way they were written
+    pub fn get_literal_radixes(&self) -> Vec<Vec<Option<Radix>>> {
+        self.literal_radixes.clone()
+    }
+
+    /// Gets constants defined during last parsing, without built-in symbols
+    pub fn get_constants(&self) -> HashMap<String, i32> {
+        let mut constants = self.constants.clone();
+        constants.remove(STACK_SYMBOL);
+        constants
+    }
+
+    /// Checks if symbol is defined for .ifdef and .ifndef
+    ///
+    /// Built-in symbols always have value, but they are defined only if feature is present
+    fn is_defined(&self, name: &str) -> bool {
+        match name {
+            STACK_SYMBOL => self.profile.has_stack(),
+            _ => self.constants.contains_key(name),
+        }
+    }
+
+    /// Parses directive - line starting with '.', directives do not produce instructions
+    ///
+    /// ### Arguments
+    /// * 'line' &str - line of code
+    /// * 'line_number' usize - line number
+    fn parse_directive(&mut self, line: &str, line_number: usize) -> Result<(), ParsingError> {
+        let words = Assembler::split_operands(line);
+        let (column, directive) = words[0];
+        let arguments = &words[1..];
+
+        match directive {
+            ".equ" | ".define" => self.parse_constant(arguments, line_number),
+            ".alias" => self.parse_alias(arguments, line_number),
+            _ => Err(
+                ParsingError::new(ParsingError::NoSuchDirective, line_number, "".to_string())
+                    .with_token(directive, column)
+                    .with_expected(t!("hint.directive")),
+            ),
+        }
+    }
+
+    /// Parses constant definition: .equ NAME value
+    ///
+    /// ### Arguments
+    /// * 'arguments' &[(usize, &str)] - name and value of constant, with their columns
+    /// * 'line' usize - line number
+    fn parse_constant(
+        &mut self,
+        arguments: &[(usize, &str)],
+        line: usize,
+    ) -> Result<(), ParsingError> {
+        Assembler::check_operand_count(arguments, 2, line)?;
+        let (name_column, name) = arguments[0];
+
+        if !Assembler::is_identifier(name) || Assembler::is_reserved_name(name) {
+            return Err(
+                ParsingError::new(ParsingError::InvalidConstantName, line, "".to_string())
+                    .with_token(name, name_column)
+                    .with_expected(t!("hint.constant_name")),
+            );
+        }
+        if self.constants.contains_key(name) {
+            return Err(
+                ParsingError::new(ParsingError::DuplicateConstant, line, name.to_string())
+                    .with_token(name, name_column),
+            );
+        }
+
+        let (value_column, value_text) = arguments[1];
+        let value = match self.parse_operand_token(arguments[1], line, false)? {
+            Operand::IntegerValue(value) => value,
+            _ => {
+                return Err(ParsingError::new(
+                    ParsingError::InvalidOperandType,
+                    line,
+                    "".to_string(),
+                )
+                .with_token(value_text, value_column)
+                .with_expected(t!("hint.integer")))
+            }
+        };
+        self.constants.insert(name.to_string(), value);
+        Ok(())
+    }
+
+    /// Parses register alias: .alias NAME register
+    ///
+    /// Alias defined before first label is visible in whole program,
+    /// alias defined after a label is visible until next global label
+    ///
+    /// ### Arguments
+    /// * 'arguments' &[(usize, &str)] - name of alias and register, with their columns
+    /// * 'line' usize - line number
+    fn parse_alias(
+        &mut self,
+        arguments: &[(usize, &str)],
+        line: usize,
+    ) -> Result<(), ParsingError> {
+        Assembler::check_operand_count(arguments, 2, line)?;
+        let (name_column, name) = arguments[0];
+
+        if Assembler::is_reserved_name(name) {
+            return Err(ParsingError::new(
+                ParsingError::AliasShadowsRegister,
+                line,
+                name.to_string(),
+            )
+            .with_token(name, name_column));
+        }
+        if !Assembler::is_identifier(name) {
+            return Err(
+                ParsingError::new(ParsingError::InvalidAliasName, line, "".to_string())
+                    .with_token(name, name_column)
+                    .with_expected(t!("hint.alias_name")),
+            );
+        }
+
+        let register = self.parse_operand_token(arguments[1], line, true)?;
+        if let Operand::Memory(_, _) = register {
+            let (column, text) = arguments[1];
+            return Err(
+                ParsingError::new(ParsingError::InvalidOperandType, line, "".to_string())
+                    .with_token(text, column),
+            );
+        }
+        let aliases = self.scoped_aliases.as_mut().unwrap_or(&mut self.aliases);
+        if aliases.contains_key(name) {
+            return Err(
+                ParsingError::new(ParsingError::DuplicateAlias, line, name.to_string())
+                    .with_token(name, name_column),
+            );
+        }
+        aliases.insert(name.to_string(), register);
+        Ok(())
+    }
+
+    /// Gets register alias refers to, aliases in current scope shadow global ones
+    fn get_alias(&self, name: &str) -> Option<&Operand> {
+        self.scoped_aliases
+            .as_ref()
+            .and_then(|aliases| aliases.get(name))
+            .or_else(|| self.aliases.get(name))
+    }
+
+    /// Checks if label ends scope of aliases, labels created by macro expansion
+    /// and local labels do not
+    pub(crate) fn starts_alias_scope(name: &str) -> bool {
+        Assembler::is_global_label(name)
+    }
+
+    /// Checks if label is global label written in source, which starts scope of local labels
+    fn is_global_label(name: &str) -> bool {
+        !name.contains('.') && !name.contains('@')
+    }
+
+    /// Finds operands in line written as aliases, None for operands that are not aliases
+    fn aliases_in_line(&self, line: &str) -> Vec<Option<String>> {
+        Assembler::split_operands(line)
+            .iter()
+            .skip(1)
+            .map(|(_, word)| self.get_alias(word).map(|_| word.to_string()))
+            .collect()
+    }
+
+    /// Gets names of aliases operands were written with, for each instruction assembled
+    /// during last parsing
+    ///
+    /// Used by disassembler to print registers by their aliases
+    pub fn get_operand_aliases(&self) -> Vec<Vec<Option<String>>> {
+        self.operand_aliases.clone()
+    }
+
+    /// Parses breakpoint condition, e.g. `acc == 42` or `[counter] >= LIMIT`
+    ///
+    /// Operands are written like operands of instructions, so they may use constants
+    /// and aliases visible at the end of last parsed program
+    ///
+    /// ### Arguments
+    /// * 'text' - two operands separated by ==, !=, <, <=, > or >=
+    pub fn parse_breakpoint_condition(
+        &mut self,
+        text: &str,
+    ) -> Result<breakpoint::Condition, ParsingError> {
+        const COMPARISONS: [(&str, Comparison); 6] = [
+            ("==", Comparison::Equal),
+            ("!=", Comparison::NotEqual),
+            ("<=", Comparison::LesserOrEqual),
+            (">=", Comparison::GreaterOrEqual),
+            ("<", Comparison::Lesser),
+            (">", Comparison::Greater),
+        ];
+        let mut position = 0;
+        let mut found = None;
+        while position < text.len() && found.is_none() {
+            let rest = &text[position..];
+            // shifts in expressions are not comparisons
+            if rest.starts_with("<<") || rest.starts_with(">>") {
+                position += 2;
+                continue;
+            }
+            found = COMPARISONS
+                .iter()
+                .find(|(symbol, _)| rest.starts_with(symbol))
+                .map(|&(symbol, comparison)| (position, symbol.len(), comparison));
+            position += rest.chars().next().map_or(1, char::len_utf8);
+        }
+        let Some((start, length, comparison)) = found else {
+            return Err(
+                ParsingError::new(ParsingError::InvalidExpression, 0, "".to_string())
+                    .with_token(text, 0)
+                    .with_expected(t!("hint.condition")),
+            );
+        };
+
+        let operand = |assembler: &mut Assembler, from: usize, to: usize| {
+            let part = &text[from..to];
+            let column = from + part.len() - part.trim_start().len();
+            assembler.parse_operand_token((column, part.trim()), 0, false)
+        };
+        let operand1 = operand(self, 0, start)?;
+        let operand2 = operand(self, start + length, text.len())?;
+        Ok(breakpoint::Condition::new(operand1, comparison, operand2))
+    }
+
+    /// Checks if line is a directive - starts with '.' but is not a local label
+    fn is_directive(line: &str) -> bool {
+        let code = line.split('#').next().unwrap_or("").trim();
+        code.starts_with('.') && !code.ends_with(':')
+    }
+
+    /// Checks if text can be used as name, letters, digits and '_', not starting with digit
+    fn is_identifier(text: &str) -> bool {
+        let mut chars = text.chars();
+        match chars.next() {
+            Some(c) if c.is_ascii_alphabetic() || c == '_' => {
+                chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+            }
+            _ => false,
+        }
+    }
+
+    /// Checks if text looks like general register (r followed by number)
+    fn is_register_name(text: &str) -> bool {
+        text.starts_with('r') && text.len() > 1 && text[1..].chars().all(|c| c.is_ascii_digit())
+    }
+
+    /// Checks if text looks like port (p followed by number)
+    fn is_port_name(text: &str) -> bool {
+        text.starts_with('p') && text.len() > 1 && text[1..].chars().all(|c| c.is_ascii_digit())
+    }
+
+    /// Checks if name is reserved for registers
+    fn is_reserved_name(name: &str) -> bool {
+        matches!(name, "acc" | "pc")
+            || Assembler::is_register_name(name)
+            || Assembler::is_port_name(name)
+    }
+
+    /// Gets symbol table built during last parsing, label name mapped to its index in program
+    pub fn get_labels(&self) -> HashMap<String, usize> {
+        self.labels.clone()
+    }
+
+    /// Parses label
+    ///
+    /// ### Arguments
+    /// * 'name' &str - name of label
+    /// * 'line' usize - line number
+    /// * 'index' usize - index of label in program
+    fn parse_label(
+        &mut self,
+        name: &str,
+        line: usize,
+        index: usize,
+    ) -> Result<Instruction, ParsingError> {
+        Ok(Instruction::new_label(
+            name[0..name.len() - 1].to_string(),
+            line,
+            index,
+        ))
+    }
+
+    fn parse_instruction(
+        &mut self,
+        line: &str,
+        current_line_number: usize,
+        index: usize,
+    ) -> Result<Instruction, ParsingError> {
+        let words = Assembler::split_operands(line);
+        if let Some(&(instruction_column, instruction_word)) = words.first() {
+            let operands = &words[1..];
+
+            match instruction_word {
+                "MOV" | "mov" => self.parse_binary_instruction(
+                    Opcode::MOV,
+                    operands,
+                    current_line_number,
+                    (false, true),
+                ),
+
+                "ADD" | "add" => {
+                    self.parse_unary_instruction(Opcode::ADD, operands, current_line_number)
+                }
+                "SUB" | "sub" => {
+                    self.parse_unary_instruction(Opcode::SUB, operands, current_line_number)
+                }
+                "MUL" | "mul" => {
+                    self.parse_unary_instruction(Opcode::MUL, operands, current_line_number)
+                }
+                "DIV" | "div" => {
+                    self.parse_unary_instruction(Opcode::DIV, operands, current_line_number)
+                }
+                "MOD" | "mod" => {
+                    self.parse_unary_instruction(Opcode::MOD, operands, current_line_number)
+                }
+
+                "INC" | "inc" => Ok(Instruction::new(Opcode::INC)),
+
+                "DEC" | "dec" => Ok(Instruction::new(Opcode::DEC)),
+
+                "NEG" | "neg" => Ok(Instruction::new(Opcode::NEG)),
+                "ABS" | "abs" => Ok(Instruction::new(Opcode::ABS)),
+                "MIN" | "min" => {
+                    self.parse_unary_instruction(Opcode::MIN, operands, current_line_number)
+                }
+                "MAX" | "max" => {
+                    self.parse_unary_instruction(Opcode::MAX, operands, current_line_number)
+                }
+                "CLAMP" | "clamp" => self.parse_binary_instruction(
+                    Opcode::CLAMP,
+                    operands,
+                    current_line_number,
+                    (false, false),
+                ),
+
+                "AND" | "and" => {
+                    self.parse_unary_instruction(Opcode::AND, operands, current_line_number)
+                }
+                "OR" | "or" => {
+                    self.parse_unary_instruction(Opcode::OR, operands, current_line_number)
+                }
+                "XOR" | "xor" => {
+                    self.parse_unary_instruction(Opcode::XOR, operands, current_line_number)
+                }
+                "NOT" | "not" => Ok(Instruction::new(Opcode::NOT)),
+
+                "SHL" | "shl" => {
+                    self.parse_unary_instruction(Opcode::SHL, operands, current_line_number)
+                }
+                "SHR" | "shr" => {
+                    self.parse_unary_instruction(Opcode::SHR, operands, current_line_number)
+                }
+                "SAR" | "sar" => {
+                    self.parse_unary_instruction(Opcode::SAR, operands, current_line_number)
+                }
+                "LSR" | "lsr" => {
+                    self.parse_unary_instruction(Opcode::LSR, operands, current_line_number)
+                }
+                "ROL" | "rol" => {
+                    self.parse_unary_instruction(Opcode::ROL, operands, current_line_number)
+                }
+                "ROR" | "ror" => {
+                    self.parse_unary_instruction(Opcode::ROR, operands, current_line_number)
+                }
+
+                "CMP" | "cmp" => self.parse_binary_instruction(
+                    Opcode::CMP,
+                    operands,
+                    current_line_number,
+                    (false, false),
+                ),
+
+                "JE" | "je" => self.parse_jump(Opcode::JE, operands, current_line_number),
+                "JNE" | "jne" => self.parse_jump(Opcode::JNE, operands, current_line_number),
+                "JL" | "jl" => self.parse_jump(Opcode::JL, operands, current_line_number),
+                "JG" | "jg" => self.parse_jump(Opcode::JG, operands, current_line_number),
+                "JZ" | "jz" => self.parse_jump(Opcode::JZ, operands, current_line_number),
+                "JNZ" | "jnz" => self.parse_jump(Opcode::JNZ, operands, current_line_number),
+                "JC" | "jc" => self.parse_jump(Opcode::JC, operands, current_line_number),
+                "JO" | "jo" => self.parse_jump(Opcode::JO, operands, current_line_number),
+                "JS" | "js" => self.parse_jump(Opcode::JS, operands, current_line_number),
+                "JA" | "ja" => self.parse_jump(Opcode::JA, operands, current_line_number),
+                "JAE" | "jae" => self.parse_jump(Opcode::JAE, operands, current_line_number),
+                "JB" | "jb" => self.parse_jump(Opcode::JB, operands, current_line_number),
+                "JBE" | "jbe" => self.parse_jump(Opcode::JBE, operands, current_line_number),
+                "JMP" | "jmp" => self.parse_jump(Opcode::JMP, operands, current_line_number),
+                "CALL" | "call" => self.parse_jump(Opcode::CALL, operands, current_line_number),
+                "RET" | "ret" => Ok(Instruction::new(Opcode::RET)),
+
+                "HLT" | "hlt" => Ok(Instruction::new(Opcode::HLT)),
+                "NOP" | "nop" => Ok(Instruction::new(Opcode::NOP)),
+
+                "PSH" | "psh" => {
+                    if self.profile.has_stack() {
+                        self.parse_unary_instruction(Opcode::PSH, operands, current_line_number)
+                    } else {
+                        Err(ParsingError::new(
+                            ParsingError::StackNotPresent,
+                            current_line_number,
+                            "stack not present".to_owned(),
+                        )
+                        .with_token(instruction_word, instruction_column))
+                    }
+                }
+                "POP" | "pop" => {
+                    if self.profile.has_stack() {
+                        self.parse_unary_instruction(Opcode::POP, operands, current_line_number)
+                    } else {
+                        Err(ParsingError::new(
+                            ParsingError::StackNotPresent,
+                            current_line_number,
+                            "stack not present".to_owned(),
+                        )
+                        .with_token(instruction_word, instruction_column))
+                    }
+                }
+
+                label if label.ends_with(':') => {
+                    self.parse_label(label, current_line_number, index)
+                }
+                _ => Err(ParsingError::new(
+                    ParsingError::NoSuchInstruction,
+                    current_line_number,
+                    "".to_string(),
+                )
+                .with_token(instruction_word, instruction_column)
+                .with_expected(t!("hint.instruction"))),
+            }
+        } else {
+            Err(ParsingError::new(
+                ParsingError::Empty,
+                current_line_number,
+                "FIXME".to_string(),
+            ))
+        }
+    }
+
+    fn parse_binary_instruction(
+        &mut self,
+        opcode: fn(Operand, Operand) -> Opcode,
+        operands: &[(usize, &str)],
+        line: usize,
+        register_only: (bool, bool),
+    ) -> Result<Instruction, ParsingError> {
+        Assembler::check_operand_count(operands, 2, line)?;
+        let (ro1, ro2) = register_only;
+        let operand1 = self.parse_operand_token(operands[0], line, ro1)?;
+        let operand2 = self.parse_operand_token(operands[1], line, ro2)?;
+        Ok(Instruction::new(opcode(operand1, operand2)))
+    }
+
+    fn parse_unary_instruction(
+        &mut self,
+        opcode: fn(Operand) -> Opcode,
+        operands: &[(usize, &str)],
+        line: usize,
+    ) -> Result<Instruction, ParsingError> {
+        Assembler::check_operand_count(operands, 1, line)?;
+
+        let operand = self.parse_operand_token(operands[0], line, false)?;
+        Ok(Instruction::new(opcode(operand)))
+    }
+
+    fn parse_jump(
+        &mut self,
+        opcode: fn(String) -> Opcode,
+        operands: &[(usize, &str)],
+        line: usize,
+    ) -> Result<Instruction, ParsingError> {
+        Assembler::check_operand_count(operands, 1, line)?;
+        let (_, label) = operands[0];
+        Ok(Instruction::new(opcode(label.to_string())))
+    }
+
+    /// Checks if instruction has expected number of operands
+    ///
+    /// Error for too many operands points at the first redundant one
+    fn check_operand_count(
+        operands: &[(usize, &str)],
+        expected: usize,
+        line: usize,
+    ) -> Result<(), ParsingError> {
+        if operands.len() > expected {
+            let (column, _) = operands[expected];
+            let (last_column, last) = operands[operands.len() - 1];
+            return Err(
+                ParsingError::new(ParsingError::TooManyOperands, line, "".to_string())
+                    .with_span((column, last_column + last.len()), "")
+                    .with_expected(t!("hint.operand_count", count = expected)),
+            );
+        }
+        if operands.len() < expected {
+            return Err(
+                ParsingError::new(ParsingError::NotEnoughOperands, line, "".to_string())
+                    .with_expected(t!("hint.operand_count", count = expected)),
+            );
+        }
+        Ok(())
+    }
+
+    /// Splits line into words, each paired with its byte offset in line, comments are skipped
+    fn split_words(line: &str) -> Vec<(usize, &str)> {
+        let code = line.split('#').next().unwrap_or("");
+        let mut words = Vec::new();
+        let mut word_start: Option<usize> = None;
+        for (i, c) in code.char_indices() {
+            if c.is_whitespace() {
+                if let Some(start) = word_start.take() {
+                    words.push((start, &code[start..i]));
+                }
+            } else if word_start.is_none() {
+                word_start = Some(i);
+            }
+        }
+        if let Some(start) = word_start {
+            words.push((start, &code[start..]));
+        }
+        words
+    }
+
+    /// Splits line into instruction (or directive) and its operands,
+    /// each paired with its byte offset in line, comments are skipped
+    ///
+    /// Operands are separated with whitespace or commas, whitespace does not separate operands
+    /// inside parentheses and around binary operators, so `MOV (BASE + 4) * 2 acc` has two operands.
+    /// Sign directly followed by operand starts new operand, `CMP acc -1` is `acc` and `-1`
+    fn split_operands(line: &str) -> Vec<(usize, &str)> {
+        const OPERATORS: &str = "+-*/%&|^~<>";
+        let code = line.split('#').next().unwrap_or("");
+        let chars: Vec<(usize, char)> = code.char_indices().collect();
+        let next_non_whitespace = |from: usize| {
+            chars[from..]
+                .iter()
+                .position(|(_, c)| !c.is_whitespace())
+                .map(|offset| from + offset)
+        };
+
+        let mut words = Vec::new();
+        let mut i = match next_non_whitespace(0) {
+            Some(i) => i,
+            None => return words,
+        };
+        // instruction is always a single word
+        let start = chars[i].0;
+        while i < chars.len() && !chars[i].1.is_whitespace() {
+            i += 1;
+        }
+        let end = chars
+            .get(i)
+            .map(|(offset, _)| *offset)
+            .unwrap_or(code.len());
+        words.push((start, &code[start..end]));
+
+        while let Some(first) = next_non_whitespace(i) {
+            let start = chars[first].0;
+            let mut end = code.len();
+            let mut depth = 0;
+            i = first;
+            while i < chars.len() {
+                let (offset, c) = chars[i];
+                i += 1;
+                match c {
+                    // skipping quoted character, which may be whitespace or quote
+                    '\'' if chars.get(i + 1).is_some_and(|(_, c)| *c == '\'') => i += 2,
+                    '(' | '[' => depth += 1,
+                    ')' | ']' => depth = (depth - 1).max(0),
+                    ',' if depth == 0 => {
+                        end = offset;
+                        break;
+                    }
+                    c if c.is_whitespace() && depth == 0 => {
+                        let previous = code[start..offset].chars().last();
+                        let next = next_non_whitespace(i).map(|j| chars[j]);
+                        let after_next = next.and_then(|(next, _)| {
+                            code[next..].chars().nth(1).filter(|c| !c.is_whitespace())
+                        });
+                        let continues = previous.is_some_and(|c| OPERATORS.contains(c))
+                            || match next {
+                                Some((_, '+' | '-')) => after_next.is_none(),
+                                Some((_, next)) => "*/%&|^<>),".contains(next),
+                                None => false,
+                            };
+                        if !continues {
+                            end = offset;
+                            break;
+                        }
+                    }
+                    _ => {}
+                }
+            }
+            words.push((start, code[start..end].trim_end()));
+        }
+        words
+    }
+
+    /// Sets language for parsing error messages
+    pub fn set_language(&mut self, language: Language) {
+        rust_i18n::set_locale(language.string_code());
+    }
 }
 #[cfg(test)]
 mod test {
     use super::*;
+    use crate::assembler::diagnostic::Severity;
+    use crate::assembler::resolver::{FileSystemResolver, MemoryResolver};
+    use std::fs;
+
+    #[test]
+    fn test_parsing_operand_empty() {
+        let mut assembler = Assembler::new();
+
+        let result = assembler.parse_operand("", 0, false);
+
+        assert!(result.is_err());
+
+        // let err = result.unwrap_err();
+        // println!("____________________________________");
+        // println!("{}", err);
+        // println!("____________________________________");
+    }
+
+    #[test]
+    fn test_parsing_operand_correct_registers() {
+        let mut assembler = Assembler::new();
+
+        let registers = ["r0", "r1", "r2", "r3", "p0", "p1", "p2", "p3"];
+
+        //println!("_________________________________________________________");
+        for register in registers.iter() {
+            let result = assembler.parse_operand(register, 0, false);
+
+            assert!(result.is_ok());
+            //println!("{:?}", result.unwrap());
+        }
+        //println!("_________________________________________________________");
+    }
+
+    #[test]
+    fn test_parsing_operand_in_correct_registers() {
+        let mut assembler = Assembler::new();
+
+        let registers = ["r-1", "r4", "rp", "pr", "p-3, p10"];
+
+        for register in registers.iter() {
+            let result = assembler.parse_operand(register, 0, false);
+            println!("_______________");
+            println!("{:?}", result);
+            println!("_______________");
+            assert!(result.is_err());
+        }
+    }
+
+    #[test]
+    fn test_parsing_operand() {
+        let mut assembler = Assembler::new();
+
+        let registers = ["r0", "r1", "r2", "r3", "p0", "p1", "p2", "p3"];
+
+        for register in registers.iter() {
+            let result = assembler.parse_operand(register, 0, false);
+
+            assert!(result.is_ok());
+
+            // let operand = result.unwrap();
+
+            // assert_eq!(operand, Operand::GeneralRegister(0));
+        }
+    }
+
+    #[test]
+    fn test_parsing_empty_string() {
+        let program_text = "";
+
+        let mut assembler = Assembler::new();
+
+        let result = assembler.parse(program_text);
+
+        assert!(result.is_err());
+
+        // let err = result.unwrap_err();
+        // println!("____________________________________");
+        // println!("{}", err);
+        // println!("____________________________________");
+
+        //assert_eq!(err.get_message(), "Empty Program")
+    }
+
+    #[test]
+    fn test_parsing_sub() {
+        let program_text = r#"
+            SUB 10
+            SUB r1
+            SUB p2
+            SUB -100
+        "#;
+
+        let mut assembler = Assembler::new();
+
+        let result = assembler.parse(program_text);
+
+        assert!(result.is_ok());
+
+        let program = result.unwrap();
+
+        let expected = vec![
+            Instruction::new(Opcode::SUB(Operand::IntegerValue(10))),
+            Instruction::new(Opcode::SUB(Operand::GeneralRegister(1))),
+            Instruction::new(Opcode::SUB(Operand::PortRegister(2))),
+            Instruction::new(Opcode::SUB(Operand::IntegerValue(-100))),
+        ];
+
+        assert_eq!(program, expected);
+    }
+
+    #[test]
+    fn test_parsing_different_base_integers() {
+        let program_text = r#"
+        ADD -7
+        ADD 0xAF
+        ADD 0b101
+
+    "#;
+        let mut assembler = Assembler::new();
+        let result = assembler.parse(program_text);
+
+        // match result {
+        //     Ok(res) => println!("{:?}", res),
+        //     Err(err) => println!("{:?}", err),
+        // }
+
+        let expected = vec![
+            Instruction::new(Opcode::ADD(Operand::IntegerValue(-7))),
+            Instruction::new(Opcode::ADD(Operand::IntegerValue(175))),
+            Instruction::new(Opcode::ADD(Operand::IntegerValue(5))),
+        ];
+
+        assert_eq!(result.unwrap(), expected);
+    }
+
+    #[test]
+    fn test_parsing_invalid_different_base_integers() {
+        let program_text = r#"
+        ADD -7
+        ADD 0xAF
+        ADD 0b1013
+
+        "#;
+        let mut assembler = Assembler::new();
+        let result = assembler.parse(program_text);
+
+        assert!(result.is_err());
+
+        let program_text = r#"
+        ADD -7
+        ADD AF
+        "#;
+
+        let mut assembler = Assembler::new();
+        let result = assembler.parse(program_text);
+
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_parsing_sub_inlaid_register() {
+        let program_text = r#"
+            SUB 10
+            SUB r1
+            SUB p6
+        "#;
+
+        let mut assembler = Assembler::new();
+
+        let result = assembler.parse(program_text);
+
+        assert!(result.is_err());
+    }
+    #[test]
+    fn test_parsing_sub_invalid_num_of_parameters() {
+        let program_text = r#"
+            SUB 2 p1
+        "#;
+
+        let mut assembler = Assembler::new();
+
+        let result = assembler.parse(program_text);
+
+        assert!(result.is_err());
+
+        // let err = result.unwrap_err();
+
+        //println!("{}", err);
+    }
+
+    #[test]
+    fn test_parsing_mov_invalid_num_parameters() {
+        let program_text = r#"
+            MOV 10
+        "#;
+
+        let mut assembler = Assembler::new();
+
+        let result = assembler.parse(program_text);
+
+        assert!(result.is_err());
+
+        //let err = result.unwrap_err();
+
+        //println!("{}", err);
+    }
+    //TODO
+    #[test]
+    fn test_parsing_mov() {
+        let program_text = r#"
+            MOV r1 r2
+            MOV 540999 acc
+            
+        "#;
+
+        let mut assembler = Assembler::new();
+
+        let result = assembler.parse(program_text);
+        assert!(result.is_ok());
+        // println!("________________________________________________---");
+        // match result {
+        //     Ok(v) => println!("{:?}", v),
+        //     Err(e) => println!("{:?}", e),
+        // }
+    }
+
+    #[test]
+    fn test_parsing_labels_and_jumps() {
+        let program_text = r#"
+        MOV 10 acc
+        loop:
+            ADD 8
+            CMP acc 20
+            JL loop
+        HLT
+        "#;
+
+        let mut assembler = Assembler::new();
+
+        let result = assembler.parse(program_text);
+        assert!(result.is_ok());
+        // println!("________________________________________________---");
+        // match result {
+        //     Ok(v) => println!("{:?}", v),
+        //     Err(e) => println!("{:?}", e),
+        // }
+    }
 
     #[test]
-    fn test_parsing_operand_empty() {
+    fn test_parsing_comments() {
+        let program_text = r#"
+        MOV 10 acc      # Moving 10 to accumulator
+        loop:           # Setting up label
+            ADD 8       # Adding 8 to accumulator
+            CMP acc 20  # Comparing value in accumulator to 20
+            JL loop     # Jumping to 'loop' label if accumulator is lesser
+        HLT             # Stopping execution
+        "#;
+
         let mut assembler = Assembler::new();
 
-        let result = assembler.parse_operand("", 0, false);
+        let result = assembler.parse(program_text);
+        assert!(result.is_ok());
+        // println!("________________________________________________---");
+        // match result {
+        //     Ok(v) => println!("{:?}", v),
+        //     Err(e) => println!("{:?}", e),
+        // }
+    }
 
-        assert!(result.is_err());
+    #[test]
+    fn test_parsing_empty_line() {
+        let program_text = r#"
+        MOV 10 acc      # Moving 10 to accumulator
 
-        // let err = result.unwrap_err();
-        // println!("____________________________________");
-        // println!("{}", err);
-        // println!("____________________________________");
+        loop:           # Setting up label
+            ADD 8       # Adding 8 to accumulator
+            CMP acc 20  # Comparing value in accumulator to 20
+            JL loop     # Jumping to 'loop' label if accumulator is lesser
+        HLT             # Stopping execution
+        "#;
+
+        let mut assembler = Assembler::new();
+
+        let result = assembler.parse(program_text);
+        assert!(result.is_ok());
+        // println!("________________________________________________---");
+        // match result {
+        //     Ok(v) => println!("{:?}", v),
+        //     Err(e) => println!("{:?}", e),
+        // }
     }
 
     #[test]
-    fn test_parsing_operand_correct_registers() {
+    fn test_lower_case_code_parsing() {
+        let program_text = r#"
+        mov 10 acc      # Moving 10 to accumulator
+
+        loop:           # Setting up label
+            add 8       # Adding 8 to accumulator
+            cmp acc 20  # Comparing value in accumulator to 20
+            jl loop     # Jumping to 'loop' label if accumulator is lesser
+        hlt             # Stopping execution
+        "#;
+
         let mut assembler = Assembler::new();
 
-        let registers = ["r0", "r1", "r2", "r3", "p0", "p1", "p2", "p3"];
+        let result = assembler.parse(program_text);
+        assert!(result.is_ok());
+        // println!("________________________________________________---");
+        // match result {
+        //     Ok(v) => println!("{:?}", v),
+        //     Err(e) => println!("{:?}", e),
+        // }
+    }
 
-        //println!("_________________________________________________________");
-        for register in registers.iter() {
-            let result = assembler.parse_operand(register, 0, false);
+    #[test]
+    fn test_line_with_only_comment() {
+        let program_text = r#"
+        #   This program does some stuff
+        #
+        #       *___________________*
+        #       |                   |
+        #       |                   |
+        #       |                   |
+        #       *___________________*
+        #
 
-            assert!(result.is_ok());
-            //println!("{:?}", result.unwrap());
-        }
-        //println!("_________________________________________________________");
+        mov 10 acc      # Moving 10 to accumulator
+
+        # Looping
+
+        loop:           # Setting up label
+            add 8       # Adding 8 to accumulator
+            cmp acc 20  # Comparing value in accumulator to 20
+            jl loop     # Jumping to 'loop' label if accumulator is lesser
+        hlt             # Stopping execution
+
+        #
+        #   Something Something Something
+        #
+        "#;
+
+        let mut assembler = Assembler::new();
+
+        let result = assembler.parse(program_text);
+        assert!(result.is_ok());
+        // println!("________________________________________________---");
+        // match result {
+        //     Ok(v) => println!("{:?}", v),
+        //     Err(e) => println!("{:?}", e),
+        // }
     }
 
     #[test]
-    fn test_parsing_operand_in_correct_registers() {
+    fn test_parsing_duplicate_label() {
+        let program_text = r#"
+        loop:
+            ADD 1
+        loop:
+            JMP loop
+        "#;
+
         let mut assembler = Assembler::new();
 
-        let registers = ["r-1", "r4", "rp", "pr", "p-3, p10"];
+        let result = assembler.parse(program_text);
 
-        for register in registers.iter() {
-            let result = assembler.parse_operand(register, 0, false);
-            println!("_______________");
-            println!("{:?}", result);
-            println!("_______________");
-            assert!(result.is_err());
+        assert_eq!(
+            result,
+            Err(
+                ParsingError::new(ParsingError::DuplicateLabel, 3, "loop".to_string())
+                    .with_token("loop:", 8)
+            )
+        );
+    }
+
+    #[test]
+    fn test_parsing_undefined_label() {
+        let program_text = r#"
+        loop:
+            ADD 1
+            CMP acc 10
+            JL lop
+        HLT
+        "#;
+
+        let mut assembler = Assembler::new();
+
+        let result = assembler.parse(program_text);
+
+        assert_eq!(
+            result,
+            Err(
+                ParsingError::new(ParsingError::UndefinedLabel, 4, "lop".to_string())
+                    .with_token("lop", 15)
+            )
+        );
+    }
+
+    #[test]
+    fn test_parsing_symbol_table() {
+        let program_text = r#"
+        start:
+            ADD 1
+            JMP end
+        end:
+            HLT
+        "#;
+
+        let mut assembler = Assembler::new();
+
+        assert!(assembler.parse(program_text).is_ok());
+
+        let labels = assembler.get_labels();
+        assert_eq!(labels.len(), 2);
+        assert_eq!(labels.get("start"), Some(&0));
+        assert_eq!(labels.get("end"), Some(&3));
+    }
+
+    #[test]
+    fn test_parsing_label_line_and_index() {
+        let program_text = r#"
+        # Comment above label
+
+        MOV 0 acc
+        loop:
+            INC
+            JMP loop
+        "#;
+
+        let mut assembler = Assembler::new();
+
+        let program = assembler.parse(program_text).unwrap();
+
+        assert!(matches!(&program[1], Instruction::Label(name, 4, 1) if name == "loop"));
+    }
+
+    #[test]
+    fn test_parsing_with_diagnostics_collects_all_errors() {
+        let program_text = r#"
+        MOV 10
+        ADD r9
+        loop:
+            INC
+            JMP loop
+            JMP end
+        HLT
+        "#;
+
+        let mut assembler = Assembler::new();
+
+        let (program, diagnostics) = assembler.parse_with_diagnostics(program_text);
+
+        // MOV and ADD are skipped, rest of program is assembled
+        assert_eq!(program.len(), 5);
+
+        let errors: Vec<(usize, (usize, usize), String)> = diagnostics
+            .iter()
+            .map(|d| {
+                (
+                    d.get_line(),
+                    d.get_span(),
+                    d.get_error().get_data().get_token(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            errors,
+            vec![
+                (1, (8, 14), "".to_string()),
+                (2, (12, 14), "r9".to_string()),
+                (6, (16, 19), "end".to_string()),
+            ]
+        );
+        assert!(matches!(
+            diagnostics[0].get_error(),
+            ParsingError::NotEnoughOperands(_)
+        ));
+        assert!(matches!(
+            diagnostics[1].get_error(),
+            ParsingError::InvalidRegisterNumber(_)
+        ));
+        assert!(matches!(
+            diagnostics[2].get_error(),
+            ParsingError::UndefinedLabel(_)
+        ));
+        assert!(diagnostics.iter().all(|d| d.is_error()));
+    }
+
+    #[test]
+    fn test_parsing_with_diagnostics_warnings() {
+        let program_text = r#"
+        start:
+            ADD 1
+        HLT
+        "#;
+
+        let mut assembler = Assembler::new();
+
+        let (program, diagnostics) = assembler.parse_with_diagnostics(program_text);
+
+        assert_eq!(program.len(), 3);
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].get_severity(), Severity::Warning);
+        assert_eq!(
+            diagnostics[0].get_error(),
+            &ParsingError::new(ParsingError::UnusedLabel, 1, "start".to_string())
+                .with_token("start:", 8)
+        );
+
+        // warnings do not stop parsing
+        assert!(assembler.parse(program_text).is_ok());
+    }
+
+    #[test]
+    fn test_parsing_constants() {
+        let program_text = r#"
+        .equ READY 7
+        .define MASK 0xFF
+        .equ ALSO_READY READY
+            MOV READY p0
+            AND MASK
+            CMP p0 ALSO_READY
+        "#;
+
+        let mut assembler = Assembler::new();
+
+        let program = assembler.parse(program_text).unwrap();
+
+        let expected = vec![
+            Instruction::new(Opcode::MOV(
+                Operand::IntegerValue(7),
+                Operand::PortRegister(0),
+            )),
+            Instruction::new(Opcode::AND(Operand::IntegerValue(255))),
+            Instruction::new(Opcode::CMP(
+                Operand::PortRegister(0),
+                Operand::IntegerValue(7),
+            )),
+        ];
+        assert_eq!(program, expected);
+        assert_eq!(assembler.get_constants().get("MASK"), Some(&255));
+    }
+
+    #[test]
+    fn test_parsing_constant_redefinition() {
+        let program_text = r#"
+        .equ READY 7
+        .define READY 8
+        "#;
+
+        let mut assembler = Assembler::new();
+
+        let result = assembler.parse(program_text);
+
+        assert_eq!(
+            result,
+            Err(
+                ParsingError::new(ParsingError::DuplicateConstant, 2, "READY".to_string())
+                    .with_token("READY", 16)
+            )
+        );
+    }
+
+    #[test]
+    fn test_parsing_undefined_constant() {
+        let program_text = r#"
+        .equ READY 7
+            CMP p0 REDY
+        "#;
+
+        let mut assembler = Assembler::new();
+
+        let result = assembler.parse(program_text);
+
+        assert_eq!(
+            result,
+            Err(
+                ParsingError::new(ParsingError::UndefinedConstant, 2, "REDY".to_string())
+                    .with_token("REDY", 19)
+            )
+        );
+    }
+
+    #[test]
+    fn test_parsing_invalid_constants() {
+        let programs = [
+            ".equ r1 5",
+            ".equ acc 5",
+            ".equ 1ST 5",
+            ".equ VALUE r1",
+            ".equ VALUE",
+            ".constant VALUE 5",
+            ".equ VALUE 5\nMOV 5 VALUE",
+        ];
+
+        for program_text in programs {
+            let mut assembler = Assembler::new();
+            assert!(assembler.parse(program_text).is_err(), "{}", program_text);
         }
     }
 
     #[test]
-    fn test_parsing_operand() {
+    fn test_parsing_constant_expressions() {
+        let program_text = r#"
+        .equ BASE 16
+        .equ MASK (1 << 4) - 1
+            MOV (BASE + 4) * 2 r0
+            AND 0xFF & MASK
+            CMP acc 'A' + 1
+            MOV 1, r1
+            CMP acc -1
+        "#;
+
         let mut assembler = Assembler::new();
 
-        let registers = ["r0", "r1", "r2", "r3", "p0", "p1", "p2", "p3"];
+        let program = assembler.parse(program_text).unwrap();
 
-        for register in registers.iter() {
-            let result = assembler.parse_operand(register, 0, false);
+        let expected = vec![
+            Instruction::new(Opcode::MOV(
+                Operand::IntegerValue(40),
+                Operand::GeneralRegister(0),
+            )),
+            Instruction::new(Opcode::AND(Operand::IntegerValue(15))),
+            Instruction::new(Opcode::CMP(Operand::ACC, Operand::IntegerValue(66))),
+            Instruction::new(Opcode::MOV(
+                Operand::IntegerValue(1),
+                Operand::GeneralRegister(1),
+            )),
+            Instruction::new(Opcode::CMP(Operand::ACC, Operand::IntegerValue(-1))),
+        ];
+        assert_eq!(program, expected);
+        assert_eq!(assembler.get_constants().get("MASK"), Some(&15));
+    }
 
-            assert!(result.is_ok());
+    #[test]
+    fn test_splitting_operands() {
+        assert_eq!(
+            Assembler::split_operands("    MOV (BASE + 4) * 2 acc # comment"),
+            vec![(4, "MOV"), (8, "(BASE + 4) * 2"), (23, "acc")]
+        );
+        assert_eq!(
+            Assembler::split_operands("CMP acc -1"),
+            vec![(0, "CMP"), (4, "acc"), (8, "-1")]
+        );
+        assert_eq!(
+            Assembler::split_operands("CMP 5 - 1 , ' '"),
+            vec![(0, "CMP"), (4, "5 - 1"), (12, "' '")]
+        );
+    }
 
-            // let operand = result.unwrap();
+    #[test]
+    fn test_parsing_expression_errors() {
+        let program_text = r#"
+        .equ ZERO 0
+            DIV 10 / ZERO
+            ADD 2147483647 + 1
+        "#;
+
+        let mut assembler = Assembler::new();
 
-            // assert_eq!(operand, Operand::GeneralRegister(0));
-        }
+        let (program, diagnostics) = assembler.parse_with_diagnostics(program_text);
+
+        assert_eq!(
+            program,
+            vec![Instruction::new(Opcode::ADD(Operand::IntegerValue(
+                i32::MIN
+            )))]
+        );
+        assert_eq!(diagnostics.len(), 2);
+        assert!(matches!(
+            diagnostics[0].get_error(),
+            ParsingError::DivisionByZero(_)
+        ));
+        assert_eq!(diagnostics[0].get_span(), (21, 25));
+        assert!(matches!(
+            diagnostics[1].get_error(),
+            ParsingError::ExpressionOverflow(_)
+        ));
+        assert_eq!(diagnostics[1].get_severity(), Severity::Warning);
+        assert_eq!(diagnostics[1].get_span(), (27, 28));
+        assert!(assembler.parse(program_text).is_err());
+        assert!(assembler.parse("ADD 2147483647 + 1").is_ok());
     }
 
     #[test]
-    fn test_parsing_empty_string() {
-        let program_text = "";
+    fn test_parsing_macros() {
+        let program_text = r#"
+        .macro wait_for port, value
+            wait:
+                CMP port value
+                JNE wait
+        .endm
+            wait_for p0 7
+            wait_for p1, 'a'
+        "#;
 
         let mut assembler = Assembler::new();
 
-        let result = assembler.parse(program_text);
-
-        assert!(result.is_err());
-
-        // let err = result.unwrap_err();
-        // println!("____________________________________");
-        // println!("{}", err);
-        // println!("____________________________________");
+        let program = assembler.parse(program_text).unwrap();
 
-        //assert_eq!(err.get_message(), "Empty Program")
+        let expected = vec![
+            Instruction::new_label("wait@1".to_string(), 6, 0),
+            Instruction::new(Opcode::CMP(
+                Operand::PortRegister(0),
+                Operand::IntegerValue(7),
+            )),
+            Instruction::new(Opcode::JNE("wait@1".to_string())),
+            Instruction::new_label("wait@2".to_string(), 7, 3),
+            Instruction::new(Opcode::CMP(
+                Operand::PortRegister(1),
+                Operand::IntegerValue(97),
+            )),
+            Instruction::new(Opcode::JNE("wait@2".to_string())),
+        ];
+        assert_eq!(program, expected);
     }
 
     #[test]
-    fn test_parsing_sub() {
+    fn test_parsing_nested_macros() {
         let program_text = r#"
-            SUB 10
-            SUB r1
-            SUB p2
-            SUB -100
+        .macro double register
+            MOV register acc
+            MUL 2
+            MOV acc register
+        .endm
+        .macro quadruple register
+            double register
+            double register
+        .endm
+            quadruple r1
         "#;
 
         let mut assembler = Assembler::new();
 
-        let result = assembler.parse(program_text);
+        let program = assembler.parse(program_text).unwrap();
 
-        assert!(result.is_ok());
+        assert_eq!(program.len(), 6);
+        assert_eq!(
+            program[5],
+            Instruction::new(Opcode::MOV(Operand::ACC, Operand::GeneralRegister(1)))
+        );
+    }
 
-        let program = result.unwrap();
+    #[test]
+    fn test_parsing_error_in_macro_expansion() {
+        let program_text = r#"
+        .macro load value
+            MOV value r7
+        .endm
+            load 5
+        "#;
 
-        let expected = vec![
-            Instruction::new(Opcode::SUB(Operand::IntegerValue(10))),
-            Instruction::new(Opcode::SUB(Operand::GeneralRegister(1))),
-            Instruction::new(Opcode::SUB(Operand::PortRegister(2))),
-            Instruction::new(Opcode::SUB(Operand::IntegerValue(-100))),
+        let mut assembler = Assembler::new();
+
+        let error = assembler.parse(program_text).unwrap_err();
+
+        assert!(matches!(error, ParsingError::InvalidRegisterNumber(_)));
+        // error points at invocation and names line in macro body
+        assert_eq!(error.get_data().get_line(), 4);
+        assert_eq!(
+            error.get_data().get_expansion(),
+            Some(("load".to_string(), 2))
+        );
+        assert_eq!(error.get_data().get_span(), None);
+        assert_eq!(
+            error.get_note(),
+            Some("in macro 'load' at line 3".to_string())
+        );
+    }
+
+    #[test]
+    fn test_parsing_invalid_macros() {
+        let programs = [
+            ".macro\n.endm",
+            ".macro r1\n.endm",
+            ".macro twice a, a\n.endm",
+            ".macro twice a\n.endm\n.macro twice b\n.endm",
+            ".macro twice a\nADD a",
+            ".endm",
+            ".macro inc_by a\nADD a\n.endm\ninc_by",
+            ".macro inc_by a\nADD a\n.endm\ninc_by 1 2",
+            ".macro forever\nforever\n.endm\nforever",
         ];
 
-        assert_eq!(program, expected);
+        for program_text in programs {
+            let mut assembler = Assembler::new();
+            assert!(assembler.parse(program_text).is_err(), "{}", program_text);
+        }
     }
 
     #[test]
-    fn test_parsing_different_base_integers() {
+    fn test_parsing_includes() {
+        let resolver = MemoryResolver::new()
+            .with_file("signals.sva", ".equ READY 7\n.include \"wait.sva\"")
+            .with_file(
+                "wait.sva",
+                ".macro wait_for port, value\nwait:\n    CMP port value\n    JNE wait\n.endm",
+            );
         let program_text = r#"
-        ADD -7
-        ADD 0xAF
-        ADD 0b101
+        .include "signals.sva"
+            wait_for p0 READY
+        "#;
 
-    "#;
-        let mut assembler = Assembler::new();
-        let result = assembler.parse(program_text);
+        let mut assembler = Assembler::new().with_resolver(resolver);
 
-        // match result {
-        //     Ok(res) => println!("{:?}", res),
-        //     Err(err) => println!("{:?}", err),
-        // }
+        let program = assembler.parse(program_text).unwrap();
 
         let expected = vec![
-            Instruction::new(Opcode::ADD(Operand::IntegerValue(-7))),
-            Instruction::new(Opcode::ADD(Operand::IntegerValue(175))),
-            Instruction::new(Opcode::ADD(Operand::IntegerValue(5))),
+            Instruction::new_label("wait@1".to_string(), 2, 0),
+            Instruction::new(Opcode::CMP(
+                Operand::PortRegister(0),
+                Operand::IntegerValue(7),
+            )),
+            Instruction::new(Opcode::JNE("wait@1".to_string())),
         ];
+        assert_eq!(program, expected);
+    }
 
-        assert_eq!(result.unwrap(), expected);
+    #[test]
+    fn test_parsing_error_in_included_file() {
+        let resolver = MemoryResolver::new().with_file("lib.sva", "ADD 1\nADD r9");
+
+        let mut assembler = Assembler::new().with_resolver(resolver);
+        let program_text = "MOV 1 acc\n.include lib.sva";
+
+        let (_, diagnostics) = assembler.parse_with_diagnostics(program_text);
+
+        assert_eq!(diagnostics.len(), 1);
+        let error = diagnostics[0].get_error();
+        assert!(matches!(error, ParsingError::InvalidRegisterNumber(_)));
+        assert_eq!(error.get_data().get_file(), Some("lib.sva".to_string()));
+        assert_eq!(error.get_data().get_line(), 1);
+        assert_eq!(error.get_data().get_location(), "lib.sva:2");
+        assert_eq!(diagnostics[0].get_span(), (4, 6));
+        assert!(diagnostics[0].render(program_text).contains("2 | ADD r9\n"));
     }
 
     #[test]
-    fn test_parsing_invalid_different_base_integers() {
-        let program_text = r#"
-        ADD -7
-        ADD 0xAF
-        ADD 0b1013
+    fn test_parsing_include_errors() {
+        let resolver = MemoryResolver::new()
+            .with_file("a.sva", ".include b.sva")
+            .with_file("b.sva", ".include a.sva");
 
-        "#;
-        let mut assembler = Assembler::new();
-        let result = assembler.parse(program_text);
+        let mut assembler = Assembler::new().with_resolver(resolver);
 
-        assert!(result.is_err());
+        let error = assembler.parse(".include a.sva").unwrap_err();
+        assert!(matches!(error, ParsingError::IncludeCycle(_)));
+        assert_eq!(error.get_data().get_file(), Some("b.sva".to_string()));
 
-        let program_text = r#"
-        ADD -7
-        ADD AF
-        "#;
+        let error = assembler.parse(".include missing.sva").unwrap_err();
+        assert_eq!(
+            error,
+            ParsingError::new(ParsingError::IncludeNotFound, 0, "missing.sva".to_string())
+                .with_token("missing.sva", 9)
+        );
 
         let mut assembler = Assembler::new();
-        let result = assembler.parse(program_text);
+        assert!(matches!(
+            assembler.parse(".include a.sva"),
+            Err(ParsingError::IncludeNotFound(_))
+        ));
+    }
 
-        assert!(result.is_err());
+    #[test]
+    fn test_parsing_too_deep_includes() {
+        let mut resolver = MemoryResolver::new();
+        for i in 0..=MAX_INCLUDE_DEPTH {
+            resolver.insert(&format!("{}.sva", i), &format!(".include {}.sva", i + 1));
+        }
+
+        let mut assembler = Assembler::new().with_resolver(resolver);
+
+        let error = assembler.parse(".include 0.sva").unwrap_err();
+        assert!(matches!(error, ParsingError::IncludeTooDeep(_)));
     }
 
     #[test]
-    fn test_parsing_sub_inlaid_register() {
+    fn test_parsing_nested_includes_from_file_system() {
+        let root = std::env::temp_dir().join("sva_nested_include_test");
+        fs::create_dir_all(root.join("lib")).unwrap();
+        fs::write(
+            root.join("lib").join("math.sva"),
+            ".include double.sva\nINC",
+        )
+        .unwrap();
+        fs::write(root.join("lib").join("double.sva"), "MUL 2").unwrap();
+        fs::write(std::env::temp_dir().join("sva_secret.sva"), "HLT").unwrap();
+
+        let mut assembler = Assembler::new().with_resolver(FileSystemResolver::new(&root));
+
+        let program = assembler.parse(".include lib/math.sva").unwrap();
+        assert_eq!(
+            program,
+            vec![
+                Instruction::new(Opcode::MUL(Operand::IntegerValue(2))),
+                Instruction::new(Opcode::INC),
+            ]
+        );
+
+        // files are not resolved relative to root, nor outside of it
+        for name in [
+            "double.sva",
+            "lib/../../sva_secret.sva",
+            "../sva_secret.sva",
+        ] {
+            let error = assembler.parse(&format!(".include {}", name)).unwrap_err();
+            assert!(
+                matches!(error, ParsingError::IncludeNotFound(_)),
+                "{}",
+                name
+            );
+        }
+    }
+
+    #[test]
+    fn test_assembler_is_send_and_sync() {
+        fn assert_send_sync<T: Send + Sync>() {}
+        assert_send_sync::<Assembler>();
+    }
+
+    #[test]
+    fn test_parsing_local_labels() {
         let program_text = r#"
-            SUB 10
-            SUB r1
-            SUB p6
+        first:
+            .loop:
+                INC
+                JL .loop
+            JMP second
+        second:
+            .loop:
+                DEC
+                JG .loop
+                JMP first.loop
         "#;
 
         let mut assembler = Assembler::new();
 
-        let result = assembler.parse(program_text);
+        let program = assembler.parse(program_text).unwrap();
 
-        assert!(result.is_err());
+        let expected = vec![
+            Instruction::new_label("first".to_string(), 1, 0),
+            Instruction::new_label("first.loop".to_string(), 2, 1),
+            Instruction::new(Opcode::INC),
+            Instruction::new(Opcode::JL("first.loop".to_string())),
+            Instruction::new(Opcode::JMP("second".to_string())),
+            Instruction::new_label("second".to_string(), 6, 5),
+            Instruction::new_label("second.loop".to_string(), 7, 6),
+            Instruction::new(Opcode::DEC),
+            Instruction::new(Opcode::JG("second.loop".to_string())),
+            Instruction::new(Opcode::JMP("first.loop".to_string())),
+        ];
+        assert_eq!(program, expected);
+        assert_eq!(assembler.get_labels().get("second.loop"), Some(&6));
     }
+
     #[test]
-    fn test_parsing_sub_invalid_num_of_parameters() {
+    fn test_parsing_local_label_after_macro_with_label() {
         let program_text = r#"
-            SUB 2 p1
+        .macro spin n
+        again:
+            CMP acc n
+            JG again
+        .endm
+        main:
+            .loop:
+                spin 0
+                JMP .loop
         "#;
 
         let mut assembler = Assembler::new();
 
-        let result = assembler.parse(program_text);
-
-        assert!(result.is_err());
-
-        // let err = result.unwrap_err();
+        let program = assembler.parse(program_text).unwrap();
 
-        //println!("{}", err);
+        assert_eq!(
+            program[5],
+            Instruction::new(Opcode::JMP("main.loop".to_string()))
+        );
+        assert_eq!(assembler.get_labels().get("main.loop"), Some(&1));
     }
 
     #[test]
-    fn test_parsing_mov_invalid_num_parameters() {
-        let program_text = r#"
-            MOV 10
-        "#;
+    fn test_parsing_undefined_local_label() {
+        let program_text = "first:\n    .loop:\nsecond:\n    JMP .loop\n    JMP first";
 
         let mut assembler = Assembler::new();
 
         let result = assembler.parse(program_text);
 
-        assert!(result.is_err());
-
-        //let err = result.unwrap_err();
-
-        //println!("{}", err);
+        // .loop is declared only in scope of first
+        assert_eq!(
+            result,
+            Err(
+                ParsingError::new(ParsingError::UndefinedLabel, 3, "second.loop".to_string())
+                    .with_token(".loop", 8)
+            )
+        );
     }
-    //TODO
+
     #[test]
-    fn test_parsing_mov() {
+    fn test_parsing_conditional_assembly() {
         let program_text = r#"
+        .if __STACK__
+            PSH r1
+            POP r2
+        .else
             MOV r1 r2
-            MOV 540999 acc
-            
+        .endif
         "#;
 
-        let mut assembler = Assembler::new();
+        let program = Assembler::new().with_stack().parse(program_text).unwrap();
+        assert_eq!(
+            program,
+            vec![
+                Instruction::new(Opcode::PSH(Operand::GeneralRegister(1))),
+                Instruction::new(Opcode::POP(Operand::GeneralRegister(2))),
+            ]
+        );
+
+        let program = Assembler::new().parse(program_text).unwrap();
+        assert_eq!(
+            program,
+            vec![Instruction::new(Opcode::MOV(
+                Operand::GeneralRegister(1),
+                Operand::GeneralRegister(2)
+            ))]
+        );
+    }
 
-        let result = assembler.parse(program_text);
-        assert!(result.is_ok());
-        // println!("________________________________________________---");
-        // match result {
-        //     Ok(v) => println!("{:?}", v),
-        //     Err(e) => println!("{:?}", e),
-        // }
+    #[test]
+    fn test_parsing_ifdef_built_in_symbol() {
+        let program_text = ".ifdef __STACK__\nPSH r0\n.else\nMOV r0 r1\n.endif";
+        let push = vec![Instruction::new(Opcode::PSH(Operand::GeneralRegister(0)))];
+        let mov = vec![Instruction::new(Opcode::MOV(
+            Operand::GeneralRegister(0),
+            Operand::GeneralRegister(1),
+        ))];
+
+        let mut assembler = Assembler::new().with_stack();
+        assert_eq!(assembler.parse(program_text), Ok(push.clone()));
+        assert_eq!(
+            assembler.parse(".ifndef __STACK__\nMOV r0 r1\n.else\nPSH r0\n.endif"),
+            Ok(push)
+        );
+        assert!(assembler.get_constants().is_empty());
+
+        let mut assembler = Assembler::new();
+        assert_eq!(assembler.parse(program_text), Ok(mov.clone()));
+        assert_eq!(
+            assembler.parse(".ifndef __STACK__\nMOV r0 r1\n.else\nPSH r0\n.endif"),
+            Ok(mov)
+        );
+        assert!(assembler.get_constants().is_empty());
     }
 
     #[test]
-    fn test_parsing_labels_and_jumps() {
+    fn test_parsing_nested_conditions() {
         let program_text = r#"
-        MOV 10 acc
-        loop:
-            ADD 8
-            CMP acc 20
-            JL loop
-        HLT
+        .equ SIZE 4
+        .ifdef SIZE
+            .if SIZE & 4 ^ __STACK__
+                INC
+            .else
+                DEC
+                .ifndef SIZE
+                    r9 is never parsed
+                .endif
+            .endif
+        .endif
+        .ifdef UNDEFINED
+            .if UNDEFINED
+                ADD UNDEFINED
+            .endif
+        .else
+            .equ STEP SIZE * 2
+        .endif
+            ADD STEP
         "#;
 
         let mut assembler = Assembler::new();
 
-        let result = assembler.parse(program_text);
-        assert!(result.is_ok());
-        // println!("________________________________________________---");
-        // match result {
-        //     Ok(v) => println!("{:?}", v),
-        //     Err(e) => println!("{:?}", e),
-        // }
+        let program = assembler.parse(program_text).unwrap();
+
+        assert_eq!(
+            program,
+            vec![
+                Instruction::new(Opcode::INC),
+                Instruction::new(Opcode::ADD(Operand::IntegerValue(8))),
+            ]
+        );
     }
 
     #[test]
-    fn test_parsing_comments() {
-        let program_text = r#"
-        MOV 10 acc      # Moving 10 to accumulator
-        loop:           # Setting up label
-            ADD 8       # Adding 8 to accumulator
-            CMP acc 20  # Comparing value in accumulator to 20
-            JL loop     # Jumping to 'loop' label if accumulator is lesser
-        HLT             # Stopping execution
-        "#;
-
+    fn test_parsing_condition_errors() {
         let mut assembler = Assembler::new();
 
-        let result = assembler.parse(program_text);
-        assert!(result.is_ok());
-        // println!("________________________________________________---");
-        // match result {
-        //     Ok(v) => println!("{:?}", v),
-        //     Err(e) => println!("{:?}", e),
-        // }
+        let error = assembler.parse("    .if 1\nINC").unwrap_err();
+        assert_eq!(
+            error,
+            ParsingError::new(ParsingError::UnterminatedCondition, 0, "".to_string())
+                .with_token(".if", 4)
+                .with_expected(".endif".to_string())
+        );
+
+        let programs = [".else", ".endif", ".if 1\n.else\n.else\n.endif"];
+        for program_text in programs {
+            assert!(
+                matches!(
+                    assembler.parse(program_text),
+                    Err(ParsingError::UnmatchedCondition(_))
+                ),
+                "{}",
+                program_text
+            );
+        }
+
+        assert!(matches!(
+            assembler.parse(".if r1\n.endif"),
+            Err(ParsingError::InvalidOperandType(_))
+        ));
+        assert!(matches!(
+            assembler.parse(".if MISSING\n.endif"),
+            Err(ParsingError::UndefinedConstant(_))
+        ));
+
+        // blocks have to end in file they were started in
+        let resolver = MemoryResolver::new().with_file("a.sva", ".if 1");
+        let mut assembler = Assembler::new().with_resolver(resolver);
+        let (_, diagnostics) = assembler.parse_with_diagnostics(".include a.sva\n.endif");
+        assert_eq!(diagnostics.len(), 2);
+        let error = diagnostics[0].get_error();
+        assert!(matches!(error, ParsingError::UnterminatedCondition(_)));
+        assert_eq!(error.get_data().get_file(), Some("a.sva".to_string()));
+        assert!(matches!(
+            diagnostics[1].get_error(),
+            ParsingError::UnmatchedCondition(_)
+        ));
     }
 
     #[test]
-    fn test_parsing_empty_line() {
+    fn test_parsing_aliases() {
         let program_text = r#"
-        MOV 10 acc      # Moving 10 to accumulator
-
-        loop:           # Setting up label
-            ADD 8       # Adding 8 to accumulator
-            CMP acc 20  # Comparing value in accumulator to 20
-            JL loop     # Jumping to 'loop' label if accumulator is lesser
-        HLT             # Stopping execution
+        .alias ready p0
+        .alias counter r2
+        main:
+            .alias counter r1
+            MOV ready counter
+            JMP next
+        next:
+            MOV ready counter
         "#;
 
         let mut assembler = Assembler::new();
 
-        let result = assembler.parse(program_text);
-        assert!(result.is_ok());
-        // println!("________________________________________________---");
-        // match result {
-        //     Ok(v) => println!("{:?}", v),
-        //     Err(e) => println!("{:?}", e),
-        // }
+        let program = assembler.parse(program_text).unwrap();
+
+        // alias defined after label shadows global one until next label
+        let expected = vec![
+            Instruction::new_label("main".to_string(), 3, 0),
+            Instruction::new(Opcode::MOV(
+                Operand::PortRegister(0),
+                Operand::GeneralRegister(1),
+            )),
+            Instruction::new(Opcode::JMP("next".to_string())),
+            Instruction::new_label("next".to_string(), 7, 3),
+            Instruction::new(Opcode::MOV(
+                Operand::PortRegister(0),
+                Operand::GeneralRegister(2),
+            )),
+        ];
+        assert_eq!(program, expected);
+        assert_eq!(
+            assembler.get_operand_aliases()[1],
+            vec![Some("ready".to_string()), Some("counter".to_string())]
+        );
+        assert_eq!(assembler.get_operand_aliases()[2], vec![None]);
     }
 
     #[test]
-    fn test_lower_case_code_parsing() {
-        let program_text = r#"
-        mov 10 acc      # Moving 10 to accumulator
+    fn test_parsing_alias_errors() {
+        let mut assembler = Assembler::new();
 
-        loop:           # Setting up label
-            add 8       # Adding 8 to accumulator
-            cmp acc 20  # Comparing value in accumulator to 20
-            jl loop     # Jumping to 'loop' label if accumulator is lesser
-        hlt             # Stopping execution
+        assert_eq!(
+            assembler.parse(".alias r1 r2"),
+            Err(
+                ParsingError::new(ParsingError::AliasShadowsRegister, 0, "r1".to_string())
+                    .with_token("r1", 7)
+            )
+        );
+        assert!(matches!(
+            assembler.parse(".alias acc r2"),
+            Err(ParsingError::AliasShadowsRegister(_))
+        ));
+        assert!(matches!(
+            assembler.parse(".alias 1x r2"),
+            Err(ParsingError::InvalidAliasName(_))
+        ));
+        assert!(matches!(
+            assembler.parse(".alias counter 5"),
+            Err(ParsingError::InvalidOperandType(_))
+        ));
+        assert!(matches!(
+            assembler.parse(".alias counter r2\n.alias counter r3"),
+            Err(ParsingError::DuplicateAlias(_))
+        ));
+
+        // scoped alias is not visible after next label
+        let error = assembler
+            .parse("first:\n.alias counter r2\nsecond:\nINC\nMOV 1 counter")
+            .unwrap_err();
+        assert!(matches!(error, ParsingError::InvalidOperandType(_)));
+        assert_eq!(error.get_data().get_line(), 4);
+    }
+
+    #[test]
+    fn test_parsing_memory_operands() {
+        let program_text = r#"
+        .equ SIZE 4
+        .alias index r2
+        MOV [r1] [r1+4]
+        ADD [ r3 - 1 ]
+        CMP [index + SIZE * 2] [index-SIZE]
         "#;
 
         let mut assembler = Assembler::new();
 
-        let result = assembler.parse(program_text);
-        assert!(result.is_ok());
-        // println!("________________________________________________---");
-        // match result {
-        //     Ok(v) => println!("{:?}", v),
-        //     Err(e) => println!("{:?}", e),
-        // }
+        let program = assembler.parse(program_text).unwrap();
+
+        let expected = vec![
+            Instruction::new(Opcode::MOV(Operand::Memory(1, 0), Operand::Memory(1, 4))),
+            Instruction::new(Opcode::ADD(Operand::Memory(3, -1))),
+            Instruction::new(Opcode::CMP(Operand::Memory(2, 8), Operand::Memory(2, -4))),
+        ];
+        assert_eq!(program, expected);
+        assert_eq!(Operand::Memory(3, -1).to_string(), "[r3-1]");
     }
 
     #[test]
-    fn test_line_with_only_comment() {
-        let program_text = r#"
-        #   This program does some stuff
-        #
-        #       *___________________*
-        #       |                   |
-        #       |                   |
-        #       |                   |
-        #       *___________________*
-        #
+    fn test_parsing_memory_operand_errors() {
+        let mut assembler = Assembler::new();
 
-        mov 10 acc      # Moving 10 to accumulator
+        assert_eq!(
+            assembler.parse("MOV 1 [p0+1]"),
+            Err(
+                ParsingError::new(ParsingError::InvalidMemoryOperand, 0, "".to_string())
+                    .with_token("p0", 7)
+                    .with_expected(t!("hint.memory"))
+            )
+        );
+        assert_eq!(
+            assembler.parse("ADD [r9]"),
+            Err(
+                ParsingError::new(ParsingError::InvalidRegisterNumber, 0, "".to_string())
+                    .with_token("r9", 5)
+                    .with_expected(t!("hint.register", max = 3))
+            )
+        );
+        assert_eq!(
+            assembler
+                .parse("ADD [r1 + MISSING]")
+                .unwrap_err()
+                .get_data()
+                .get_span(),
+            Some((10, 17))
+        );
+        for text in ["ADD [r1", "ADD []", "ADD [+4]", "ADD [r1 4]", "ADD [r1*2]"] {
+            assert!(
+                matches!(
+                    assembler.parse(text),
+                    Err(ParsingError::InvalidMemoryOperand(_))
+                ),
+                "{}",
+                text
+            );
+        }
+        assert!(matches!(
+            assembler.parse(".alias cell [r1]"),
+            Err(ParsingError::InvalidOperandType(_))
+        ));
+    }
 
-        # Looping
+    #[test]
+    fn test_parsing_breakpoint_conditions() {
+        let mut assembler = Assembler::new();
+        assembler
+            .parse(".equ LIMIT 1 << 4\n.alias counter r1\nINC")
+            .unwrap();
+
+        let conditions = [
+            ("acc == 42", "acc == 42"),
+            ("counter>=LIMIT", "r1 >= 16"),
+            ("  [counter+2] != 'A'", "[r1+2] != 65"),
+            ("p0 < 1 << 2", "p0 < 4"),
+            ("LIMIT >> 2 > r0", "4 > r0"),
+        ];
+        for (text, parsed) in conditions {
+            assert_eq!(
+                assembler
+                    .parse_breakpoint_condition(text)
+                    .map(|condition| condition.to_string()),
+                Ok(parsed.to_string())
+            );
+        }
 
-        loop:           # Setting up label
-            add 8       # Adding 8 to accumulator
-            cmp acc 20  # Comparing value in accumulator to 20
-            jl loop     # Jumping to 'loop' label if accumulator is lesser
-        hlt             # Stopping execution
+        assert_eq!(
+            assembler.parse_breakpoint_condition("acc = 5"),
+            Err(
+                ParsingError::new(ParsingError::InvalidExpression, 0, "".to_string())
+                    .with_token("acc = 5", 0)
+                    .with_expected(t!("hint.condition"))
+            )
+        );
+        assert_eq!(
+            assembler
+                .parse_breakpoint_condition("acc == r7")
+                .unwrap_err()
+                .get_data()
+                .get_span(),
+            Some((7, 9))
+        );
+    }
 
-        #
-        #   Something Something Something
-        #
-        "#;
+    #[test]
+    fn test_parsing_with_profile() {
+        let profile = MachineProfile::new().with_registers(2).with_ports(8);
+
+        let mut assembler = Assembler::new_with_profile(profile);
+
+        assert_eq!(
+            assembler.parse("MOV p7 r1"),
+            Ok(vec![Instruction::new(Opcode::MOV(
+                Operand::PortRegister(7),
+                Operand::GeneralRegister(1)
+            ))])
+        );
+        assert_eq!(
+            assembler.parse("MOV p0 r2"),
+            Err(
+                ParsingError::new(ParsingError::InvalidRegisterNumber, 0, "".to_string())
+                    .with_token("r2", 7)
+                    .with_expected(t!("hint.register", max = 1))
+            )
+        );
+        assert!(matches!(
+            assembler.parse("PSH r0"),
+            Err(ParsingError::StackNotPresent(_))
+        ));
+
+        let mut assembler = Assembler::new_with_profile(profile.with_stack(4));
+        assert!(assembler.parse(".if __STACK__\nPSH r0\n.endif").is_ok());
+    }
+
+    #[test]
+    fn test_parsing_call_and_ret() {
+        let program_text = "    CALL print\n    HLT\nprint:\n    MOV acc p0\n    ret";
 
         let mut assembler = Assembler::new();
 
-        let result = assembler.parse(program_text);
-        assert!(result.is_ok());
-        // println!("________________________________________________---");
-        // match result {
-        //     Ok(v) => println!("{:?}", v),
-        //     Err(e) => println!("{:?}", e),
-        // }
+        let program = assembler.parse(program_text).unwrap();
+
+        assert_eq!(
+            program[0],
+            Instruction::new(Opcode::CALL("print".to_string()))
+        );
+        assert_eq!(program[4], Instruction::new(Opcode::RET));
+        assert_eq!(
+            assembler.parse("CALL missing"),
+            Err(
+                ParsingError::new(ParsingError::UndefinedLabel, 0, "missing".to_string())
+                    .with_token("missing", 5)
+            )
+        );
     }
 }
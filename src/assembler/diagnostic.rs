@@ -0,0 +1,208 @@
+use std::fmt;
+
+use rust_i18n::t;
+
+use super::parsing_err::ParsingError;
+
+/// Severity of diagnostic
+#[derive(Debug, serde::Deserialize, serde::Serialize, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    /// Line could not be assembled, program is incomplete
+    Error,
+    /// Program was assembled but probably does not do what was intended
+    Warning,
+}
+
+/// Single message produced while assembling, used by editors to mark problems in code
+#[derive(Debug, serde::Deserialize, serde::Serialize, Clone, PartialEq)]
+pub struct Diagnostic {
+    severity: Severity,
+    /// Line number (starting from 0)
+    line: usize,
+    /// Range of columns (start inclusive, end exclusive, in bytes) that diagnostic refers to
+    span: (usize, usize),
+    /// Kind of error with its data
+    error: ParsingError,
+    /// Localized message, in language set when diagnostic was created
+    message: String,
+    /// Text of line diagnostic refers to, used to render diagnostics for included files
+    #[serde(default)]
+    source_line: String,
+}
+
+impl Diagnostic {
+    /// Creates diagnostic
+    ///
+    /// ### Arguments
+    /// * 'severity' - error or warning
+    /// * 'error' - kind of error with its data
+    /// * 'source_line' - text of line error occurred at, used to compute column span
+    pub fn new(severity: Severity, error: ParsingError, source_line: &str) -> Diagnostic {
+        let message = error.get_description();
+        let data = error.get_data();
+        Diagnostic {
+            severity,
+            line: data.get_line(),
+            span: data
+                .get_span()
+                .unwrap_or_else(|| Diagnostic::code_span(source_line)),
+            error,
+            message,
+            source_line: source_line.to_string(),
+        }
+    }
+
+    pub fn error(error: ParsingError, source_line: &str) -> Diagnostic {
+        Diagnostic::new(Severity::Error, error, source_line)
+    }
+
+    pub fn warning(error: ParsingError, source_line: &str) -> Diagnostic {
+        Diagnostic::new(Severity::Warning, error, source_line)
+    }
+
+    /// Columns of code in line, without indentation and comment
+    fn code_span(source_line: &str) -> (usize, usize) {
+        let code = source_line.split('#').next().unwrap_or("");
+        let start = code.len() - code.trim_start().len();
+        let end = code.trim_end().len();
+        if end < start {
+            return (start, start);
+        }
+        (start, end)
+    }
+
+    pub fn get_severity(&self) -> Severity {
+        self.severity
+    }
+
+    pub fn is_error(&self) -> bool {
+        self.severity == Severity::Error
+    }
+
+    pub fn get_line(&self) -> usize {
+        self.line
+    }
+
+    pub fn get_span(&self) -> (usize, usize) {
+        self.span
+    }
+
+    pub fn get_error(&self) -> &ParsingError {
+        &self.error
+    }
+
+    pub fn get_message(&self) -> String {
+        self.message.clone()
+    }
+
+    /// Converts diagnostic back to error it was created from
+    pub fn into_error(self) -> ParsingError {
+        self.error
+    }
+
+    /// Renders diagnostic with line of source code and token underlined, rustc-style
+    ///
+    /// ### Arguments
+    /// * 'program_text' - whole source code diagnostic was produced for,
+    ///   diagnostics for included files use line they were created with
+    ///
+    /// ### Example
+    ///
+    /// ```text
+    /// Error: Invalid register number
+    ///  --> line 3:9
+    ///   |
+    /// 3 |     ADD r7
+    ///   |         ^^ expected register r0..r3, found r7
+    /// ```
+    pub fn render(&self, program_text: &str) -> String {
+        let severity = match self.severity {
+            Severity::Error => t!("diagnostic.error"),
+            Severity::Warning => t!("diagnostic.warning"),
+        };
+        let file = self.error.get_data().get_file();
+        let source_line = match file {
+            Some(_) => self.source_line.as_str(),
+            None => program_text.lines().nth(self.line).unwrap_or(""),
+        };
+        let line_number = (self.line + 1).to_string();
+        let gutter = " ".repeat(line_number.len());
+
+        let (start, end) = self.span;
+        let start = start.min(source_line.len());
+        let end = end.clamp(start, source_line.len());
+        // keeping tabs so underline stays aligned with source line
+        let padding: String = source_line[..start]
+            .chars()
+            .map(|c| if c == '\t' { '\t' } else { ' ' })
+            .collect();
+        let underline = "^".repeat(source_line[start..end].chars().count().max(1));
+        let label = self
+            .error
+            .get_hint()
+            .unwrap_or_else(|| self.error.get_message());
+
+        let mut rendered = format!("{}: {}\n", severity, self.error.get_error_type());
+        rendered += &format!(
+            "{}--> {}{} {}:{}\n",
+            gutter,
+            file.map(|file| format!("{} ", file)).unwrap_or_default(),
+            t!("diagnostic.line"),
+            line_number,
+            start + 1
+        );
+        rendered += &format!("{} |\n", gutter);
+        rendered += &format!("{} | {}\n", line_number, source_line);
+        rendered += format!("{} | {}{} {}", gutter, padding, underline, label).trim_end();
+        rendered += "\n";
+        if let Some(note) = self.error.get_note() {
+            rendered += &format!("{} = {}\n", gutter, note);
+        }
+        rendered
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let severity = match self.severity {
+            Severity::Error => t!("diagnostic.error"),
+            Severity::Warning => t!("diagnostic.warning"),
+        };
+        // format of diagnostic:
+        // { severity } : { file : }? { line } : { column } \t { message }
+        write!(
+            f,
+            "{}:{}:{}\t{}",
+            severity,
+            self.error.get_data().get_location(),
+            self.span.0 + 1,
+            self.message
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_diagnostic_span() {
+        let error = ParsingError::new(ParsingError::NoSuchInstruction, 2, "".to_owned());
+
+        let diagnostic = Diagnostic::error(error, "    MVO 10 acc   # comment");
+
+        assert_eq!(diagnostic.get_line(), 2);
+        assert_eq!(diagnostic.get_span(), (4, 14));
+        assert!(diagnostic.is_error());
+    }
+
+    #[test]
+    fn test_diagnostic_token_span() {
+        let error = ParsingError::new(ParsingError::InvalidRegisterNumber, 0, "".to_owned())
+            .with_token("r7", 8);
+
+        let diagnostic = Diagnostic::error(error, "    ADD r7");
+
+        assert_eq!(diagnostic.get_span(), (8, 10));
+    }
+}
@@ -0,0 +1,290 @@
+use std::collections::HashMap;
+
+use crate::assembler::assembler::Assembler;
+use crate::vm::{instruction::Instruction, operand::Operand};
+
+/// Notation of integer literal in assembly code
+#[derive(Debug, serde::Deserialize, serde::Serialize, Clone, Copy, PartialEq, Eq)]
+pub enum Radix {
+    /// 42, -7
+    Decimal,
+    /// 0b101
+    Binary,
+    /// 0xAF
+    Hex,
+    /// 'a'
+    Char,
+}
+
+impl Radix {
+    /// Recognizes radix of integer literal, None if text is not a literal
+    pub fn of_literal(text: &str) -> Option<Radix> {
+        match text {
+            binary
+                if binary.len() > 2
+                    && binary.starts_with("0b")
+                    && binary[2..].chars().all(|c| c == '0' || c == '1') =>
+            {
+                Some(Radix::Binary)
+            }
+            hex if hex.len() > 2
+                && hex.starts_with("0x")
+                && hex[2..].chars().all(|c| c.is_ascii_hexdigit()) =>
+            {
+                Some(Radix::Hex)
+            }
+            c if c.len() == 3 && c.starts_with('\'') && c.ends_with('\'') => Some(Radix::Char),
+            decimal
+                if !decimal.is_empty()
+                    && decimal
+                        .chars()
+                        .all(|c| c.is_numeric() || (c == '-' && decimal.starts_with('-'))) =>
+            {
+                Some(Radix::Decimal)
+            }
+            _ => None,
+        }
+    }
+
+    /// Formats value as literal in this radix
+    ///
+    /// Falls back to decimal if value can't be written in this radix
+    /// and parsed back to the same value (negative numbers, characters that are not printable)
+    pub fn format(&self, value: i32) -> String {
+        match self {
+            Radix::Binary if value >= 0 => format!("0b{:b}", value),
+            Radix::Hex if value >= 0 => format!("0x{:X}", value),
+            Radix::Char => match u8::try_from(value) {
+                Ok(c) if c.is_ascii_graphic() && c != b'#' => format!("'{}'", c as char),
+                _ => value.to_string(),
+            },
+            _ => value.to_string(),
+        }
+    }
+}
+
+/// Turns assembled program back into assembly code
+///
+/// ### Example
+///
+/// ```rs
+/// let mut assembler = Assembler::new();
+/// let program = assembler.parse(program_text)?;
+/// let text = Disassembler::new()
+///     .with_radixes(assembler.get_literal_radixes())
+///     .disassemble(&program);
+/// assert_eq!(assembler.parse(&text)?, program);
+/// ```
+///
+/// With aliases recorded by assembler, registers are printed by their aliases
+/// and `.alias` directives are emitted where aliases are first needed in their scope.
+/// These directives can take more lines than in source, moving following labels
+/// below their source lines
+pub struct Disassembler {
+    /// Text placed before every instruction that is not a label
+    indentation: String,
+    /// Radixes of operands, for every instruction in program, None if operand wasn't a literal
+    radixes: Vec<Vec<Option<Radix>>>,
+    /// Aliases of operands, for every instruction in program, None if operand wasn't an alias
+    aliases: Vec<Vec<Option<String>>>,
+}
+
+impl Default for Disassembler {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Disassembler {
+    pub fn new() -> Disassembler {
+        Disassembler {
+            indentation: "    ".to_string(),
+            radixes: Vec::new(),
+            aliases: Vec::new(),
+        }
+    }
+
+    pub fn with_indentation(mut self, indentation: &str) -> Disassembler {
+        self.indentation = indentation.to_string();
+        self
+    }
+
+    /// Sets radixes used to print integer literals, as recorded by assembler
+    ///
+    /// Literals of instructions without recorded radix are printed in decimal
+    pub fn with_radixes(mut self, radixes: Vec<Vec<Option<Radix>>>) -> Disassembler {
+        self.radixes = radixes;
+        self
+    }
+
+    /// Sets aliases used to print registers, as recorded by assembler
+    ///
+    /// Registers of instructions without recorded alias are printed by their names
+    pub fn with_aliases(mut self, aliases: Vec<Vec<Option<String>>>) -> Disassembler {
+        self.aliases = aliases;
+        self
+    }
+
+    /// Disassembles program into canonical assembly code
+    ///
+    /// Labels are placed at the start of line, every other instruction is indented.
+    /// Empty lines are inserted before labels so they stay on their source lines,
+    /// then assembling the result gives the same program. Label that can't be moved down
+    /// to its line, e.g. one from included file or macro, is placed right after preceding instruction
+    pub fn disassemble(&self, program: &[Instruction]) -> String {
+        let mut text = String::new();
+        // number of the line next instruction is written to
+        let mut line = 0;
+        // aliases defined before first label are global, later ones end at next global label
+        let mut global_aliases: HashMap<String, Operand> = HashMap::new();
+        let mut scoped_aliases: Option<HashMap<String, Operand>> = None;
+        for (index, instruction) in program.iter().enumerate() {
+            match instruction {
+                Instruction::Label(name, source_line, _) => {
+                    if Assembler::starts_alias_scope(name) {
+                        scoped_aliases = Some(HashMap::new());
+                    }
+                    while line < *source_line {
+                        text += "\n";
+                        line += 1;
+                    }
+                }
+                Instruction::Opcode(opcode) => {
+                    for (operand, alias) in
+                        opcode.get_operands().iter().zip(self.get_aliases(index))
+                    {
+                        let Some(alias) = alias else { continue };
+                        let defined = scoped_aliases
+                            .as_ref()
+                            .and_then(|aliases| aliases.get(alias))
+                            .or_else(|| global_aliases.get(alias));
+                        if defined != Some(operand) {
+                            text += &format!("{}.alias {} {}\n", self.indentation, alias, operand);
+                            line += 1;
+                            scoped_aliases
+                                .as_mut()
+                                .unwrap_or(&mut global_aliases)
+                                .insert(alias.clone(), operand.clone());
+                        }
+                    }
+                }
+            }
+            text += &self.disassemble_instruction(index, instruction);
+            text += "\n";
+            line += 1;
+        }
+        text
+    }
+
+    /// Gets aliases of operands of instruction, None for operands without alias
+    fn get_aliases(&self, index: usize) -> impl Iterator<Item = &Option<String>> {
+        self.aliases
+            .get(index)
+            .into_iter()
+            .flatten()
+            .chain(std::iter::repeat(&None))
+    }
+
+    /// Disassembles single instruction
+    ///
+    /// ### Arguments
+    /// * 'index' - index of instruction in program, used to find radixes of its literals
+    /// * 'instruction' - instruction to be disassembled
+    pub fn disassemble_instruction(&self, index: usize, instruction: &Instruction) -> String {
+        let opcode = match instruction {
+            Instruction::Label(_, _, _) => return instruction.to_string(),
+            Instruction::Opcode(opcode) => opcode,
+        };
+        let radixes = self.radixes.get(index);
+
+        let mut text = format!("{}{}", self.indentation, opcode.get_mnemonic());
+        let aliases = opcode
+            .get_operands()
+            .into_iter()
+            .zip(self.get_aliases(index));
+        for (position, (operand, alias)) in aliases.enumerate() {
+            let operand = match (operand, alias) {
+                (Operand::IntegerValue(value), _) => radixes
+                    .and_then(|radixes| radixes.get(position).copied().flatten())
+                    .unwrap_or(Radix::Decimal)
+                    .format(value),
+                (_, Some(alias)) => alias.clone(),
+                (operand, None) => operand.to_string(),
+            };
+            text += &format!(" {}", operand);
+        }
+        if let Some(label) = opcode.get_label() {
+            text += &format!(" {}", label);
+        }
+        text
+    }
+}
+
+/// Disassembles program into canonical assembly code, printing literals in decimal
+pub fn disassemble(program: &[Instruction]) -> String {
+    Disassembler::new().disassemble(program)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::vm::opcodes::Opcode;
+
+    #[test]
+    fn test_radix_of_literal() {
+        assert_eq!(Radix::of_literal("-12"), Some(Radix::Decimal));
+        assert_eq!(Radix::of_literal("0b101"), Some(Radix::Binary));
+        assert_eq!(Radix::of_literal("0xAF"), Some(Radix::Hex));
+        assert_eq!(Radix::of_literal("'a'"), Some(Radix::Char));
+        assert_eq!(Radix::of_literal("r1"), None);
+        assert_eq!(Radix::of_literal("loop"), None);
+        assert_eq!(Radix::of_literal("0xF0 | 1"), None);
+    }
+
+    #[test]
+    fn test_radix_format_fallback() {
+        assert_eq!(Radix::Hex.format(175), "0xAF");
+        assert_eq!(Radix::Hex.format(-1), "-1");
+        assert_eq!(Radix::Binary.format(5), "0b101");
+        assert_eq!(Radix::Char.format(65), "'A'");
+        assert_eq!(Radix::Char.format(' ' as i32), "32");
+        assert_eq!(Radix::Char.format('#' as i32), "35");
+    }
+
+    #[test]
+    fn test_disassemble() {
+        let program = vec![
+            Instruction::new(Opcode::MOV(Operand::IntegerValue(10), Operand::ACC)),
+            Instruction::new_label("loop".to_string(), 1, 1),
+            Instruction::new(Opcode::AND(Operand::IntegerValue(255))),
+            Instruction::new(Opcode::JL("loop".to_string())),
+            Instruction::new(Opcode::HLT),
+        ];
+
+        let text = Disassembler::new()
+            .with_radixes(vec![vec![], vec![], vec![Some(Radix::Hex)]])
+            .disassemble(&program);
+
+        assert_eq!(
+            text,
+            "    MOV 10 acc\nloop:\n    AND 0xFF\n    JL loop\n    HLT\n"
+        );
+    }
+
+    #[test]
+    fn test_disassemble_keeps_label_lines() {
+        let program = vec![
+            Instruction::new_label("start".to_string(), 2, 0),
+            Instruction::new(Opcode::INC),
+            Instruction::new_label("end".to_string(), 5, 2),
+            Instruction::new_label("after".to_string(), 5, 3),
+            Instruction::new(Opcode::JMP("start".to_string())),
+        ];
+
+        // label that can't be on its line is placed right after preceding instruction
+        assert_eq!(
+            disassemble(&program),
+            "\n\nstart:\n    INC\n\nend:\nafter:\n    JMP start\n"
+        );
+    }
+}
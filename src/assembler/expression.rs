@@ -0,0 +1,468 @@
+use std::collections::HashMap;
+
+use rust_i18n::t;
+
+use super::parsing_err::ParsingError;
+
+/// Token of constant expression with its span (relative to start of expression)
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Number(i32),
+    Constant(String),
+    Operator(&'static str),
+    OpeningParenthesis,
+    ClosingParenthesis,
+}
+
+/// Token paired with its span
+type SpannedToken = (Token, (usize, usize));
+
+/// Evaluates constant expressions used as operands, e.g. `(BASE + 4) * 2` or `'A' + 1`
+///
+/// Supported operators, from highest to lowest precedence (like in C):
+/// * unary `-`, `+`, `~`
+/// * `*`, `/`, `%`
+/// * `+`, `-`
+/// * `<<`, `>>`
+/// * `&`
+/// * `^`
+/// * `|`
+///
+/// Arithmetic is done on i32 and wraps like in VM, overflow is reported as warning,
+/// division by zero is an error
+pub struct Expression<'a> {
+    tokens: Vec<SpannedToken>,
+    position: usize,
+    constants: &'a HashMap<String, i32>,
+    line: usize,
+    /// Length of expression text, used as span of errors at the end of expression
+    length: usize,
+    warnings: Vec<ParsingError>,
+}
+
+impl<'a> Expression<'a> {
+    /// Evaluates expression
+    ///
+    /// ### Arguments
+    /// * 'text' - expression
+    /// * 'constants' - constants that can be used in expression
+    /// * 'line' - line number, used in errors
+    ///
+    /// ### Returns
+    /// * value of expression and warnings, spans of errors and warnings are relative to start of text
+    pub fn evaluate(
+        text: &str,
+        constants: &'a HashMap<String, i32>,
+        line: usize,
+    ) -> Result<(i32, Vec<ParsingError>), ParsingError> {
+        let mut expression = Expression {
+            tokens: Expression::tokenize(text, constants, line)?,
+            position: 0,
+            constants,
+            line,
+            length: text.len(),
+            warnings: Vec::new(),
+        };
+        let value = expression.parse_binary(0)?;
+        if let Some((_, span)) = expression.tokens.get(expression.position) {
+            return Err(
+                ParsingError::new(ParsingError::InvalidExpression, line, "".to_string())
+                    .with_span(*span, &text[span.0..span.1])
+                    .with_expected(t!("hint.expression_operator")),
+            );
+        }
+        Ok((value, expression.warnings))
+    }
+
+    /// Splits expression into tokens
+    fn tokenize(
+        text: &str,
+        constants: &HashMap<String, i32>,
+        line: usize,
+    ) -> Result<Vec<SpannedToken>, ParsingError> {
+        let mut tokens: Vec<SpannedToken> = Vec::new();
+        let chars: Vec<(usize, char)> = text.char_indices().collect();
+        let end_of = |i: usize| {
+            chars
+                .get(i)
+                .map(|(offset, _)| *offset)
+                .unwrap_or(text.len())
+        };
+        let mut i = 0;
+
+        while i < chars.len() {
+            let (start, c) = chars[i];
+            // minus in place of operand is a part of decimal literal,
+            // so that -2147483648 can be written
+            let unary_position = matches!(
+                tokens.last(),
+                None | Some((Token::Operator(_), _)) | Some((Token::OpeningParenthesis, _))
+            );
+            let token = match c {
+                c if c.is_whitespace() => {
+                    i += 1;
+                    continue;
+                }
+                '\'' => {
+                    // quoted character may be a quote itself: '''
+                    let mut j = i + 2;
+                    while j < chars.len() && chars[j].1 != '\'' {
+                        j += 1;
+                    }
+                    let literal = &text[start..end_of(j + 1)];
+                    i = j + 1;
+                    Token::Number(
+                        parse_literal(literal, line).map_err(|e| e.with_token(literal, start))?,
+                    )
+                }
+                c if c.is_ascii_digit()
+                    || (c == '-'
+                        && unary_position
+                        && chars.get(i + 1).is_some_and(|(_, c)| c.is_ascii_digit())) =>
+                {
+                    let mut j = i + 1;
+                    while j < chars.len() && (chars[j].1.is_alphanumeric() || chars[j].1 == '_') {
+                        j += 1;
+                    }
+                    let literal = &text[start..end_of(j)];
+                    i = j;
+                    Token::Number(
+                        parse_literal(literal, line).map_err(|e| e.with_token(literal, start))?,
+                    )
+                }
+                c if c.is_alphabetic() || c == '_' => {
+                    let mut j = i + 1;
+                    while j < chars.len() && (chars[j].1.is_alphanumeric() || chars[j].1 == '_') {
+                        j += 1;
+                    }
+                    let name = &text[start..end_of(j)];
+                    i = j;
+                    if !constants.contains_key(name) {
+                        return Err(ParsingError::new(
+                            ParsingError::UndefinedConstant,
+                            line,
+                            name.to_string(),
+                        )
+                        .with_token(name, start));
+                    }
+                    Token::Constant(name.to_string())
+                }
+                '(' => {
+                    i += 1;
+                    Token::OpeningParenthesis
+                }
+                ')' => {
+                    i += 1;
+                    Token::ClosingParenthesis
+                }
+                '<' | '>' => {
+                    let operator = if c == '<' { "<<" } else { ">>" };
+                    if !text[start..].starts_with(operator) {
+                        return Err(ParsingError::new(
+                            ParsingError::InvalidExpression,
+                            line,
+                            "".to_string(),
+                        )
+                        .with_token(&text[start..end_of(i + 1)], start)
+                        .with_expected(operator.to_string()));
+                    }
+                    i += 2;
+                    Token::Operator(operator)
+                }
+                '+' | '-' | '*' | '/' | '%' | '&' | '|' | '^' | '~' => {
+                    i += 1;
+                    Token::Operator(match c {
+                        '+' => "+",
+                        '-' => "-",
+                        '*' => "*",
+                        '/' => "/",
+                        '%' => "%",
+                        '&' => "&",
+                        '|' => "|",
+                        '^' => "^",
+                        _ => "~",
+                    })
+                }
+                _ => {
+                    return Err(ParsingError::new(
+                        ParsingError::InvalidExpression,
+                        line,
+                        "".to_string(),
+                    )
+                    .with_token(&text[start..end_of(i + 1)], start)
+                    .with_expected(t!("hint.expression_operand")))
+                }
+            };
+            tokens.push((token, (start, end_of(i))));
+        }
+        Ok(tokens)
+    }
+
+    /// Precedence of binary operator, higher binds stronger
+    fn precedence(operator: &str) -> Option<u8> {
+        match operator {
+            "|" => Some(1),
+            "^" => Some(2),
+            "&" => Some(3),
+            "<<" | ">>" => Some(4),
+            "+" | "-" => Some(5),
+            "*" | "/" | "%" => Some(6),
+            _ => None,
+        }
+    }
+
+    /// Parses binary operations with precedence at least 'min_precedence' (precedence climbing)
+    fn parse_binary(&mut self, min_precedence: u8) -> Result<i32, ParsingError> {
+        let mut value = self.parse_unary()?;
+
+        while let Some((Token::Operator(operator), span)) = self.tokens.get(self.position).cloned()
+        {
+            let precedence = match Expression::precedence(operator) {
+                Some(precedence) if precedence >= min_precedence => precedence,
+                _ => break,
+            };
+            self.position += 1;
+            let (rhs_start, _) = self.current_span();
+            let rhs = self.parse_binary(precedence + 1)?;
+            let (_, rhs_end) = self.previous_span();
+            value = self.apply(operator, value, rhs, span, (rhs_start, rhs_end))?;
+        }
+        Ok(value)
+    }
+
+    /// Parses unary operation, number, constant or expression in parentheses
+    fn parse_unary(&mut self) -> Result<i32, ParsingError> {
+        let (token, span) = match self.tokens.get(self.position).cloned() {
+            Some(token) => token,
+            None => {
+                return Err(ParsingError::new(
+                    ParsingError::InvalidExpression,
+                    self.line,
+                    "".to_string(),
+                )
+                .with_span((self.length, self.length), "")
+                .with_expected(t!("hint.expression_operand")))
+            }
+        };
+        self.position += 1;
+
+        match token {
+            Token::Number(value) => Ok(value),
+            Token::Constant(name) => Ok(self.constants[&name]),
+            Token::Operator("-") => {
+                let value = self.parse_unary()?;
+                if value == i32::MIN {
+                    self.overflow(span);
+                }
+                Ok(value.wrapping_neg())
+            }
+            Token::Operator("+") => self.parse_unary(),
+            Token::Operator("~") => Ok(!self.parse_unary()?),
+            Token::OpeningParenthesis => {
+                let value = self.parse_binary(0)?;
+                match self.tokens.get(self.position) {
+                    Some((Token::ClosingParenthesis, _)) => {
+                        self.position += 1;
+                        Ok(value)
+                    }
+                    _ => Err(ParsingError::new(
+                        ParsingError::InvalidExpression,
+                        self.line,
+                        "".to_string(),
+                    )
+                    .with_span(span, "(")
+                    .with_expected("')'".to_string())),
+                }
+            }
+            _ => Err(
+                ParsingError::new(ParsingError::InvalidExpression, self.line, "".to_string())
+                    .with_span(span, "")
+                    .with_expected(t!("hint.expression_operand")),
+            ),
+        }
+    }
+
+    /// Applies binary operator
+    ///
+    /// ### Arguments
+    /// * 'operator' - operator
+    /// * 'lhs', 'rhs' - values of operands
+    /// * 'span' - span of operator
+    /// * 'rhs_span' - span of right operand
+    fn apply(
+        &mut self,
+        operator: &str,
+        lhs: i32,
+        rhs: i32,
+        span: (usize, usize),
+        rhs_span: (usize, usize),
+    ) -> Result<i32, ParsingError> {
+        if matches!(operator, "/" | "%") && rhs == 0 {
+            return Err(
+                ParsingError::new(ParsingError::DivisionByZero, self.line, "".to_string())
+                    .with_span(rhs_span, ""),
+            );
+        }
+        let (value, overflow) = match operator {
+            "+" => lhs.overflowing_add(rhs),
+            "-" => lhs.overflowing_sub(rhs),
+            "*" => lhs.overflowing_mul(rhs),
+            "/" => lhs.overflowing_div(rhs),
+            "%" => lhs.overflowing_rem(rhs),
+            "&" => (lhs & rhs, false),
+            "|" => (lhs | rhs, false),
+            "^" => (lhs ^ rhs, false),
+            // shifts by 0..32 behave like SHL and SAR in vm, other amounts wrap and are reported
+            "<<" => (
+                lhs.wrapping_shl(rhs.try_into().unwrap_or(0)),
+                !(0..32).contains(&rhs),
+            ),
+            ">>" => (
+                lhs.wrapping_shr(rhs.try_into().unwrap_or(0)),
+                !(0..32).contains(&rhs),
+            ),
+            _ => unreachable!(),
+        };
+        if overflow {
+            self.overflow(span);
+        }
+        Ok(value)
+    }
+
+    fn overflow(&mut self, span: (usize, usize)) {
+        self.warnings.push(
+            ParsingError::new(ParsingError::ExpressionOverflow, self.line, "".to_string())
+                .with_span(span, ""),
+        );
+    }
+
+    /// Span of current token, or end of expression
+    fn current_span(&self) -> (usize, usize) {
+        self.tokens
+            .get(self.position)
+            .map(|(_, span)| *span)
+            .unwrap_or((self.length, self.length))
+    }
+
+    /// Span of last consumed token
+    fn previous_span(&self) -> (usize, usize) {
+        self.tokens[self.position - 1].1
+    }
+}
+
+/// Parses integer literal: decimal, binary (0b), hexadecimal (0x) or character ('a')
+///
+/// Binary and hexadecimal literals are bit patterns, so any 32 bit value is accepted,
+/// e.g. 0xFFFFFFFF is -1
+///
+/// ### Arguments
+/// * 'text' - literal
+/// * 'line' - line number, used in errors
+pub fn parse_literal(text: &str, line: usize) -> Result<i32, ParsingError> {
+    match text {
+        binary if binary.starts_with("0b") => u32::from_str_radix(&binary[2..], 2)
+            .map(|value| value as i32)
+            .map_err(|_| {
+                ParsingError::new(ParsingError::InvalidBinaryLiteral, line, "".to_string())
+                    .with_expected(t!("hint.binary"))
+            }),
+        hex if hex.starts_with("0x") => u32::from_str_radix(&hex[2..], 16)
+            .map(|value| value as i32)
+            .map_err(|_| {
+                ParsingError::new(ParsingError::InvalidHexLiteral, line, "".to_string())
+                    .with_expected(t!("hint.hex"))
+            }),
+        c if c.starts_with('\'') => {
+            let mut chars = c.chars();
+            match (chars.next(), chars.next(), chars.next(), chars.next()) {
+                (Some('\''), Some(value), Some('\''), None) => Ok(value as i32),
+                _ => Err(
+                    ParsingError::new(ParsingError::InvalidCharLiteral, line, "".to_string())
+                        .with_expected(t!("hint.char")),
+                ),
+            }
+        }
+        decimal => decimal.parse::<i32>().map_err(|_| {
+            ParsingError::new(ParsingError::InvalidNumericLiteral, line, "".to_string())
+                .with_expected(t!("hint.integer"))
+        }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn evaluate(text: &str) -> Result<i32, ParsingError> {
+        let constants = HashMap::from([("BASE".to_string(), 16), ("MASK".to_string(), 0x0F)]);
+        Expression::evaluate(text, &constants, 0).map(|(value, _)| value)
+    }
+
+    #[test]
+    fn test_expression_precedence() {
+        assert_eq!(evaluate("(BASE + 4) * 2"), Ok(40));
+        assert_eq!(evaluate("BASE + 4 * 2"), Ok(24));
+        assert_eq!(evaluate("0xFF & MASK"), Ok(15));
+        assert_eq!(evaluate("'A' + 1"), Ok(66));
+        assert_eq!(evaluate("1 << 4 | 1"), Ok(17));
+        assert_eq!(evaluate("1 | 6 ^ 3 & 2"), Ok(5));
+        assert_eq!(evaluate("-BASE - -2"), Ok(-14));
+        assert_eq!(evaluate("~0"), Ok(-1));
+        assert_eq!(evaluate("17 % 5 - 10 / 3"), Ok(-1));
+        assert_eq!(evaluate("-2147483648"), Ok(i32::MIN));
+        assert_eq!(evaluate("' ' + 0"), Ok(32));
+        assert_eq!(evaluate("0xFFFFFFFF"), Ok(-1));
+        assert_eq!(evaluate("0x80000000 | 1"), Ok(i32::MIN + 1));
+    }
+
+    #[test]
+    fn test_expression_overflow_wraps_with_warning() {
+        let constants = HashMap::new();
+
+        let (value, warnings) = Expression::evaluate("2147483647 + 1", &constants, 3).unwrap();
+
+        assert_eq!(value, i32::MIN);
+        assert_eq!(
+            warnings,
+            vec![
+                ParsingError::new(ParsingError::ExpressionOverflow, 3, "".to_string())
+                    .with_span((11, 12), "")
+            ]
+        );
+    }
+
+    #[test]
+    fn test_expression_errors() {
+        assert!(matches!(
+            evaluate("BASE / (MASK - 15)"),
+            Err(ParsingError::DivisionByZero(_))
+        ));
+        assert_eq!(
+            evaluate("BASE + MISSING")
+                .unwrap_err()
+                .get_data()
+                .get_span(),
+            Some((7, 14))
+        );
+        assert!(matches!(
+            evaluate("(BASE + 1"),
+            Err(ParsingError::InvalidExpression(_))
+        ));
+        assert!(matches!(
+            evaluate("BASE +"),
+            Err(ParsingError::InvalidExpression(_))
+        ));
+        assert!(matches!(
+            evaluate("1 < 2"),
+            Err(ParsingError::InvalidExpression(_))
+        ));
+        assert!(matches!(
+            evaluate("0b102"),
+            Err(ParsingError::InvalidBinaryLiteral(_))
+        ));
+        assert!(matches!(
+            evaluate("0x100000000"),
+            Err(ParsingError::InvalidHexLiteral(_))
+        ));
+    }
+}
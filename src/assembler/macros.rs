@@ -0,0 +1,158 @@
+use std::collections::HashSet;
+
+/// Macro defined with `.macro name a, b` ... `.endm`
+///
+/// ### Example
+///
+/// ```text
+/// .macro wait_for port, value
+///     wait:
+///         CMP port value
+///         JNE wait
+/// .endm
+///     wait_for p0 7
+/// ```
+#[derive(Debug, serde::Deserialize, serde::Serialize, Clone, PartialEq)]
+pub struct Macro {
+    name: String,
+    parameters: Vec<String>,
+    /// Lines of macro body paired with their line numbers in source
+    body: Vec<(usize, String)>,
+    /// Labels declared in macro body, renamed on each expansion
+    labels: HashSet<String>,
+}
+
+impl Macro {
+    /// Creates macro
+    ///
+    /// ### Arguments
+    /// * 'name' - name used to invoke macro
+    /// * 'parameters' - names of parameters
+    /// * 'body' - lines of macro body paired with their line numbers in source
+    pub fn new(name: &str, parameters: Vec<String>, body: Vec<(usize, String)>) -> Macro {
+        let labels = body
+            .iter()
+            .filter_map(|(_, line)| {
+                let code = line.split('#').next().unwrap_or("").trim();
+                // local labels are renamed without their leading '.'
+                code.strip_suffix(':')
+                    .map(|label| label.trim_start_matches('.').to_string())
+            })
+            .collect();
+        Macro {
+            name: name.to_string(),
+            parameters,
+            body,
+            labels,
+        }
+    }
+
+    pub fn get_name(&self) -> String {
+        self.name.clone()
+    }
+
+    pub fn get_parameters(&self) -> Vec<String> {
+        self.parameters.clone()
+    }
+
+    /// Expands macro, replacing parameters with arguments
+    ///
+    /// Arguments are substituted as text, labels declared in body get 'suffix' appended,
+    /// so each expansion has its own labels
+    ///
+    /// ### Arguments
+    /// * 'arguments' - text of arguments, one for each parameter
+    /// * 'suffix' - text unique to this expansion
+    ///
+    /// ### Returns
+    /// * expanded lines paired with line numbers of body lines they were created from
+    pub fn expand(&self, arguments: &[&str], suffix: &str) -> Vec<(usize, String)> {
+        self.body
+            .iter()
+            .map(|(line_number, line)| {
+                (
+                    *line_number,
+                    self.substitute(line, |name| {
+                        if let Some(position) = self.parameters.iter().position(|p| p == name) {
+                            return Some(arguments[position].to_string());
+                        }
+                        if self.labels.contains(name) {
+                            return Some(format!("{}{}", name, suffix));
+                        }
+                        None
+                    }),
+                )
+            })
+            .collect()
+    }
+
+    /// Replaces names in code part of line, character literals and comments are left untouched
+    fn substitute(&self, line: &str, replacement: impl Fn(&str) -> Option<String>) -> String {
+        let chars: Vec<(usize, char)> = line.char_indices().collect();
+        let mut result = String::new();
+        let mut i = 0;
+        while i < chars.len() {
+            let (start, c) = chars[i];
+            match c {
+                '#' => {
+                    result += &line[start..];
+                    break;
+                }
+                '\'' if chars.get(i + 2).is_some_and(|(_, c)| *c == '\'') => {
+                    let end = chars.get(i + 3).map(|(end, _)| *end).unwrap_or(line.len());
+                    result += &line[start..end];
+                    i += 3;
+                }
+                c if c.is_alphanumeric() || c == '_' => {
+                    let mut j = i;
+                    while j < chars.len() && (chars[j].1.is_alphanumeric() || chars[j].1 == '_') {
+                        j += 1;
+                    }
+                    let end = chars.get(j).map(|(end, _)| *end).unwrap_or(line.len());
+                    let name = &line[start..end];
+                    match replacement(name) {
+                        Some(replaced) => result += &replaced,
+                        None => result += name,
+                    }
+                    i = j;
+                }
+                c => {
+                    result.push(c);
+                    i += 1;
+                }
+            }
+        }
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_macro_expansion() {
+        let handshake = Macro::new(
+            "handshake",
+            vec!["port".to_string(), "value".to_string()],
+            vec![
+                (1, "    wait:".to_string()),
+                (2, "        CMP port value # port is compared".to_string()),
+                (3, "        JNE wait".to_string()),
+                (4, "        MOV 'v' port1".to_string()),
+            ],
+        );
+
+        let expanded = handshake.expand(&["p0", "7"], "@1");
+
+        assert_eq!(
+            expanded,
+            vec![
+                (1, "    wait@1:".to_string()),
+                (2, "        CMP p0 7 # port is compared".to_string()),
+                (3, "        JNE wait@1".to_string()),
+                (4, "        MOV 'v' port1".to_string()),
+            ]
+        );
+    }
+}
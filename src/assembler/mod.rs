@@ -1,3 +1,8 @@
 #[allow(clippy::module_inception)]
 pub mod assembler;
+pub mod diagnostic;
+pub mod disassembler;
+pub mod expression;
+pub mod macros;
 pub mod parsing_err;
+pub mod resolver;
@@ -3,21 +3,85 @@ use std::fmt;
 
 use rust_i18n::t;
 
+use super::diagnostic::Diagnostic;
+
 /// Data contained in parsing error
 #[derive(Debug, serde::Deserialize, serde::Serialize, Clone, PartialEq)]
 pub struct ParsingErrorData {
     line: usize,
     message: String,
+    /// Range of columns (start inclusive, end exclusive, in bytes) of offending token,
+    /// None if error refers to whole line
+    #[serde(default)]
+    span: Option<(usize, usize)>,
+    /// Text of offending token
+    #[serde(default)]
+    token: String,
+    /// Localized hint describing what was expected instead of token
+    #[serde(default)]
+    expected: Option<String>,
+    /// Included file and macro error occurred in, None for line of main program
+    #[serde(default)]
+    origin: Option<Box<ErrorOrigin>>,
+}
+
+/// Where error occurred, if it is not a line written in main program
+#[derive(Debug, serde::Deserialize, serde::Serialize, Clone, PartialEq, Default)]
+struct ErrorOrigin {
+    /// Name of included file error occurred in
+    file: Option<String>,
+    /// Name of macro and line in its body, if error occurred in macro expansion
+    /// (line of error is then the line macro was invoked at)
+    expansion: Option<(String, usize)>,
 }
 
 impl ParsingErrorData {
     fn new(line: usize, message: String) -> ParsingErrorData {
-        ParsingErrorData { line, message }
+        ParsingErrorData {
+            line,
+            message,
+            span: None,
+            token: String::new(),
+            expected: None,
+            origin: None,
+        }
+    }
+
+    pub fn get_line(&self) -> usize {
+        self.line
+    }
+
+    pub fn get_span(&self) -> Option<(usize, usize)> {
+        self.span
+    }
+
+    pub fn get_token(&self) -> String {
+        self.token.clone()
+    }
+
+    pub fn get_expected(&self) -> Option<String> {
+        self.expected.clone()
+    }
+
+    pub fn get_expansion(&self) -> Option<(String, usize)> {
+        self.origin.as_ref()?.expansion.clone()
+    }
+
+    pub fn get_file(&self) -> Option<String> {
+        self.origin.as_ref()?.file.clone()
+    }
+
+    /// Gets location of error: line number (starting from 1), preceded by file name for included files
+    pub fn get_location(&self) -> String {
+        match self.get_file() {
+            Some(file) => format!("{}:{}", file, self.line + 1),
+            None => (self.line + 1).to_string(),
+        }
     }
 }
 
 /// Represents errors that can occur while attempting to assemble code to SVA instruction
-#[derive(Debug, serde::Deserialize, serde::Serialize, PartialEq)]
+#[derive(Debug, serde::Deserialize, serde::Serialize, Clone, PartialEq)]
 pub enum ParsingError {
     EmptyOperand(ParsingErrorData),
     InvalidPortNumber(ParsingErrorData),
@@ -33,6 +97,30 @@ pub enum ParsingError {
     NotEnoughOperands(ParsingErrorData),
     TooManyOperands(ParsingErrorData),
     StackNotPresent(ParsingErrorData),
+    UndefinedLabel(ParsingErrorData),
+    DuplicateLabel(ParsingErrorData),
+    UnusedLabel(ParsingErrorData),
+    NoSuchDirective(ParsingErrorData),
+    InvalidConstantName(ParsingErrorData),
+    DuplicateConstant(ParsingErrorData),
+    UndefinedConstant(ParsingErrorData),
+    InvalidExpression(ParsingErrorData),
+    DivisionByZero(ParsingErrorData),
+    ExpressionOverflow(ParsingErrorData),
+    InvalidMacroName(ParsingErrorData),
+    DuplicateMacro(ParsingErrorData),
+    UnterminatedMacro(ParsingErrorData),
+    UnmatchedEndMacro(ParsingErrorData),
+    MacroRecursion(ParsingErrorData),
+    IncludeNotFound(ParsingErrorData),
+    IncludeCycle(ParsingErrorData),
+    IncludeTooDeep(ParsingErrorData),
+    UnterminatedCondition(ParsingErrorData),
+    UnmatchedCondition(ParsingErrorData),
+    InvalidAliasName(ParsingErrorData),
+    DuplicateAlias(ParsingErrorData),
+    AliasShadowsRegister(ParsingErrorData),
+    InvalidMemoryOperand(ParsingErrorData),
 }
 
 impl ParsingError {
@@ -60,17 +148,159 @@ impl ParsingError {
             ParsingError::TooManyOperands(data) => data.clone(),
             ParsingError::StackNotPresent(data) => data.clone(),
             ParsingError::InvalidCharLiteral(data) => data.clone(),
+            ParsingError::UndefinedLabel(data) => data.clone(),
+            ParsingError::DuplicateLabel(data) => data.clone(),
+            ParsingError::UnusedLabel(data) => data.clone(),
+            ParsingError::NoSuchDirective(data) => data.clone(),
+            ParsingError::InvalidConstantName(data) => data.clone(),
+            ParsingError::DuplicateConstant(data) => data.clone(),
+            ParsingError::UndefinedConstant(data) => data.clone(),
+            ParsingError::InvalidExpression(data) => data.clone(),
+            ParsingError::DivisionByZero(data) => data.clone(),
+            ParsingError::ExpressionOverflow(data) => data.clone(),
+            ParsingError::InvalidMacroName(data) => data.clone(),
+            ParsingError::DuplicateMacro(data) => data.clone(),
+            ParsingError::UnterminatedMacro(data) => data.clone(),
+            ParsingError::UnmatchedEndMacro(data) => data.clone(),
+            ParsingError::MacroRecursion(data) => data.clone(),
+            ParsingError::IncludeNotFound(data) => data.clone(),
+            ParsingError::IncludeCycle(data) => data.clone(),
+            ParsingError::IncludeTooDeep(data) => data.clone(),
+            ParsingError::UnterminatedCondition(data) => data.clone(),
+            ParsingError::UnmatchedCondition(data) => data.clone(),
+            ParsingError::InvalidAliasName(data) => data.clone(),
+            ParsingError::DuplicateAlias(data) => data.clone(),
+            ParsingError::AliasShadowsRegister(data) => data.clone(),
+            ParsingError::InvalidMemoryOperand(data) => data.clone(),
+        }
+    }
+
+    fn get_data_mut(&mut self) -> &mut ParsingErrorData {
+        match self {
+            ParsingError::EmptyOperand(data) => data,
+            ParsingError::InvalidPortNumber(data) => data,
+            ParsingError::InvalidRegisterNumber(data) => data,
+            ParsingError::InvalidOperandType(data) => data,
+            ParsingError::InvalidNumericLiteral(data) => data,
+            ParsingError::InvalidBinaryLiteral(data) => data,
+            ParsingError::InvalidHexLiteral(data) => data,
+            ParsingError::Empty(data) => data,
+            ParsingError::NoSuchInstruction(data) => data,
+            ParsingError::NotImplanted(data) => data,
+            ParsingError::NotEnoughOperands(data) => data,
+            ParsingError::TooManyOperands(data) => data,
+            ParsingError::StackNotPresent(data) => data,
+            ParsingError::InvalidCharLiteral(data) => data,
+            ParsingError::UndefinedLabel(data) => data,
+            ParsingError::DuplicateLabel(data) => data,
+            ParsingError::UnusedLabel(data) => data,
+            ParsingError::NoSuchDirective(data) => data,
+            ParsingError::InvalidConstantName(data) => data,
+            ParsingError::DuplicateConstant(data) => data,
+            ParsingError::UndefinedConstant(data) => data,
+            ParsingError::InvalidExpression(data) => data,
+            ParsingError::DivisionByZero(data) => data,
+            ParsingError::ExpressionOverflow(data) => data,
+            ParsingError::InvalidMacroName(data) => data,
+            ParsingError::DuplicateMacro(data) => data,
+            ParsingError::UnterminatedMacro(data) => data,
+            ParsingError::UnmatchedEndMacro(data) => data,
+            ParsingError::MacroRecursion(data) => data,
+            ParsingError::IncludeNotFound(data) => data,
+            ParsingError::IncludeCycle(data) => data,
+            ParsingError::IncludeTooDeep(data) => data,
+            ParsingError::UnterminatedCondition(data) => data,
+            ParsingError::UnmatchedCondition(data) => data,
+            ParsingError::InvalidAliasName(data) => data,
+            ParsingError::DuplicateAlias(data) => data,
+            ParsingError::AliasShadowsRegister(data) => data,
+            ParsingError::InvalidMemoryOperand(data) => data,
+        }
+    }
+
+    /// Sets offending token and its position, unless already set
+    ///
+    /// ### Arguments
+    /// * 'token' - text of token
+    /// * 'column' - byte offset of token in line
+    pub fn with_token(self, token: &str, column: usize) -> ParsingError {
+        self.with_span((column, column + token.len()), token)
+    }
+
+    /// Sets range of columns error refers to and text found there, unless already set
+    ///
+    /// ### Arguments
+    /// * 'span' - start (inclusive) and end (exclusive) byte offset in line
+    /// * 'token' - text found in span, empty if it should not be shown in hint
+    pub fn with_span(mut self, span: (usize, usize), token: &str) -> ParsingError {
+        let data = self.get_data_mut();
+        if data.span.is_none() {
+            data.span = Some(span);
+            data.token = token.to_string();
+        }
+        self
+    }
+
+    /// Moves span of error by offset, used when error was found in part of line
+    ///
+    /// ### Arguments
+    /// * 'offset' - byte offset of part of line in whole line
+    pub fn with_offset(mut self, offset: usize) -> ParsingError {
+        let data = self.get_data_mut();
+        if let Some((start, end)) = data.span {
+            data.span = Some((start + offset, end + offset));
         }
+        self
+    }
+
+    /// Marks error as occurred in macro expansion, unless already marked
+    ///
+    /// Span is cleared, as it refers to expanded code and not to line macro was invoked at
+    ///
+    /// ### Arguments
+    /// * 'name' - name of macro
+    /// * 'body_line' - line number of macro body line error occurred in
+    pub fn in_macro(mut self, name: &str, body_line: usize) -> ParsingError {
+        let data = self.get_data_mut();
+        let origin = data.origin.get_or_insert_with(Default::default);
+        if origin.expansion.is_none() {
+            origin.expansion = Some((name.to_string(), body_line));
+            data.span = None;
+        }
+        self
+    }
+
+    /// Marks error as occurred in included file, unless already marked
+    ///
+    /// ### Arguments
+    /// * 'file' - name of included file
+    pub fn in_file(mut self, file: &str) -> ParsingError {
+        let origin = self
+            .get_data_mut()
+            .origin
+            .get_or_insert_with(Default::default);
+        if origin.file.is_none() {
+            origin.file = Some(file.to_string());
+        }
+        self
+    }
+
+    /// Sets hint describing what was expected, unless already set
+    pub fn with_expected(mut self, expected: String) -> ParsingError {
+        let data = self.get_data_mut();
+        if data.expected.is_none() {
+            data.expected = Some(expected);
+        }
+        self
     }
 
     pub fn get_message(&self) -> String {
         self.get_data().message
     }
-}
 
-impl fmt::Display for ParsingError {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        let error_type = match self {
+    /// Gets localized name of error type
+    pub fn get_error_type(&self) -> String {
+        match self {
             ParsingError::EmptyOperand(_) => t!("error.empty_operand"),
             ParsingError::InvalidPortNumber(_) => t!("error.invalid_port_number"),
             ParsingError::InvalidRegisterNumber(_) => t!("error.invalid_register_number"),
@@ -85,18 +315,103 @@ impl fmt::Display for ParsingError {
             ParsingError::TooManyOperands(_) => t!("error.too_many_operands"),
             ParsingError::StackNotPresent(_) => t!("error.stack_not_present"),
             ParsingError::InvalidCharLiteral(_) => t!("error.invalid_char_literal"),
+            ParsingError::UndefinedLabel(_) => t!("error.undefined_label"),
+            ParsingError::DuplicateLabel(_) => t!("error.duplicate_label"),
+            ParsingError::UnusedLabel(_) => t!("error.unused_label"),
+            ParsingError::NoSuchDirective(_) => t!("error.no_such_directive"),
+            ParsingError::InvalidConstantName(_) => t!("error.invalid_constant_name"),
+            ParsingError::DuplicateConstant(_) => t!("error.duplicate_constant"),
+            ParsingError::UndefinedConstant(_) => t!("error.undefined_constant"),
+            ParsingError::InvalidExpression(_) => t!("error.invalid_expression"),
+            ParsingError::DivisionByZero(_) => t!("error.division_by_zero"),
+            ParsingError::ExpressionOverflow(_) => t!("error.expression_overflow"),
+            ParsingError::InvalidMacroName(_) => t!("error.invalid_macro_name"),
+            ParsingError::DuplicateMacro(_) => t!("error.duplicate_macro"),
+            ParsingError::UnterminatedMacro(_) => t!("error.unterminated_macro"),
+            ParsingError::UnmatchedEndMacro(_) => t!("error.unmatched_end_macro"),
+            ParsingError::MacroRecursion(_) => t!("error.macro_recursion"),
+            ParsingError::IncludeNotFound(_) => t!("error.include_not_found"),
+            ParsingError::IncludeCycle(_) => t!("error.include_cycle"),
+            ParsingError::IncludeTooDeep(_) => t!("error.include_too_deep"),
+            ParsingError::UnterminatedCondition(_) => t!("error.unterminated_condition"),
+            ParsingError::UnmatchedCondition(_) => t!("error.unmatched_condition"),
+            ParsingError::InvalidAliasName(_) => t!("error.invalid_alias_name"),
+            ParsingError::DuplicateAlias(_) => t!("error.duplicate_alias"),
+            ParsingError::AliasShadowsRegister(_) => t!("error.alias_shadows_register"),
+            ParsingError::InvalidMemoryOperand(_) => t!("error.invalid_memory_operand"),
+        }
+    }
+
+    /// Gets localized hint, e.g. "expected register r0..r3, found r7"
+    pub fn get_hint(&self) -> Option<String> {
+        let data = self.get_data();
+        let expected = data.expected?;
+        if data.token.is_empty() {
+            return Some(t!("hint.expected", expected = expected));
+        }
+        Some(t!(
+            "hint.expected_found",
+            expected = expected,
+            found = data.token
+        ))
+    }
+
+    /// Gets localized note about macro expansion error occurred in, e.g. "in macro 'wait' at line 3"
+    pub fn get_note(&self) -> Option<String> {
+        let (name, body_line) = self.get_data().get_expansion()?;
+        Some(t!("hint.in_macro", name = name, line = body_line + 1))
+    }
+
+    /// Gets details of error - message, hint and note
+    pub fn get_details(&self) -> String {
+        let details: Vec<String> = [Some(self.get_message()), self.get_hint(), self.get_note()]
+            .into_iter()
+            .flatten()
+            .filter(|detail| !detail.is_empty())
+            .collect();
+        details.join(", ")
+    }
+
+    /// Gets localized description of error (error type and details) without line number
+    pub fn get_description(&self) -> String {
+        let details = self.get_details();
+        if details.is_empty() {
+            return self.get_error_type();
+        }
+        format!("{}\t{}", self.get_error_type(), details)
+    }
+
+    /// Renders error with line of source code and offending token underlined, rustc-style
+    ///
+    /// ### Arguments
+    /// * 'program_text' - whole source code error was produced for
+    ///
+    /// Text of included files is not known here, so their lines are not shown,
+    /// diagnostics returned by assembler render them
+    pub fn render(&self, program_text: &str) -> String {
+        let data = self.get_data();
+        let source_line = match data.get_file() {
+            Some(_) => "",
+            None => program_text.lines().nth(data.line).unwrap_or(""),
         };
+        Diagnostic::error(self.clone(), source_line).render(program_text)
+    }
+}
+
+impl fmt::Display for ParsingError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let error_type = self.get_error_type();
 
         let error_data = self.get_data();
         // format of error message:
-        // { parsing error } : { line } \t { error type } \t { details/message }
+        // { parsing error } : { file : }? { line } \t { error type } \t { details/message }
         write!(
             f,
             "{}:{}\t{}\t{}",
             t!("error.parsing"),
-            error_data.line + 1,
+            error_data.get_location(),
             error_type,
-            error_data.message
+            self.get_details()
         )
     }
 }
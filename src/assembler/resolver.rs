@@ -0,0 +1,149 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Provides source code of files included with `.include` directive
+pub trait SourceResolver {
+    /// Finds file included by name
+    ///
+    /// ### Arguments
+    /// * 'name' - name of file, as written in .include directive
+    /// * 'including' - canonical name of file containing directive, None for main program
+    ///
+    /// ### Returns
+    /// * name that is the same for all names referring to the same file, used to resolve it
+    ///   and to detect include cycles, None if there is no such file
+    fn canonical_name(&self, name: &str, _including: Option<&str>) -> Option<String> {
+        Some(name.to_string())
+    }
+
+    /// Gets source code of file
+    ///
+    /// ### Arguments
+    /// * 'canonical_name' - name of file returned by canonical_name
+    ///
+    /// ### Returns
+    /// * source code, None if there is no such file
+    fn resolve(&self, canonical_name: &str) -> Option<String>;
+}
+
+/// Resolves included files relative to file including them, main program includes files
+/// relative to root directory
+///
+/// Files outside of root directory can't be included, also with absolute paths or `..`
+pub struct FileSystemResolver {
+    directory: PathBuf,
+}
+
+impl FileSystemResolver {
+    /// Creates resolver
+    ///
+    /// ### Arguments
+    /// * 'directory' - root directory, names included by main program are relative to it
+    pub fn new(directory: impl Into<PathBuf>) -> FileSystemResolver {
+        FileSystemResolver {
+            directory: directory.into(),
+        }
+    }
+}
+
+impl SourceResolver for FileSystemResolver {
+    fn canonical_name(&self, name: &str, including: Option<&str>) -> Option<String> {
+        let root = fs::canonicalize(&self.directory).ok()?;
+        let directory = match including {
+            Some(including) => Path::new(including).parent()?.to_path_buf(),
+            None => root.clone(),
+        };
+        let path = fs::canonicalize(directory.join(name)).ok()?;
+        match path.starts_with(&root) && path.is_file() {
+            true => Some(path.to_string_lossy().to_string()),
+            false => None,
+        }
+    }
+
+    fn resolve(&self, canonical_name: &str) -> Option<String> {
+        fs::read_to_string(canonical_name).ok()
+    }
+}
+
+/// Resolves included files from map of names to source code, used in tests and UI
+#[derive(Debug, Default, Clone)]
+pub struct MemoryResolver {
+    files: HashMap<String, String>,
+}
+
+impl MemoryResolver {
+    pub fn new() -> MemoryResolver {
+        MemoryResolver {
+            files: HashMap::new(),
+        }
+    }
+
+    /// Adds file to resolver
+    ///
+    /// ### Arguments
+    /// * 'name' - name file is included by
+    /// * 'text' - source code of file
+    pub fn with_file(mut self, name: &str, text: &str) -> MemoryResolver {
+        self.insert(name, text);
+        self
+    }
+
+    /// Adds file to resolver, replacing file with the same name
+    pub fn insert(&mut self, name: &str, text: &str) {
+        self.files.insert(name.to_string(), text.to_string());
+    }
+}
+
+impl SourceResolver for MemoryResolver {
+    fn resolve(&self, canonical_name: &str) -> Option<String> {
+        self.files.get(canonical_name).cloned()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_file_system_resolver() {
+        let root = std::env::temp_dir().join("sva_resolver_test");
+        fs::create_dir_all(root.join("lib")).unwrap();
+        fs::write(root.join("main.sva"), "NOP").unwrap();
+        fs::write(root.join("lib").join("math.sva"), "INC").unwrap();
+        fs::write(root.join("lib").join("util.sva"), "DEC").unwrap();
+        let outside = std::env::temp_dir().join("sva_resolver_outside.sva");
+        fs::write(&outside, "HLT").unwrap();
+
+        let resolver = FileSystemResolver::new(&root);
+
+        let math = resolver.canonical_name("lib/math.sva", None).unwrap();
+        assert_eq!(resolver.resolve(&math), Some("INC".to_string()));
+        assert_eq!(
+            resolver.canonical_name("./lib/../lib/math.sva", None),
+            Some(math.clone())
+        );
+        assert_eq!(resolver.canonical_name("missing.sva", None), None);
+
+        // nested includes are relative to including file
+        let util = resolver.canonical_name("util.sva", Some(&math)).unwrap();
+        assert_eq!(resolver.resolve(&util), Some("DEC".to_string()));
+        assert!(resolver
+            .canonical_name("../main.sva", Some(&math))
+            .is_some());
+
+        // files outside of root are rejected
+        assert_eq!(
+            resolver.canonical_name("../../sva_resolver_outside.sva", Some(&math)),
+            None
+        );
+        assert_eq!(
+            resolver.canonical_name("../sva_resolver_outside.sva", None),
+            None
+        );
+        assert_eq!(
+            resolver.canonical_name(&outside.to_string_lossy(), None),
+            None
+        );
+    }
+}
@@ -1,5 +1,3 @@
-use std::usize;
-
 use serde::{Deserialize, Serialize};
 
 use super::{connection::Connection, port::Port};
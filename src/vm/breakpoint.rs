@@ -0,0 +1,312 @@
+use std::fmt::{self};
+
+use super::{instruction::Instruction, operand::Operand};
+
+/// Relation between operands of breakpoint condition
+#[derive(Debug, PartialEq, Eq, Clone, Copy, serde::Deserialize, serde::Serialize)]
+pub enum Comparison {
+    Equal,
+    NotEqual,
+    Lesser,
+    LesserOrEqual,
+    Greater,
+    GreaterOrEqual,
+}
+
+impl Comparison {
+    /// Gets operator used for comparison in conditions, e.g. "=="
+    pub fn get_symbol(&self) -> &'static str {
+        match self {
+            Comparison::Equal => "==",
+            Comparison::NotEqual => "!=",
+            Comparison::Lesser => "<",
+            Comparison::LesserOrEqual => "<=",
+            Comparison::Greater => ">",
+            Comparison::GreaterOrEqual => ">=",
+        }
+    }
+
+    /// Compares values as signed numbers
+    pub fn compare(&self, value1: i32, value2: i32) -> bool {
+        match self {
+            Comparison::Equal => value1 == value2,
+            Comparison::NotEqual => value1 != value2,
+            Comparison::Lesser => value1 < value2,
+            Comparison::LesserOrEqual => value1 <= value2,
+            Comparison::Greater => value1 > value2,
+            Comparison::GreaterOrEqual => value1 >= value2,
+        }
+    }
+}
+
+/// Condition of breakpoint, checked before instruction at breakpoint is executed
+///
+/// ### Example
+///
+/// ```rs
+/// // acc == 42
+/// let condition = Condition::new(Operand::ACC, Comparison::Equal, Operand::IntegerValue(42));
+/// ```
+#[derive(Debug, PartialEq, Eq, Clone, serde::Deserialize, serde::Serialize)]
+pub struct Condition {
+    operand1: Operand,
+    comparison: Comparison,
+    operand2: Operand,
+}
+
+impl Condition {
+    pub fn new(operand1: Operand, comparison: Comparison, operand2: Operand) -> Condition {
+        Condition {
+            operand1,
+            comparison,
+            operand2,
+        }
+    }
+
+    pub fn get_operands(&self) -> (Operand, Operand) {
+        (self.operand1.clone(), self.operand2.clone())
+    }
+
+    pub fn get_comparison(&self) -> Comparison {
+        self.comparison
+    }
+}
+
+impl fmt::Display for Condition {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} {} {}",
+            self.operand1,
+            self.comparison.get_symbol(),
+            self.operand2
+        )
+    }
+}
+
+/// Place in program breakpoint refers to
+#[derive(Debug, PartialEq, Eq, Clone, serde::Deserialize, serde::Serialize)]
+pub enum Location {
+    /// Index of instruction in program
+    Instruction(usize),
+    /// First instruction after label
+    Label(String),
+}
+
+/// Place vm pauses at before executing instruction, if its condition is met
+#[derive(Debug, PartialEq, Eq, Clone, serde::Deserialize, serde::Serialize)]
+pub struct Breakpoint {
+    location: Location,
+    condition: Option<Condition>,
+}
+
+impl Breakpoint {
+    /// Creates breakpoint at instruction with given index in program
+    pub fn at_instruction(index: usize) -> Breakpoint {
+        Breakpoint {
+            location: Location::Instruction(index),
+            condition: None,
+        }
+    }
+
+    /// Creates breakpoint at first instruction after label
+    pub fn at_label(name: &str) -> Breakpoint {
+        Breakpoint {
+            location: Location::Label(name.to_string()),
+            condition: None,
+        }
+    }
+
+    /// Makes vm pause at breakpoint only if condition is met
+    pub fn with_condition(mut self, condition: Condition) -> Breakpoint {
+        self.condition = Some(condition);
+        self
+    }
+
+    pub fn get_location(&self) -> Location {
+        self.location.clone()
+    }
+
+    pub fn get_condition(&self) -> Option<Condition> {
+        self.condition.clone()
+    }
+}
+
+/// Place watched by watchpoint
+#[derive(Debug, PartialEq, Eq, Clone, serde::Deserialize, serde::Serialize)]
+pub enum Watched {
+    /// Register, port, acc, pc or memory cell, memory operands refer to the same cell
+    /// if their addresses are equal when it is accessed
+    Operand(Operand),
+    /// Number of values on stack, read by POP and written by PSH, POP, CALL and RET
+    StackDepth,
+}
+
+/// Kind of access that triggers watchpoint
+#[derive(Debug, PartialEq, Eq, Clone, Copy, serde::Deserialize, serde::Serialize)]
+pub enum Access {
+    Read,
+    Write,
+    /// Write of value different from previous one
+    Change,
+}
+
+/// Pauses vm after instruction that accessed watched place
+///
+/// ### Example
+///
+/// ```rs
+/// // pause when anything writes 0 to p1
+/// let watchpoint = Watchpoint::new(Watched::Operand(Operand::PortRegister(1)), Access::Write)
+///     .with_value(Comparison::Equal, 0);
+/// ```
+#[derive(Debug, PartialEq, Eq, Clone, serde::Deserialize, serde::Serialize)]
+pub struct Watchpoint {
+    watched: Watched,
+    access: Access,
+    /// Predicate value read or written has to match
+    value: Option<(Comparison, i32)>,
+}
+
+impl Watchpoint {
+    pub fn new(watched: Watched, access: Access) -> Watchpoint {
+        Watchpoint {
+            watched,
+            access,
+            value: None,
+        }
+    }
+
+    /// Makes watchpoint trigger only if value read or written is in given relation to 'value'
+    pub fn with_value(mut self, comparison: Comparison, value: i32) -> Watchpoint {
+        self.value = Some((comparison, value));
+        self
+    }
+
+    pub fn get_watched(&self) -> Watched {
+        self.watched.clone()
+    }
+
+    pub fn get_access(&self) -> Access {
+        self.access
+    }
+
+    /// Checks if access to watched place triggers watchpoint
+    ///
+    /// ### Arguments
+    /// * 'access' - Read or Write
+    /// * 'old_value' - value before access
+    /// * 'new_value' - value after access, the same as old one for reads
+    pub fn is_triggered_by(&self, access: Access, old_value: i32, new_value: i32) -> bool {
+        let access_matches = match self.access {
+            Access::Change => access == Access::Write && old_value != new_value,
+            watched_access => watched_access == access,
+        };
+        access_matches
+            && self
+                .value
+                .is_none_or(|(comparison, value)| comparison.compare(new_value, value))
+    }
+}
+
+/// Report of triggered watchpoint
+#[derive(Debug, PartialEq, Clone, serde::Deserialize, serde::Serialize)]
+pub struct WatchpointHit {
+    watchpoint: Watchpoint,
+    /// Index of instruction that triggered watchpoint
+    pc: usize,
+    instruction: Instruction,
+    access: Access,
+    old_value: i32,
+    new_value: i32,
+}
+
+impl WatchpointHit {
+    pub fn new(
+        watchpoint: Watchpoint,
+        pc: usize,
+        instruction: Instruction,
+        access: Access,
+        values: (i32, i32),
+    ) -> WatchpointHit {
+        WatchpointHit {
+            watchpoint,
+            pc,
+            instruction,
+            access,
+            old_value: values.0,
+            new_value: values.1,
+        }
+    }
+
+    pub fn get_watchpoint(&self) -> Watchpoint {
+        self.watchpoint.clone()
+    }
+
+    pub fn get_pc(&self) -> usize {
+        self.pc
+    }
+
+    pub fn get_instruction(&self) -> Instruction {
+        self.instruction.clone()
+    }
+
+    pub fn get_access(&self) -> Access {
+        self.access
+    }
+
+    /// Gets values before and after access
+    pub fn get_values(&self) -> (i32, i32) {
+        (self.old_value, self.new_value)
+    }
+}
+
+impl fmt::Display for WatchpointHit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let watched = match &self.watchpoint.watched {
+            Watched::Operand(operand) => operand.to_string(),
+            Watched::StackDepth => t!("watchpoint.stack_depth"),
+        };
+        let message = t!(
+            "watchpoint.hit",
+            access = format!("{:?}", self.access),
+            watched = watched,
+            pc = self.pc,
+            instruction = self.instruction,
+            old_value = self.old_value,
+            new_value = self.new_value
+        );
+        write!(f, "{}", message)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_condition_display() {
+        let condition = Condition::new(
+            Operand::Memory(1, 2),
+            Comparison::GreaterOrEqual,
+            Operand::IntegerValue(-3),
+        );
+        assert_eq!(condition.to_string(), "[r1+2] >= -3");
+        assert!(Comparison::LesserOrEqual.compare(-3, -3));
+        assert!(!Comparison::NotEqual.compare(7, 7));
+    }
+
+    #[test]
+    fn test_watchpoint_triggers() {
+        let watched = Watched::Operand(Operand::ACC);
+        let change = Watchpoint::new(watched.clone(), Access::Change);
+        assert!(change.is_triggered_by(Access::Write, 1, 2));
+        assert!(!change.is_triggered_by(Access::Write, 2, 2));
+        assert!(!change.is_triggered_by(Access::Read, 1, 2));
+
+        let write = Watchpoint::new(watched, Access::Write).with_value(Comparison::Lesser, 0);
+        assert!(write.is_triggered_by(Access::Write, -1, -1));
+        assert!(!write.is_triggered_by(Access::Write, -1, 0));
+        assert!(!write.is_triggered_by(Access::Read, -1, -1));
+    }
+}
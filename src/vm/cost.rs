@@ -0,0 +1,158 @@
+use std::collections::HashMap;
+use std::fmt::{self};
+
+use super::{instruction::Instruction, opcodes::Opcode};
+
+/// Number of cycles instructions take, used to score programs
+///
+/// Instructions missing in table take default cost, labels take no cycles
+///
+/// ### Example
+///
+/// ```rs
+/// let costs = CostTable::new().with_cost("MUL", 3)?.with_cost("DIV", 8)?;
+/// let vm = VirtualMachine::new_with_program(program).with_costs(costs);
+/// ```
+#[derive(Debug, PartialEq, Eq, Clone, serde::Deserialize, serde::Serialize)]
+#[serde(try_from = "RawCostTable")]
+pub struct CostTable {
+    /// Cost of instructions missing in table
+    default_cost: u64,
+    /// Costs of instructions by mnemonic, every key is one of Opcode::MNEMONICS
+    costs: HashMap<String, u64>,
+}
+
+/// Cost table as serialized, mnemonics are checked before it becomes CostTable
+#[derive(serde::Deserialize)]
+struct RawCostTable {
+    default_cost: u64,
+    costs: HashMap<String, u64>,
+}
+
+impl TryFrom<RawCostTable> for CostTable {
+    type Error = CostError;
+
+    fn try_from(raw: RawCostTable) -> Result<Self, Self::Error> {
+        raw.costs.into_iter().try_fold(
+            CostTable::new().with_default_cost(raw.default_cost),
+            |costs, (mnemonic, cost)| costs.with_cost(&mnemonic, cost),
+        )
+    }
+}
+
+/// Error in cost table
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum CostError {
+    /// Cost set for mnemonic that is not mnemonic of any opcode
+    UnknownMnemonic(String),
+}
+
+impl fmt::Display for CostError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let message = match self {
+            CostError::UnknownMnemonic(mnemonic) => {
+                t!("cost.unknown_mnemonic", mnemonic = mnemonic)
+            }
+        };
+        write!(f, "{}", message)
+    }
+}
+
+impl Default for CostTable {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl CostTable {
+    /// Creates table where every instruction takes one cycle
+    pub fn new() -> CostTable {
+        CostTable {
+            default_cost: 1,
+            costs: HashMap::new(),
+        }
+    }
+
+    /// Sets cost of instruction
+    ///
+    /// ### Arguments
+    ///
+    /// * 'mnemonic' - mnemonic of instruction, e.g. "MUL", case insensitive
+    /// * 'cost' - number of cycles instruction takes
+    ///
+    /// ### Returns
+    /// * UnknownMnemonic error if no opcode has mnemonic, e.g. it is misspelled
+    pub fn with_cost(mut self, mnemonic: &str, cost: u64) -> Result<CostTable, CostError> {
+        let mnemonic = mnemonic.to_uppercase();
+        if !Opcode::MNEMONICS.contains(&mnemonic.as_str()) {
+            return Err(CostError::UnknownMnemonic(mnemonic));
+        }
+        self.costs.insert(mnemonic, cost);
+        Ok(self)
+    }
+
+    /// Sets cost of instructions missing in table
+    pub fn with_default_cost(mut self, cost: u64) -> CostTable {
+        self.default_cost = cost;
+        self
+    }
+
+    pub fn get_default_cost(&self) -> u64 {
+        self.default_cost
+    }
+
+    pub fn get_costs(&self) -> HashMap<String, u64> {
+        self.costs.clone()
+    }
+
+    /// Gets number of cycles instruction takes
+    pub fn get_cost(&self, instruction: &Instruction) -> u64 {
+        match instruction {
+            Instruction::Opcode(opcode) => self
+                .costs
+                .get(opcode.get_mnemonic())
+                .copied()
+                .unwrap_or(self.default_cost),
+            Instruction::Label(..) => 0,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::vm::operand::Operand;
+
+    #[test]
+    fn test_cost_table() {
+        let costs = CostTable::new()
+            .with_cost("mul", 3)
+            .and_then(|costs| costs.with_cost("DIV", 8))
+            .unwrap()
+            .with_default_cost(2);
+        let mul = Instruction::new(Opcode::MUL(Operand::IntegerValue(2)));
+        let div = Instruction::new(Opcode::DIV(Operand::GeneralRegister(0)));
+        assert_eq!(costs.get_cost(&mul), 3);
+        assert_eq!(costs.get_cost(&div), 8);
+        assert_eq!(costs.get_cost(&Instruction::new(Opcode::NOP)), 2);
+        assert_eq!(
+            costs.get_cost(&Instruction::new_label("loop".to_string(), 0, 0)),
+            0
+        );
+        assert_eq!(CostTable::default().get_cost(&mul), 1);
+    }
+
+    #[test]
+    fn test_cost_table_rejects_unknown_mnemonics() {
+        assert_eq!(
+            CostTable::new().with_cost("MULL", 3),
+            Err(CostError::UnknownMnemonic("MULL".to_string()))
+        );
+
+        let costs = CostTable::new().with_cost("MUL", 3).unwrap();
+        let json = serde_json::to_string(&costs).unwrap();
+        assert_eq!(serde_json::from_str::<CostTable>(&json).unwrap(), costs);
+        let json = r#"{"default_cost":1,"costs":{"MULL":3}}"#;
+        assert!(serde_json::from_str::<CostTable>(json).is_err());
+    }
+}
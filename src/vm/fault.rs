@@ -0,0 +1,47 @@
+use std::fmt::{self};
+
+/// Error in program detected while it runs, vm stops at instruction causing it
+#[derive(Debug, PartialEq, Eq, Clone, Copy, serde::Deserialize, serde::Serialize)]
+pub enum VmFault {
+    /// DIV or MOD with divisor 0
+    DivideByZero,
+    /// Value written to pc is outside of program
+    InvalidJumpTarget(i32),
+    /// Jump to label that is not in program
+    UndefinedLabel,
+    /// PSH with full stack
+    StackOverflow,
+    /// POP with empty stack
+    StackUnderflow,
+    /// PSH or POP on vm without stack
+    StackNotPresent,
+    /// Register or port that vm doesn't have, or integer used as destination
+    IllegalOperand,
+    /// Memory operand refers to address outside of data memory
+    InvalidMemoryAddress(i64),
+    /// CALL on vm with stack, return address doesn't fit in word
+    ReturnAddressOverflow(usize),
+}
+
+impl fmt::Display for VmFault {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let message = match self {
+            VmFault::DivideByZero => t!("fault.divide_by_zero"),
+            VmFault::InvalidJumpTarget(target) => {
+                t!("fault.invalid_jump_target", target = target)
+            }
+            VmFault::UndefinedLabel => t!("fault.undefined_label"),
+            VmFault::StackOverflow => t!("fault.stack_overflow"),
+            VmFault::StackUnderflow => t!("fault.stack_underflow"),
+            VmFault::StackNotPresent => t!("fault.stack_not_present"),
+            VmFault::IllegalOperand => t!("fault.illegal_operand"),
+            VmFault::InvalidMemoryAddress(address) => {
+                t!("fault.invalid_memory_address", address = address)
+            }
+            VmFault::ReturnAddressOverflow(address) => {
+                t!("fault.return_address_overflow", address = address)
+            }
+        };
+        write!(f, "{}", message)
+    }
+}
@@ -1,20 +1,205 @@
 use std::fmt::{self};
 
-/// Possible states of Flag register of SVA
+/// Status register of SVA, set by arithmetic, logic, shift operations and CMP
+///
+/// Comparison results are derived from bits the same way as after subtraction,
+/// so `CMP a b` followed by JL jumps if a < b, even if a - b overflows
 #[derive(Debug, PartialEq, Eq, Clone, Copy, serde::Deserialize, serde::Serialize)]
+#[serde(from = "SavedFlag")]
+pub struct Flag {
+    /// Result was 0
+    zero: bool,
+    /// Unsigned result didn't fit in word, borrow for subtraction, last bit shifted out for shifts
+    carry: bool,
+    /// Signed result didn't fit in word
+    overflow: bool,
+    /// Result was negative
+    negative: bool,
+}
+
+/// Flag as saved, vm saved before status bits were added has result of last comparison
+#[derive(serde::Deserialize)]
+#[serde(untagged)]
+enum SavedFlag {
+    Bits {
+        zero: bool,
+        carry: bool,
+        overflow: bool,
+        negative: bool,
+    },
+    Comparison(ComparisonResult),
+}
 
-pub enum Flag {
-    EQUAL = -1,
-    GREATER = 0,
-    LESSER = 1,
+/// Result of last comparison, used as flag before status bits were added
+#[derive(serde::Deserialize)]
+#[allow(clippy::upper_case_acronyms)]
+enum ComparisonResult {
+    EQUAL,
+    GREATER,
+    LESSER,
+}
+
+impl From<SavedFlag> for Flag {
+    fn from(saved: SavedFlag) -> Self {
+        match saved {
+            SavedFlag::Bits {
+                zero,
+                carry,
+                overflow,
+                negative,
+            } => Flag {
+                zero,
+                carry,
+                overflow,
+                negative,
+            },
+            // bits set by CMP giving the same comparison result
+            SavedFlag::Comparison(ComparisonResult::EQUAL) => Flag::from_result(0, false, false),
+            SavedFlag::Comparison(ComparisonResult::GREATER) => Flag::from_result(1, false, false),
+            SavedFlag::Comparison(ComparisonResult::LESSER) => Flag::from_result(-1, false, false),
+        }
+    }
+}
+
+impl Default for Flag {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Flag {
+    /// Creates flags of fresh vm, acc is 0 so zero is set
+    pub fn new() -> Flag {
+        Flag::from_result(0, false, false)
+    }
+
+    /// Creates flags describing result of operation
+    ///
+    /// ### Arguments
+    /// * 'result' - result of operation, wrapped to word size
+    /// * 'carry' - whether unsigned result didn't fit in word
+    /// * 'overflow' - whether signed result didn't fit in word
+    pub fn from_result(result: i32, carry: bool, overflow: bool) -> Flag {
+        Flag {
+            zero: result == 0,
+            carry,
+            overflow,
+            negative: result < 0,
+        }
+    }
+
+    /// Creates flags from bits of to_bits
+    pub fn from_bits(bits: u8) -> Flag {
+        Flag {
+            zero: bits & 0b0001 != 0,
+            carry: bits & 0b0010 != 0,
+            overflow: bits & 0b0100 != 0,
+            negative: bits & 0b1000 != 0,
+        }
+    }
+
+    /// Packs flags into bits, zero is the lowest one, followed by carry, overflow and negative
+    pub fn to_bits(&self) -> u8 {
+        u8::from(self.zero)
+            | u8::from(self.carry) << 1
+            | u8::from(self.overflow) << 2
+            | u8::from(self.negative) << 3
+    }
+
+    pub fn is_zero(&self) -> bool {
+        self.zero
+    }
+
+    pub fn is_carry(&self) -> bool {
+        self.carry
+    }
+
+    pub fn is_overflow(&self) -> bool {
+        self.overflow
+    }
+
+    pub fn is_negative(&self) -> bool {
+        self.negative
+    }
+
+    /// Checks if compared operands were equal
+    pub fn is_equal(&self) -> bool {
+        self.zero
+    }
+
+    /// Checks if first compared operand was lesser, as signed numbers
+    pub fn is_lesser(&self) -> bool {
+        self.negative != self.overflow
+    }
+
+    /// Checks if first compared operand was greater, as signed numbers
+    pub fn is_greater(&self) -> bool {
+        !self.zero && self.negative == self.overflow
+    }
+
+    /// Checks if first compared operand was greater, as unsigned numbers
+    pub fn is_above(&self) -> bool {
+        !self.carry && !self.zero
+    }
+
+    /// Checks if first compared operand was lesser, as unsigned numbers
+    pub fn is_below(&self) -> bool {
+        self.carry
+    }
 }
 
 impl fmt::Display for Flag {
+    /// Writes set bits as letters ZCON, cleared bits as '-'
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        match *self {
-            Flag::EQUAL => write!(f, "EQUAL"),
-            Flag::GREATER => write!(f, "GREATER"),
-            Flag::LESSER => write!(f, "LESSER"),
+        let bits = [
+            (self.zero, 'Z'),
+            (self.carry, 'C'),
+            (self.overflow, 'O'),
+            (self.negative, 'N'),
+        ];
+        for (set, letter) in bits {
+            write!(f, "{}", if set { letter } else { '-' })?;
         }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_deserialize_flag() {
+        let flag = Flag::from_result(-3, true, false);
+        let json = serde_json::to_string(&flag).unwrap();
+        assert_eq!(serde_json::from_str::<Flag>(&json).unwrap(), flag);
+
+        // flag saved before status bits were added
+        let flag: Flag = serde_json::from_str("\"EQUAL\"").unwrap();
+        assert!(flag.is_equal());
+        let flag: Flag = serde_json::from_str("\"GREATER\"").unwrap();
+        assert!(flag.is_greater());
+        let flag: Flag = serde_json::from_str("\"LESSER\"").unwrap();
+        assert!(flag.is_lesser());
+        assert!(serde_json::from_str::<Flag>("\"ABOVE\"").is_err());
+    }
+
+    #[test]
+    fn test_flag_comparison_results() {
+        // 5 - 7
+        let flag = Flag::from_result(-2, true, false);
+        assert!(flag.is_lesser() && !flag.is_greater() && !flag.is_equal());
+
+        // i32::MIN - 1 overflows to positive number, but i32::MIN is still lesser
+        let flag = Flag::from_result(i32::MAX, false, true);
+        assert!(flag.is_lesser() && !flag.is_greater());
+
+        let flag = Flag::new();
+        assert!(flag.is_equal() && !flag.is_lesser() && !flag.is_greater());
+        assert_eq!(flag.to_string(), "Z---");
+
+        // -1 - 1 as unsigned is 0xFFFFFFFF - 1, no borrow
+        let flag = Flag::from_result(-2, false, false);
+        assert!(flag.is_lesser() && flag.is_above() && !flag.is_below());
     }
 }
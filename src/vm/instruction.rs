@@ -4,9 +4,42 @@ use std::fmt::{self, Formatter, Result};
 
 /// Represents instruction in SVA, either an opcode or label
 #[derive(PartialEq, Eq, Clone, Debug, serde::Deserialize, serde::Serialize)]
+#[serde(from = "SavedInstruction")]
 pub enum Instruction {
     Opcode(Opcode),
-    Label(String, usize),
+    /// Label with its name, line in source code and index in program
+    ///
+    /// Name of local label is qualified with its scope, e.g. `main.loop`
+    Label(String, usize, usize),
+}
+
+/// Instruction as saved, label saved before index was added has only name and line
+#[derive(serde::Deserialize)]
+enum SavedInstruction {
+    Opcode(Opcode),
+    Label(SavedLabel),
+}
+
+#[derive(serde::Deserialize)]
+#[serde(untagged)]
+enum SavedLabel {
+    Indexed(String, usize, usize),
+    /// Vm used to jump to line of such label, so it becomes its index
+    Unindexed(String, usize),
+}
+
+impl From<SavedInstruction> for Instruction {
+    fn from(saved: SavedInstruction) -> Self {
+        match saved {
+            SavedInstruction::Opcode(opcode) => Instruction::Opcode(opcode),
+            SavedInstruction::Label(SavedLabel::Indexed(name, line, index)) => {
+                Instruction::Label(name, line, index)
+            }
+            SavedInstruction::Label(SavedLabel::Unindexed(name, line)) => {
+                Instruction::Label(name, line, line)
+            }
+        }
+    }
 }
 
 impl Instruction {
@@ -14,14 +47,20 @@ impl Instruction {
         Instruction::Opcode(opcode)
     }
 
-    pub fn new_label(name: String, line: usize) -> Instruction {
-        Instruction::Label(name, line)
+    /// Creates label
+    ///
+    /// ### Arguments
+    /// * 'name' - name of label
+    /// * 'line' - line in source code, used for diagnostics
+    /// * 'index' - index of label in program, used as jump target
+    pub fn new_label(name: String, line: usize, index: usize) -> Instruction {
+        Instruction::Label(name, line, index)
     }
 
     pub fn get_opcode(&self) -> Option<Opcode> {
         match self {
             Instruction::Opcode(opcode) => Some(opcode.clone()),
-            Instruction::Label(_, _) => None,
+            Instruction::Label(_, _, _) => None,
         }
     }
 }
@@ -30,7 +69,7 @@ impl fmt::Display for Instruction {
     fn fmt(&self, f: &mut Formatter<'_>) -> Result {
         match self {
             Instruction::Opcode(opcode) => write!(f, "{}", opcode),
-            Instruction::Label(name, _) => write!(f, "{}", name),
+            Instruction::Label(name, _, _) => write!(f, "{}:", name),
         }
     }
 }
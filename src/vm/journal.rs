@@ -0,0 +1,298 @@
+use std::collections::VecDeque;
+use std::fmt::{self};
+use std::mem;
+use std::sync::{
+    atomic::{AtomicUsize, Ordering},
+    Arc,
+};
+
+use super::flag::Flag;
+
+/// Memory budget of journal in bytes, enough for several thousands of simple instructions
+pub const DEFAULT_JOURNAL_BUDGET: usize = 256 * 1024;
+
+/// Side effect of instruction, with value needed to undo it
+#[derive(Debug, PartialEq, Eq, Clone, Copy, serde::Deserialize, serde::Serialize)]
+pub enum Change {
+    /// Write to general purpose register, with its previous value
+    Register(usize, i32),
+    /// Write to port, with its previous value
+    Port {
+        index: usize,
+        old_value: i32,
+        /// Port is connected to components that are not journaled together with vm,
+        /// which may have already read the new value
+        connected: bool,
+    },
+    /// Write to memory cell at address, with its previous value
+    Memory(usize, i32),
+    /// Value pushed on top of stack
+    StackPush,
+    /// Value popped from top of stack
+    StackPop(i32),
+    /// Value dropped from bottom of full stack
+    StackDropOldest(i32),
+    /// Top of full stack overwritten, with its previous value
+    StackOverwriteTop(i32),
+    /// Return address pushed by CALL on vm without stack
+    ReturnPush,
+    /// Return address popped by RET on vm without stack
+    ReturnPop(usize),
+}
+
+impl Change {
+    /// Checks if change is write to port shared with other components
+    pub fn is_connected_port_write(&self) -> bool {
+        matches!(
+            self,
+            Change::Port {
+                connected: true,
+                ..
+            }
+        )
+    }
+}
+
+/// Side effects of single executed instruction, with registers from before it
+#[derive(Debug, PartialEq, Eq, Clone, serde::Deserialize, serde::Serialize)]
+pub struct JournalEntry {
+    /// Order of entry among entries of all journals sharing clock
+    sequence: usize,
+    /// Index of executed instruction
+    pc: usize,
+    acc: i32,
+    flag: Flag,
+    changes: Vec<Change>,
+}
+
+impl JournalEntry {
+    pub fn get_sequence(&self) -> usize {
+        self.sequence
+    }
+
+    pub fn get_pc(&self) -> usize {
+        self.pc
+    }
+
+    pub fn get_acc(&self) -> i32 {
+        self.acc
+    }
+
+    pub fn get_flag(&self) -> Flag {
+        self.flag
+    }
+
+    pub fn get_changes(&self) -> Vec<Change> {
+        self.changes.clone()
+    }
+
+    /// Checks if instruction wrote to port shared with other components
+    pub fn has_connected_port_write(&self) -> bool {
+        self.changes.iter().any(Change::is_connected_port_write)
+    }
+
+    /// Estimated number of bytes used by entry
+    fn get_size(&self) -> usize {
+        mem::size_of::<JournalEntry>() + self.changes.len() * mem::size_of::<Change>()
+    }
+}
+
+/// Record of side effects of executed instructions, used to step vm back
+///
+/// The oldest entries are dropped when journal exceeds its memory budget.
+/// Writes to connected ports can't be undone by single vm, as other components may have already
+/// acted on them, unless all components connected to port are vms journaled together
+///
+/// ### Example
+///
+/// ```rs
+/// let mut vm = VirtualMachine::new_with_program(program).with_journal(DEFAULT_JOURNAL_BUDGET);
+/// vm.step();
+/// vm.step_back()?;
+/// ```
+#[derive(Debug, Clone, serde::Deserialize, serde::Serialize)]
+pub struct Journal {
+    entries: VecDeque<JournalEntry>,
+    /// Entry of instruction being executed
+    pending: Option<JournalEntry>,
+    /// Maximal number of bytes used by entries
+    budget: usize,
+    /// Number of bytes used by entries
+    used: usize,
+    /// Source of entry sequence numbers, shared by journals recorded together
+    clock: Arc<AtomicUsize>,
+    /// Connected ports whose every peer is vm journaled together with this one,
+    /// writes to them can be undone
+    reversible_ports: Vec<usize>,
+}
+
+impl Journal {
+    /// Creates empty journal
+    ///
+    /// ### Arguments
+    ///
+    /// * 'budget' - maximal number of bytes used by entries
+    pub fn new(budget: usize) -> Journal {
+        Journal {
+            entries: VecDeque::new(),
+            pending: None,
+            budget,
+            used: 0,
+            clock: Arc::new(AtomicUsize::new(0)),
+            reversible_ports: Vec::new(),
+        }
+    }
+
+    /// Makes journal part of group recorded together, entries of all journals sharing
+    /// clock are ordered
+    pub fn with_clock(mut self, clock: Arc<AtomicUsize>) -> Journal {
+        self.clock = clock;
+        self
+    }
+
+    /// Sets connected ports whose every peer is journaled together with vm,
+    /// writes to them are not marked as connected port writes
+    pub fn with_reversible_ports(mut self, ports: Vec<usize>) -> Journal {
+        self.reversible_ports = ports;
+        self
+    }
+
+    pub fn get_budget(&self) -> usize {
+        self.budget
+    }
+
+    /// Gets estimated number of bytes used by entries
+    pub fn get_used(&self) -> usize {
+        self.used
+    }
+
+    pub fn get_entries(&self) -> Vec<JournalEntry> {
+        self.entries.iter().cloned().collect()
+    }
+
+    /// Gets entry of the most recently executed instruction
+    pub fn get_last(&self) -> Option<&JournalEntry> {
+        self.entries.back()
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Checks if writes to port can be undone, though it is connected
+    pub fn is_port_reversible(&self, index: usize) -> bool {
+        self.reversible_ports.contains(&index)
+    }
+
+    /// Makes writes to port irreversible, e.g. after it is connected to other component
+    pub fn mark_port_irreversible(&mut self, index: usize) {
+        self.reversible_ports.retain(|&port| port != index);
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+        self.pending = None;
+        self.used = 0;
+    }
+
+    /// Starts entry of instruction, saving registers from before it
+    pub fn begin(&mut self, pc: usize, acc: i32, flag: Flag) {
+        self.pending = Some(JournalEntry {
+            sequence: self.clock.fetch_add(1, Ordering::SeqCst),
+            pc,
+            acc,
+            flag,
+            changes: Vec::new(),
+        });
+    }
+
+    /// Adds change to entry of instruction being executed
+    pub fn record(&mut self, change: Change) {
+        if let Some(entry) = &mut self.pending {
+            entry.changes.push(change);
+        }
+    }
+
+    /// Saves entry of executed instruction, dropping the oldest entries above budget
+    pub fn commit(&mut self) {
+        let Some(entry) = self.pending.take() else {
+            return;
+        };
+        self.used += entry.get_size();
+        self.entries.push_back(entry);
+        while self.used > self.budget {
+            match self.entries.pop_front() {
+                Some(oldest) => self.used -= oldest.get_size(),
+                None => break,
+            }
+        }
+    }
+
+    /// Forgets entry of instruction that didn't execute
+    pub fn discard(&mut self) {
+        self.pending = None;
+    }
+
+    /// Removes entry of the most recently executed instruction
+    pub fn pop(&mut self) -> Option<JournalEntry> {
+        let entry = self.entries.pop_back()?;
+        self.used -= entry.get_size();
+        Some(entry)
+    }
+}
+
+/// Reason vm can't be stepped back
+#[derive(Debug, PartialEq, Eq, Clone, Copy, serde::Deserialize, serde::Serialize)]
+pub enum ReverseError {
+    /// Vm doesn't record journal
+    JournalDisabled,
+    /// All recorded instructions were already undone or dropped
+    JournalEmpty,
+    /// Instruction at pc wrote to connected port and vm is not journaled together with others
+    IrreversiblePortWrite(usize),
+    /// No instruction at pc was recorded in journal
+    NotInJournal(usize),
+}
+
+impl fmt::Display for ReverseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let message = match self {
+            ReverseError::JournalDisabled => t!("reverse.journal_disabled"),
+            ReverseError::JournalEmpty => t!("reverse.journal_empty"),
+            ReverseError::IrreversiblePortWrite(pc) => {
+                t!("reverse.irreversible_port_write", pc = pc)
+            }
+            ReverseError::NotInJournal(pc) => t!("reverse.not_in_journal", pc = pc),
+        };
+        write!(f, "{}", message)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_journal_budget() {
+        let entry_size = mem::size_of::<JournalEntry>() + mem::size_of::<Change>();
+        let mut journal = Journal::new(entry_size * 2);
+        for pc in 0..3 {
+            journal.begin(pc, 0, Flag::new());
+            journal.record(Change::Register(0, pc as i32));
+            journal.commit();
+        }
+        assert_eq!(journal.len(), 2);
+        assert_eq!(journal.get_used(), entry_size * 2);
+        assert_eq!(journal.get_entries()[0].get_pc(), 1);
+
+        journal.begin(3, 0, Flag::new());
+        journal.discard();
+        journal.commit();
+        assert_eq!(journal.pop().map(|entry| entry.get_sequence()), Some(2));
+        assert_eq!(journal.get_used(), entry_size);
+    }
+}
@@ -2,4 +2,10 @@ pub mod flag;
 pub mod opcodes;
 pub mod virtual_machine;
 pub mod instruction;
-pub mod operand;
\ No newline at end of file
+pub mod operand;
+pub mod profile;
+pub mod fault;
+pub mod breakpoint;
+pub mod journal;
+pub mod trace;
+pub mod cost;
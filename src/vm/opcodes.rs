@@ -9,6 +9,24 @@ pub enum JMPCondition {
     NEQ,
     LST,
     GRT,
+    /// Zero flag set
+    ZERO,
+    /// Zero flag not set
+    NZERO,
+    /// Carry flag set
+    CARRY,
+    /// Overflow flag set
+    OVERFLOW,
+    /// Negative flag set
+    SIGN,
+    /// Greater as unsigned numbers
+    ABOVE,
+    /// Greater or equal as unsigned numbers
+    ABOVEEQ,
+    /// Lesser as unsigned numbers
+    BELOW,
+    /// Lesser or equal as unsigned numbers
+    BELOWEQ,
     NONE,
 }
 
@@ -41,6 +59,16 @@ pub enum Opcode {
     INC,
     /// Decrements acc by 1
     DEC,
+    /// Negates acc
+    NEG,
+    /// Replaces acc with its absolute value, i32::MIN stays unchanged and sets overflow
+    ABS,
+    /// Sets acc to smaller of acc and operand
+    MIN(Operand),
+    /// Sets acc to greater of acc and operand
+    MAX(Operand),
+    /// Limits acc to range from first to second operand
+    CLAMP(Operand, Operand),
 
     // ------------  Bit operations ------------
     /// ANDx operand with  acc
@@ -53,10 +81,23 @@ pub enum Opcode {
     NOT,
     /// Shifts bits to left
     SHL(Operand),
-    /// Shifts bits to right
+    /// Alias of SAR, shifts bits to right keeping sign of acc
+    ///
+    /// SHR has always been arithmetic shift, use LSR for logical one
     SHR(Operand),
+    /// Shifts bits to right, keeping sign of acc
+    SAR(Operand),
+    /// Shifts bits to right, filling upper bits with 0
+    LSR(Operand),
+    /// Rotates bits of word to left
+    ROL(Operand),
+    /// Rotates bits of word to right
+    ROR(Operand),
 
     // ------------ Jumping logic ------------
+    // Jumps keep name of targeted label and are never rewritten to program index,
+    // vm looks name up among labels of loaded program, where assembler has checked
+    // that it is declared exactly once
     /// Compare
     CMP(Operand, Operand),
     /// Jum to label
@@ -69,6 +110,30 @@ pub enum Opcode {
     JL(String),
     /// Jump to label if flag set to greater
     JG(String),
+    /// Jump to label if result was zero
+    JZ(String),
+    /// Jump to label if result was not zero
+    JNZ(String),
+    /// Jump to label if carry flag is set
+    JC(String),
+    /// Jump to label if overflow flag is set
+    JO(String),
+    /// Jump to label if result was negative
+    JS(String),
+    /// Jump to label if flag set to greater, comparing unsigned numbers
+    JA(String),
+    /// Jump to label if flag set to greater or equal, comparing unsigned numbers
+    JAE(String),
+    /// Jump to label if flag set to lesser, comparing unsigned numbers
+    JB(String),
+    /// Jump to label if flag set to lesser or equal, comparing unsigned numbers
+    JBE(String),
+
+    // ------------ Subroutines ------------
+    /// Saves return address and jumps to label
+    CALL(String),
+    /// Jumps to address saved by the most recent CALL
+    RET,
 
     // ------------ Stack ------------
     /// Copies operand to stack
@@ -77,33 +142,238 @@ pub enum Opcode {
     POP(Operand),
 }
 
+impl Opcode {
+    /// Mnemonics of all opcodes, in order of their declaration
+    pub const MNEMONICS: [&'static str; 44] = [
+        "NOP", "HLT", "MOV", "ADD", "SUB", "MUL", "DIV", "MOD", "INC", "DEC", "NEG", "ABS", "MIN",
+        "MAX", "CLAMP", "AND", "OR", "XOR", "NOT", "SHL", "SHR", "SAR", "LSR", "ROL", "ROR", "CMP",
+        "JMP", "JE", "JNE", "JL", "JG", "JZ", "JNZ", "JC", "JO", "JS", "JA", "JAE", "JB", "JBE",
+        "CALL", "RET", "PSH", "POP",
+    ];
+
+    /// Gets name of label targeted by jump, None for other opcodes
+    pub fn get_label(&self) -> Option<String> {
+        match self {
+            Opcode::JMP(label)
+            | Opcode::JE(label)
+            | Opcode::JNE(label)
+            | Opcode::JL(label)
+            | Opcode::JG(label)
+            | Opcode::JZ(label)
+            | Opcode::JNZ(label)
+            | Opcode::JC(label)
+            | Opcode::JO(label)
+            | Opcode::JS(label)
+            | Opcode::JA(label)
+            | Opcode::JAE(label)
+            | Opcode::JB(label)
+            | Opcode::JBE(label)
+            | Opcode::CALL(label) => Some(label.clone()),
+            _ => None,
+        }
+    }
+
+    /// Gets mutable name of label targeted by jump, None for other opcodes
+    pub fn get_label_mut(&mut self) -> Option<&mut String> {
+        match self {
+            Opcode::JMP(label)
+            | Opcode::JE(label)
+            | Opcode::JNE(label)
+            | Opcode::JL(label)
+            | Opcode::JG(label)
+            | Opcode::JZ(label)
+            | Opcode::JNZ(label)
+            | Opcode::JC(label)
+            | Opcode::JO(label)
+            | Opcode::JS(label)
+            | Opcode::JA(label)
+            | Opcode::JAE(label)
+            | Opcode::JB(label)
+            | Opcode::JBE(label)
+            | Opcode::CALL(label) => Some(label),
+            _ => None,
+        }
+    }
+
+    /// Gets mnemonic used for opcode in assembly code
+    pub fn get_mnemonic(&self) -> &'static str {
+        match self {
+            Opcode::NOP => "NOP",
+            Opcode::HLT => "HLT",
+            Opcode::MOV(_, _) => "MOV",
+            Opcode::ADD(_) => "ADD",
+            Opcode::SUB(_) => "SUB",
+            Opcode::MUL(_) => "MUL",
+            Opcode::DIV(_) => "DIV",
+            Opcode::MOD(_) => "MOD",
+            Opcode::INC => "INC",
+            Opcode::DEC => "DEC",
+            Opcode::NEG => "NEG",
+            Opcode::ABS => "ABS",
+            Opcode::MIN(_) => "MIN",
+            Opcode::MAX(_) => "MAX",
+            Opcode::CLAMP(_, _) => "CLAMP",
+            Opcode::AND(_) => "AND",
+            Opcode::OR(_) => "OR",
+            Opcode::XOR(_) => "XOR",
+            Opcode::NOT => "NOT",
+            Opcode::SHL(_) => "SHL",
+            Opcode::SHR(_) => "SHR",
+            Opcode::SAR(_) => "SAR",
+            Opcode::LSR(_) => "LSR",
+            Opcode::ROL(_) => "ROL",
+            Opcode::ROR(_) => "ROR",
+            Opcode::CMP(_, _) => "CMP",
+            Opcode::JMP(_) => "JMP",
+            Opcode::JE(_) => "JE",
+            Opcode::JNE(_) => "JNE",
+            Opcode::JL(_) => "JL",
+            Opcode::JG(_) => "JG",
+            Opcode::JZ(_) => "JZ",
+            Opcode::JNZ(_) => "JNZ",
+            Opcode::JC(_) => "JC",
+            Opcode::JO(_) => "JO",
+            Opcode::JS(_) => "JS",
+            Opcode::JA(_) => "JA",
+            Opcode::JAE(_) => "JAE",
+            Opcode::JB(_) => "JB",
+            Opcode::JBE(_) => "JBE",
+            Opcode::CALL(_) => "CALL",
+            Opcode::RET => "RET",
+            Opcode::PSH(_) => "PSH",
+            Opcode::POP(_) => "POP",
+        }
+    }
+
+    /// Gets operands of opcode in order they appear in assembly code
+    pub fn get_operands(&self) -> Vec<Operand> {
+        match self {
+            Opcode::MOV(op1, op2) | Opcode::CMP(op1, op2) | Opcode::CLAMP(op1, op2) => {
+                vec![op1.clone(), op2.clone()]
+            }
+            Opcode::ADD(op)
+            | Opcode::SUB(op)
+            | Opcode::MUL(op)
+            | Opcode::DIV(op)
+            | Opcode::MOD(op)
+            | Opcode::AND(op)
+            | Opcode::OR(op)
+            | Opcode::XOR(op)
+            | Opcode::SHL(op)
+            | Opcode::SHR(op)
+            | Opcode::SAR(op)
+            | Opcode::LSR(op)
+            | Opcode::ROL(op)
+            | Opcode::ROR(op)
+            | Opcode::MIN(op)
+            | Opcode::MAX(op)
+            | Opcode::PSH(op)
+            | Opcode::POP(op) => vec![op.clone()],
+            _ => Vec::new(),
+        }
+    }
+}
+
 impl Display for Opcode {
     fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
-        match self {
-            Opcode::NOP => write!(f, "NOP"),
-            Opcode::HLT => write!(f, "HLT"),
-            Opcode::MOV(op1, op2) => write!(f, "MOV {} {}", op1, op2),
-            Opcode::ADD(op) => write!(f, "ADD {}", op),
-            Opcode::SUB(op) => write!(f, "SUB {}", op),
-            Opcode::MUL(op) => write!(f, "MUL {}", op),
-            Opcode::DIV(op) => write!(f, "DIV {}", op),
-            Opcode::MOD(op) => write!(f, "MOD {}", op),
-            Opcode::INC => write!(f, "INC"),
-            Opcode::DEC => write!(f, "DEC"),
-            Opcode::AND(op) => write!(f, "AND {}", op),
-            Opcode::OR(op) => write!(f, "OR {}", op),
-            Opcode::XOR(op) => write!(f, "XOR {}", op),
-            Opcode::NOT => write!(f, "NOT"),
-            Opcode::SHL(op) => write!(f, "SHL {}", op),
-            Opcode::SHR(op) => write!(f, "SHR {}", op),
-            Opcode::CMP(op1, op2) => write!(f, "CMP {} {}", op1, op2),
-            Opcode::JMP(label) => write!(f, "JMP {}", label),
-            Opcode::JE(label) => write!(f, "JE {}", label),
-            Opcode::JNE(label) => write!(f, "JNE {}", label),
-            Opcode::JL(label) => write!(f, "JL {}", label),
-            Opcode::JG(label) => write!(f, "JG {}", label),
-            Opcode::PSH(op) => write!(f, "PSH {}", op),
-            Opcode::POP(op) => write!(f, "POP {}", op),
+        write!(f, "{}", self.get_mnemonic())?;
+        for operand in self.get_operands() {
+            write!(f, " {}", operand)?;
+        }
+        if let Some(label) = self.get_label() {
+            write!(f, " {}", label)?;
         }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_opcode_display() {
+        let opcodes = [
+            (Opcode::NOP, "NOP"),
+            (
+                Opcode::MOV(Operand::IntegerValue(-5), Operand::GeneralRegister(2)),
+                "MOV -5 r2",
+            ),
+            (Opcode::ADD(Operand::PortRegister(4)), "ADD p4"),
+            (Opcode::CMP(Operand::ACC, Operand::PC), "CMP acc pc"),
+            (Opcode::JNE("loop".to_string()), "JNE loop"),
+            (Opcode::CALL("print".to_string()), "CALL print"),
+            (Opcode::RET, "RET"),
+            (
+                Opcode::CLAMP(Operand::IntegerValue(0), Operand::GeneralRegister(1)),
+                "CLAMP 0 r1",
+            ),
+            (Opcode::ROR(Operand::IntegerValue(3)), "ROR 3"),
+            (Opcode::LSR(Operand::GeneralRegister(0)), "LSR r0"),
+            (Opcode::NEG, "NEG"),
+            (
+                Opcode::MOV(Operand::Memory(1, 4), Operand::Memory(0, -2)),
+                "MOV [r1+4] [r0-2]",
+            ),
+        ];
+
+        for (opcode, text) in opcodes {
+            assert_eq!(opcode.to_string(), text);
+        }
+    }
+
+    #[test]
+    fn test_opcode_mnemonics() {
+        let op = Operand::ACC;
+        let label = "loop".to_string();
+        let opcodes = [
+            Opcode::NOP,
+            Opcode::HLT,
+            Opcode::MOV(op.clone(), op.clone()),
+            Opcode::ADD(op.clone()),
+            Opcode::SUB(op.clone()),
+            Opcode::MUL(op.clone()),
+            Opcode::DIV(op.clone()),
+            Opcode::MOD(op.clone()),
+            Opcode::INC,
+            Opcode::DEC,
+            Opcode::NEG,
+            Opcode::ABS,
+            Opcode::MIN(op.clone()),
+            Opcode::MAX(op.clone()),
+            Opcode::CLAMP(op.clone(), op.clone()),
+            Opcode::AND(op.clone()),
+            Opcode::OR(op.clone()),
+            Opcode::XOR(op.clone()),
+            Opcode::NOT,
+            Opcode::SHL(op.clone()),
+            Opcode::SHR(op.clone()),
+            Opcode::SAR(op.clone()),
+            Opcode::LSR(op.clone()),
+            Opcode::ROL(op.clone()),
+            Opcode::ROR(op.clone()),
+            Opcode::CMP(op.clone(), op.clone()),
+            Opcode::JMP(label.clone()),
+            Opcode::JE(label.clone()),
+            Opcode::JNE(label.clone()),
+            Opcode::JL(label.clone()),
+            Opcode::JG(label.clone()),
+            Opcode::JZ(label.clone()),
+            Opcode::JNZ(label.clone()),
+            Opcode::JC(label.clone()),
+            Opcode::JO(label.clone()),
+            Opcode::JS(label.clone()),
+            Opcode::JA(label.clone()),
+            Opcode::JAE(label.clone()),
+            Opcode::JB(label.clone()),
+            Opcode::JBE(label.clone()),
+            Opcode::CALL(label.clone()),
+            Opcode::RET,
+            Opcode::PSH(op.clone()),
+            Opcode::POP(op.clone()),
+        ];
+
+        let mnemonics: Vec<&str> = opcodes.iter().map(Opcode::get_mnemonic).collect();
+        assert_eq!(mnemonics, Opcode::MNEMONICS);
     }
 }
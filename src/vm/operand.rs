@@ -14,6 +14,8 @@ pub enum Operand {
     ACC,
     /// Program counter register
     PC,
+    /// Data memory cell at address stored in general register plus offset, [r1] or [r1+4]
+    Memory(usize, i32),
 }
 
 impl Operand {
@@ -36,6 +38,11 @@ impl fmt::Display for Operand {
             Operand::PortRegister(port) => write!(f, "p{}", port),
             Operand::ACC => write!(f, "acc"),
             Operand::PC => write!(f, "pc"),
+            Operand::Memory(register, 0) => write!(f, "[r{}]", register),
+            Operand::Memory(register, offset) if *offset < 0 => {
+                write!(f, "[r{}{}]", register, offset)
+            }
+            Operand::Memory(register, offset) => write!(f, "[r{}+{}]", register, offset),
         }
     }
 }
@@ -0,0 +1,221 @@
+use std::fmt::{self};
+
+/// Depth of stack used when stack is enabled without giving its depth
+pub const DEFAULT_STACK_DEPTH: usize = 32;
+
+/// Parameters of machine, shared by assembler and vm so both agree on available registers
+///
+/// ### Example
+///
+/// ```rs
+/// let profile = MachineProfile::new().with_registers(2).with_ports(8).with_stack(16);
+/// let program = Assembler::new_with_profile(profile).parse(program_text)?;
+/// let mut vm = VirtualMachine::new_with_profile(profile);
+/// vm.load_program(program);
+/// ```
+#[derive(Debug, serde::Deserialize, serde::Serialize, Clone, Copy, PartialEq, Eq)]
+#[serde(try_from = "RawMachineProfile")]
+pub struct MachineProfile {
+    /// Number of general purpose registers r0..
+    registers: usize,
+    /// Number of ports p0..
+    ports: usize,
+    /// Maximal number of values on stack, None if machine has no stack
+    stack_depth: Option<usize>,
+    /// Number of bits in word, values stored in registers are wrapped to it
+    word_size: u32,
+    /// Number of words in data memory used by memory operands
+    memory_size: usize,
+}
+
+/// Profile as serialized, word size is checked before it becomes MachineProfile
+#[derive(serde::Deserialize)]
+struct RawMachineProfile {
+    registers: usize,
+    ports: usize,
+    stack_depth: Option<usize>,
+    word_size: u32,
+    memory_size: usize,
+}
+
+impl TryFrom<RawMachineProfile> for MachineProfile {
+    type Error = ProfileError;
+
+    fn try_from(raw: RawMachineProfile) -> Result<Self, Self::Error> {
+        if !(1..=32).contains(&raw.word_size) {
+            return Err(ProfileError::InvalidWordSize(raw.word_size));
+        }
+        Ok(MachineProfile {
+            registers: raw.registers,
+            ports: raw.ports,
+            stack_depth: raw.stack_depth,
+            word_size: raw.word_size,
+            memory_size: raw.memory_size,
+        })
+    }
+}
+
+/// Error in machine profile
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum ProfileError {
+    /// Word size is not in range 1..=32 bits
+    InvalidWordSize(u32),
+}
+
+impl fmt::Display for ProfileError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let message = match self {
+            ProfileError::InvalidWordSize(bits) => {
+                t!("profile.invalid_word_size", bits = bits)
+            }
+        };
+        write!(f, "{}", message)
+    }
+}
+
+impl Default for MachineProfile {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl MachineProfile {
+    /// Creates profile of default machine: 4 registers, 6 ports, no stack, 32 bit words
+    /// and no data memory
+    pub fn new() -> MachineProfile {
+        MachineProfile {
+            registers: 4,
+            ports: 6,
+            stack_depth: None,
+            word_size: 32,
+            memory_size: 0,
+        }
+    }
+
+    pub fn with_registers(mut self, registers: usize) -> MachineProfile {
+        self.registers = registers;
+        self
+    }
+
+    pub fn with_ports(mut self, ports: usize) -> MachineProfile {
+        self.ports = ports;
+        self
+    }
+
+    pub fn with_stack(mut self, depth: usize) -> MachineProfile {
+        self.stack_depth = Some(depth);
+        self
+    }
+
+    pub fn without_stack(mut self) -> MachineProfile {
+        self.stack_depth = None;
+        self
+    }
+
+    /// Sets number of bits in word
+    ///
+    /// ### Panics
+    /// * if 'bits' is not in range 1..=32
+    pub fn with_word_size(mut self, bits: u32) -> MachineProfile {
+        assert!((1..=32).contains(&bits), "word size must be 1..=32 bits");
+        self.word_size = bits;
+        self
+    }
+
+    pub fn with_memory(mut self, size: usize) -> MachineProfile {
+        self.memory_size = size;
+        self
+    }
+
+    pub fn get_registers(&self) -> usize {
+        self.registers
+    }
+
+    pub fn get_ports(&self) -> usize {
+        self.ports
+    }
+
+    pub fn get_stack_depth(&self) -> Option<usize> {
+        self.stack_depth
+    }
+
+    pub fn has_stack(&self) -> bool {
+        self.stack_depth.is_some()
+    }
+
+    pub fn get_word_size(&self) -> u32 {
+        self.word_size
+    }
+
+    pub fn get_memory_size(&self) -> usize {
+        self.memory_size
+    }
+
+    /// Wraps value to word size, keeping it signed
+    ///
+    /// ### Example
+    ///
+    /// ```rs
+    /// let profile = MachineProfile::new().with_word_size(8);
+    /// assert_eq!(profile.wrap(200), -56);
+    /// ```
+    pub fn wrap(&self, value: i32) -> i32 {
+        let unused_bits = 32 - self.word_size;
+        value.wrapping_shl(unused_bits).wrapping_shr(unused_bits)
+    }
+
+    /// Interprets value as unsigned number made of word size bits
+    ///
+    /// ### Example
+    ///
+    /// ```rs
+    /// let profile = MachineProfile::new().with_word_size(8);
+    /// assert_eq!(profile.to_unsigned(-1), 255);
+    /// ```
+    pub fn to_unsigned(&self, value: i32) -> u32 {
+        (value as u32) & (u32::MAX >> (32 - self.word_size))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_wrapping_to_word_size() {
+        let profile = MachineProfile::new();
+        assert_eq!(profile.wrap(i32::MIN), i32::MIN);
+        assert_eq!(profile.wrap(-7), -7);
+
+        let profile = profile.with_word_size(8);
+        assert_eq!(profile.wrap(127), 127);
+        assert_eq!(profile.wrap(128), -128);
+        assert_eq!(profile.wrap(200), -56);
+        assert_eq!(profile.wrap(-129), 127);
+
+        assert_eq!(profile.to_unsigned(-1), 255);
+        assert_eq!(profile.to_unsigned(300), 44);
+
+        let profile = profile.with_word_size(1);
+        assert_eq!(profile.wrap(1), -1);
+        assert_eq!(profile.wrap(2), 0);
+    }
+
+    #[test]
+    fn test_deserialize_checks_word_size() {
+        let profile = MachineProfile::new().with_word_size(8).with_stack(4);
+        let json = serde_json::to_string(&profile).unwrap();
+        assert_eq!(
+            serde_json::from_str::<MachineProfile>(&json).unwrap(),
+            profile
+        );
+
+        for bits in [0, 33] {
+            let json = json.replace("\"word_size\":8", &format!("\"word_size\":{}", bits));
+            let error = serde_json::from_str::<MachineProfile>(&json).unwrap_err();
+            assert!(error
+                .to_string()
+                .contains(&ProfileError::InvalidWordSize(bits).to_string()));
+        }
+    }
+}
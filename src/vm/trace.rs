@@ -0,0 +1,541 @@
+use std::fmt::{self};
+
+use super::flag::Flag;
+
+/// First bytes of binary trace
+const BINARY_MAGIC: &[u8; 4] = b"SVAT";
+/// Version of binary trace format
+const BINARY_VERSION: u8 = 1;
+
+/// Value changed by instruction, with its new value
+#[derive(Debug, PartialEq, Eq, Clone, serde::Deserialize, serde::Serialize)]
+pub enum Delta {
+    Acc(i32),
+    Register(usize, i32),
+    Port(usize, i32),
+    Flag(Flag),
+    /// Whole stack, values from bottom to top
+    Stack(Vec<i32>),
+    /// Data memory cell at address
+    Memory(usize, i32),
+}
+
+impl Delta {
+    /// Tag of delta in binary trace
+    fn get_tag(&self) -> u8 {
+        match self {
+            Delta::Acc(_) => 0,
+            Delta::Register(..) => 1,
+            Delta::Port(..) => 2,
+            Delta::Flag(_) => 3,
+            Delta::Stack(_) => 4,
+            Delta::Memory(..) => 5,
+        }
+    }
+}
+
+impl fmt::Display for Delta {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Delta::Acc(value) => write!(f, "acc={}", value),
+            Delta::Register(index, value) => write!(f, "r{}={}", index, value),
+            Delta::Port(index, value) => write!(f, "p{}={}", index, value),
+            Delta::Flag(flag) => write!(f, "flag={}", flag),
+            Delta::Stack(stack) => write!(f, "stack={:?}", stack),
+            Delta::Memory(address, value) => write!(f, "mem[{}]={}", address, value),
+        }
+    }
+}
+
+/// Registers, flags, ports, stack and data memory of vm, compared before and after instruction
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Snapshot {
+    pub acc: i32,
+    pub flag: Flag,
+    pub registers: Vec<i32>,
+    pub ports: Vec<i32>,
+    pub stack: Vec<i32>,
+    pub memory: Vec<i32>,
+}
+
+impl Snapshot {
+    /// Lists values that differ in later snapshot
+    pub fn deltas(&self, after: &Snapshot) -> Vec<Delta> {
+        let mut deltas = Vec::new();
+        if self.acc != after.acc {
+            deltas.push(Delta::Acc(after.acc));
+        }
+        let changed = |before: &[i32], after: &[i32]| -> Vec<(usize, i32)> {
+            after
+                .iter()
+                .enumerate()
+                .filter(|&(index, value)| before.get(index) != Some(value))
+                .map(|(index, &value)| (index, value))
+                .collect()
+        };
+        deltas.extend(
+            changed(&self.registers, &after.registers)
+                .into_iter()
+                .map(|(index, value)| Delta::Register(index, value)),
+        );
+        deltas.extend(
+            changed(&self.ports, &after.ports)
+                .into_iter()
+                .map(|(index, value)| Delta::Port(index, value)),
+        );
+        if self.flag != after.flag {
+            deltas.push(Delta::Flag(after.flag));
+        }
+        if self.stack != after.stack {
+            deltas.push(Delta::Stack(after.stack.clone()));
+        }
+        deltas.extend(
+            changed(&self.memory, &after.memory)
+                .into_iter()
+                .map(|(address, value)| Delta::Memory(address, value)),
+        );
+        deltas
+    }
+}
+
+/// Record of single executed instruction
+#[derive(Debug, PartialEq, Eq, Clone, serde::Deserialize, serde::Serialize)]
+pub struct TraceStep {
+    /// Number of instructions executed before this one since trace started
+    step: usize,
+    pc: usize,
+    /// Instruction as written by disassembler
+    instruction: String,
+    deltas: Vec<Delta>,
+}
+
+impl TraceStep {
+    pub fn new(step: usize, pc: usize, instruction: String, deltas: Vec<Delta>) -> TraceStep {
+        TraceStep {
+            step,
+            pc,
+            instruction,
+            deltas,
+        }
+    }
+
+    pub fn get_step(&self) -> usize {
+        self.step
+    }
+
+    pub fn get_pc(&self) -> usize {
+        self.pc
+    }
+
+    pub fn get_instruction(&self) -> String {
+        self.instruction.clone()
+    }
+
+    pub fn get_deltas(&self) -> Vec<Delta> {
+        self.deltas.clone()
+    }
+
+    /// Checks if steps executed the same instruction with the same effects
+    fn is_equivalent(&self, other: &TraceStep) -> bool {
+        self.pc == other.pc && self.instruction == other.instruction && self.deltas == other.deltas
+    }
+}
+
+impl fmt::Display for TraceStep {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "#{} {}: {}", self.step, self.pc, self.instruction)?;
+        for delta in &self.deltas {
+            write!(f, " {}", delta)?;
+        }
+        Ok(())
+    }
+}
+
+/// First step at which two traces differ
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Divergence {
+    /// Index of step in both traces
+    step: usize,
+    /// Step of first trace, None if it ended earlier
+    expected: Option<TraceStep>,
+    /// Step of second trace, None if it ended earlier
+    actual: Option<TraceStep>,
+}
+
+impl Divergence {
+    pub fn get_step(&self) -> usize {
+        self.step
+    }
+
+    pub fn get_expected(&self) -> Option<TraceStep> {
+        self.expected.clone()
+    }
+
+    pub fn get_actual(&self) -> Option<TraceStep> {
+        self.actual.clone()
+    }
+}
+
+impl fmt::Display for Divergence {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let describe = |step: &Option<TraceStep>| match step {
+            Some(step) => step.to_string(),
+            None => t!("trace.ended").to_string(),
+        };
+        write!(
+            f,
+            "{}\n- {}\n+ {}",
+            t!("trace.divergence", step = self.step),
+            describe(&self.expected),
+            describe(&self.actual)
+        )
+    }
+}
+
+/// Error in imported trace
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum TraceError {
+    /// Line of JSON Lines trace, counted from 1, is not a valid step
+    InvalidJson(usize),
+    /// Binary trace is malformed at byte offset
+    InvalidBinary(usize),
+}
+
+impl fmt::Display for TraceError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let message = match self {
+            TraceError::InvalidJson(line) => t!("trace.invalid_json", line = line),
+            TraceError::InvalidBinary(offset) => t!("trace.invalid_binary", offset = offset),
+        };
+        write!(f, "{}", message)
+    }
+}
+
+/// Executed instructions with values they changed, used to compare runs of programs
+///
+/// ### Example
+///
+/// ```rs
+/// let mut vm = VirtualMachine::new_with_program(program).with_trace();
+/// vm.run();
+/// let trace = vm.take_trace().unwrap();
+/// if let Some(divergence) = reference.diff(&trace) {
+///     println!("{}", divergence);
+/// }
+/// ```
+#[derive(Debug, PartialEq, Eq, Clone, Default, serde::Deserialize, serde::Serialize)]
+pub struct Trace {
+    steps: Vec<TraceStep>,
+}
+
+impl Trace {
+    pub fn new() -> Trace {
+        Trace { steps: Vec::new() }
+    }
+
+    /// Adds executed instruction, numbering it after previous ones
+    pub fn record(&mut self, pc: usize, instruction: String, deltas: Vec<Delta>) {
+        let step = self.steps.len();
+        self.steps
+            .push(TraceStep::new(step, pc, instruction, deltas));
+    }
+
+    pub fn get_steps(&self) -> Vec<TraceStep> {
+        self.steps.clone()
+    }
+
+    pub fn len(&self) -> usize {
+        self.steps.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.steps.is_empty()
+    }
+
+    /// Finds first step at which other trace differs from this one
+    ///
+    /// Steps are the same if they executed the same instruction at the same pc
+    /// and changed the same values
+    ///
+    /// ### Returns
+    /// * None if traces are the same
+    pub fn diff(&self, other: &Trace) -> Option<Divergence> {
+        let length = self.steps.len().max(other.steps.len());
+        (0..length)
+            .find(
+                |&index| match (self.steps.get(index), other.steps.get(index)) {
+                    (Some(expected), Some(actual)) => !expected.is_equivalent(actual),
+                    _ => true,
+                },
+            )
+            .map(|index| Divergence {
+                step: index,
+                expected: self.steps.get(index).cloned(),
+                actual: other.steps.get(index).cloned(),
+            })
+    }
+
+    /// Exports trace to JSON Lines, one step per line
+    pub fn to_jsonl(&self) -> String {
+        self.steps
+            .iter()
+            .filter_map(|step| serde_json::to_string(step).ok())
+            .map(|line| line + "\n")
+            .collect()
+    }
+
+    /// Imports trace from JSON Lines, empty lines are skipped
+    ///
+    /// ### Returns
+    /// * InvalidJson error with number of first invalid line
+    pub fn from_jsonl(text: &str) -> Result<Trace, TraceError> {
+        let steps = text
+            .lines()
+            .enumerate()
+            .filter(|(_, line)| !line.trim().is_empty())
+            .map(|(index, line)| {
+                serde_json::from_str(line).map_err(|_| TraceError::InvalidJson(index + 1))
+            })
+            .collect::<Result<Vec<TraceStep>, TraceError>>()?;
+        Ok(Trace { steps })
+    }
+
+    /// Exports trace to compact binary format
+    ///
+    /// Trace starts with magic bytes "SVAT" and version, followed by steps.
+    /// Numbers are LEB128 varints, signed values are zigzag encoded first.
+    /// Step is its number, pc, length and UTF-8 bytes of instruction, number of deltas
+    /// and deltas, each being tag followed by its values
+    pub fn to_binary(&self) -> Vec<u8> {
+        let mut bytes = BINARY_MAGIC.to_vec();
+        bytes.push(BINARY_VERSION);
+        for step in &self.steps {
+            write_varint(&mut bytes, step.step as u64);
+            write_varint(&mut bytes, step.pc as u64);
+            write_varint(&mut bytes, step.instruction.len() as u64);
+            bytes.extend_from_slice(step.instruction.as_bytes());
+            write_varint(&mut bytes, step.deltas.len() as u64);
+            for delta in &step.deltas {
+                bytes.push(delta.get_tag());
+                match delta {
+                    Delta::Acc(value) => write_signed(&mut bytes, *value),
+                    Delta::Register(index, value)
+                    | Delta::Port(index, value)
+                    | Delta::Memory(index, value) => {
+                        write_varint(&mut bytes, *index as u64);
+                        write_signed(&mut bytes, *value);
+                    }
+                    Delta::Flag(flag) => bytes.push(flag.to_bits()),
+                    Delta::Stack(stack) => {
+                        write_varint(&mut bytes, stack.len() as u64);
+                        stack
+                            .iter()
+                            .for_each(|&value| write_signed(&mut bytes, value));
+                    }
+                }
+            }
+        }
+        bytes
+    }
+
+    /// Imports trace from binary format written by to_binary
+    ///
+    /// ### Returns
+    /// * InvalidBinary error with offset of first malformed byte
+    pub fn from_binary(bytes: &[u8]) -> Result<Trace, TraceError> {
+        if bytes.len() < 5 || &bytes[..4] != BINARY_MAGIC || bytes[4] != BINARY_VERSION {
+            return Err(TraceError::InvalidBinary(0));
+        }
+        let mut reader = Reader { bytes, offset: 5 };
+        let mut steps = Vec::new();
+        while reader.offset < bytes.len() {
+            let step = reader.read_usize()?;
+            let pc = reader.read_usize()?;
+            let length = reader.read_usize()?;
+            let start = reader.offset;
+            let instruction = String::from_utf8(reader.read_bytes(length)?.to_vec())
+                .map_err(|_| TraceError::InvalidBinary(start))?;
+            let count = reader.read_usize()?;
+            let mut deltas = Vec::new();
+            for _ in 0..count {
+                let offset = reader.offset;
+                let delta =
+                    match reader.read_bytes(1)?[0] {
+                        0 => Delta::Acc(reader.read_signed()?),
+                        1 => Delta::Register(reader.read_usize()?, reader.read_signed()?),
+                        2 => Delta::Port(reader.read_usize()?, reader.read_signed()?),
+                        3 => Delta::Flag(Flag::from_bits(reader.read_bytes(1)?[0])),
+                        4 => {
+                            let length = reader.read_usize()?;
+                            let stack = (0..length)
+                                .map(|_| reader.read_signed())
+                                .collect::<Result<Vec<i32>, TraceError>>()?;
+                            Delta::Stack(stack)
+                        }
+                        5 => Delta::Memory(reader.read_usize()?, reader.read_signed()?),
+                        _ => return Err(TraceError::InvalidBinary(offset)),
+                    };
+                deltas.push(delta);
+            }
+            steps.push(TraceStep::new(step, pc, instruction, deltas));
+        }
+        Ok(Trace { steps })
+    }
+}
+
+/// Writes number as LEB128 varint
+fn write_varint(bytes: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        bytes.push((value & 0x7F) as u8 | 0x80);
+        value >>= 7;
+    }
+    bytes.push(value as u8);
+}
+
+/// Writes signed number as zigzag encoded varint, so small negative numbers stay short
+fn write_signed(bytes: &mut Vec<u8>, value: i32) {
+    write_varint(bytes, ((value << 1) ^ (value >> 31)) as u32 as u64);
+}
+
+/// Cursor over binary trace
+struct Reader<'a> {
+    bytes: &'a [u8],
+    offset: usize,
+}
+
+impl Reader<'_> {
+    fn read_bytes(&mut self, length: usize) -> Result<&[u8], TraceError> {
+        let end = self
+            .offset
+            .checked_add(length)
+            .filter(|&end| end <= self.bytes.len())
+            .ok_or(TraceError::InvalidBinary(self.offset))?;
+        let bytes = &self.bytes[self.offset..end];
+        self.offset = end;
+        Ok(bytes)
+    }
+
+    fn read_varint(&mut self) -> Result<u64, TraceError> {
+        let start = self.offset;
+        let mut value = 0u64;
+        for shift in (0..64).step_by(7) {
+            let byte = *self
+                .bytes
+                .get(self.offset)
+                .ok_or(TraceError::InvalidBinary(self.offset))?;
+            self.offset += 1;
+            value |= u64::from(byte & 0x7F) << shift;
+            if byte & 0x80 == 0 {
+                return Ok(value);
+            }
+        }
+        Err(TraceError::InvalidBinary(start))
+    }
+
+    fn read_usize(&mut self) -> Result<usize, TraceError> {
+        let start = self.offset;
+        usize::try_from(self.read_varint()?).map_err(|_| TraceError::InvalidBinary(start))
+    }
+
+    fn read_signed(&mut self) -> Result<i32, TraceError> {
+        let start = self.offset;
+        let value =
+            u32::try_from(self.read_varint()?).map_err(|_| TraceError::InvalidBinary(start))?;
+        Ok((value >> 1) as i32 ^ -((value & 1) as i32))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample_trace() -> Trace {
+        let mut trace = Trace::new();
+        trace.record(0, "MOV -300 r1".to_string(), vec![Delta::Register(1, -300)]);
+        trace.record(
+            1,
+            "PSH r1".to_string(),
+            vec![Delta::Stack(vec![-300, i32::MAX, i32::MIN])],
+        );
+        trace.record(
+            2,
+            "SUB 1".to_string(),
+            vec![
+                Delta::Acc(-1),
+                Delta::Flag(Flag::from_result(-1, true, false)),
+            ],
+        );
+        trace.record(3, "MOV acc p5".to_string(), vec![Delta::Port(5, -1)]);
+        trace.record(4, "MOV acc [r1+7]".to_string(), vec![Delta::Memory(7, -1)]);
+        trace
+    }
+
+    #[test]
+    fn test_trace_export() {
+        let trace = sample_trace();
+        let jsonl = trace.to_jsonl();
+        assert_eq!(jsonl.lines().count(), 5);
+        assert_eq!(Trace::from_jsonl(&jsonl), Ok(trace.clone()));
+        assert_eq!(
+            Trace::from_jsonl(&(jsonl + "\n{\"step\":5}\n")),
+            Err(TraceError::InvalidJson(7))
+        );
+
+        let binary = trace.to_binary();
+        assert_eq!(Trace::from_binary(&binary), Ok(trace));
+        assert_eq!(
+            Trace::from_binary(&binary[..binary.len() - 1]),
+            Err(TraceError::InvalidBinary(binary.len() - 1))
+        );
+        assert_eq!(
+            Trace::from_binary(b"SVAT"),
+            Err(TraceError::InvalidBinary(0))
+        );
+        assert_eq!(Trace::from_binary(&binary[..5]), Ok(Trace::new()));
+    }
+
+    #[test]
+    fn test_trace_diff() {
+        let expected = sample_trace();
+        assert_eq!(expected.diff(&expected.clone()), None);
+
+        let mut actual = Trace::new();
+        let steps = expected.get_steps();
+        actual.record(0, "MOV -300 r1".to_string(), vec![Delta::Register(1, -300)]);
+        actual.record(1, "PSH r1".to_string(), vec![Delta::Stack(vec![-300])]);
+        let divergence = expected.diff(&actual).unwrap();
+        assert_eq!(divergence.get_step(), 1);
+        assert_eq!(divergence.get_expected(), Some(steps[1].clone()));
+
+        // shorter trace diverges where it ends
+        let mut prefix = Trace::new();
+        prefix.record(0, "MOV -300 r1".to_string(), vec![Delta::Register(1, -300)]);
+        let divergence = expected.diff(&prefix).unwrap();
+        assert_eq!((divergence.get_step(), divergence.get_actual()), (1, None));
+    }
+
+    #[test]
+    fn test_snapshot_deltas() {
+        let before = Snapshot {
+            acc: 0,
+            flag: Flag::new(),
+            registers: vec![0, 0],
+            ports: vec![1, 2],
+            stack: vec![],
+            memory: vec![0; 4],
+        };
+        let mut after = before.clone();
+        assert!(before.deltas(&after).is_empty());
+        after.ports[1] = 3;
+        after.stack.push(4);
+        after.memory[2] = 5;
+        assert_eq!(
+            before.deltas(&after),
+            vec![
+                Delta::Port(1, 3),
+                Delta::Stack(vec![4]),
+                Delta::Memory(2, 5)
+            ]
+        );
+    }
+}
@@ -2,19 +2,27 @@
 
 use std::fmt::{self};
 
+use std::collections::HashMap;
+use std::mem;
+use std::sync::atomic::AtomicUsize;
 use std::sync::{Arc, Mutex};
+use std::thread;
 use std::thread::JoinHandle;
 use std::time::Duration;
-use std::{collections::HashMap, ops::IndexMut};
-use std::{thread, usize};
 
 use crate::components::connection::Connection;
 use crate::components::port::Port;
 
 use crate::vm::{
+    breakpoint::{Access, Breakpoint, Condition, Location, Watched, Watchpoint, WatchpointHit},
+    cost::CostTable,
+    fault::VmFault,
     flag::Flag,
     instruction::Instruction,
+    journal::{Change, Journal, ReverseError},
     opcodes::{JMPCondition, Opcode},
+    profile::MachineProfile,
+    trace::{Snapshot, Trace},
 };
 
 use super::operand::Operand;
@@ -26,6 +34,62 @@ pub enum VmStatus {
     Running,
     Stopped,
     Finished,
+    /// Program caused fault, pc points at instruction that caused it
+    Faulted(VmFault),
+    /// Paused at breakpoint, pc points at instruction that is not executed yet
+    AtBreakpoint,
+    /// Paused after instruction that triggered watchpoint
+    AtWatchpoint,
+}
+
+/// What PSH does when stack is full and POP does when stack is empty
+#[derive(Debug, serde::Deserialize, serde::Serialize, PartialEq, Clone, Copy, Default)]
+pub enum StackPolicy {
+    /// Vm faults with StackOverflow or StackUnderflow
+    Fault,
+    /// PSH replaces top value, POP from empty stack gives 0
+    #[default]
+    OverwriteTop,
+    /// PSH drops the oldest value, like ring buffer, POP from empty stack gives 0
+    DropOldest,
+    /// Instruction waits until other thread makes space on stack or pushes value to it
+    Block,
+}
+
+/// Kinds of shifts and rotations of acc
+#[derive(Debug, PartialEq, Clone, Copy)]
+enum Shift {
+    Left,
+    /// Fills upper bits with 0
+    LogicalRight,
+    /// Fills upper bits with sign of acc
+    ArithmeticRight,
+    RotateLeft,
+    RotateRight,
+}
+
+/// Result of executing single instruction
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum StepResult {
+    /// Instruction was executed, there are more to execute
+    Continue,
+    /// Instruction can't be executed yet, pc is left at it so it is retried
+    Blocked,
+    /// HLT was executed or program ended
+    Halted,
+    /// Instruction caused fault, vm is in Faulted status
+    Faulted(VmFault),
+    /// Vm paused at breakpoint before executing instruction
+    Breakpoint,
+    /// Vm paused after instruction that triggered watchpoint
+    Watchpoint,
+}
+
+impl StepResult {
+    /// Checks if vm can execute next instruction
+    pub fn is_continue(&self) -> bool {
+        *self == StepResult::Continue
+    }
 }
 
 #[derive(Debug, serde::Deserialize, serde::Serialize)]
@@ -37,9 +101,9 @@ pub struct VirtualMachine {
     /// Flag register contain special states used for branching logic
     flag: Flag,
     /// General purpose registers
-    pub r: [i32; 4],
+    pub r: Vec<i32>,
     /// Ports - registers used for I/O
-    p: [Port; 6],
+    p: Vec<Port>,
     /// Labels used for jumps
     labels: HashMap<String, usize>,
     /// Vector of instructions to be executed
@@ -48,14 +112,104 @@ pub struct VirtualMachine {
     status: VmStatus,
     /// Delay between instruction in ms ( sleep between execution )
     delay_ms: u32,
-    /// vm has stack
-    stack_present: bool,
-    /// Stack size
-    stack_size: usize,
+    /// Number of registers and ports, stack depth and word size
+    #[serde(
+        flatten,
+        serialize_with = "serialize_profile",
+        deserialize_with = "deserialize_profile"
+    )]
+    profile: MachineProfile,
     /// Stack
     stack: Vec<i32>,
+    /// Behaviour of full and empty stack
+    #[serde(default)]
+    stack_policy: StackPolicy,
+    /// Return addresses of CALL, used if vm has no stack
+    #[serde(default)]
+    return_stack: Vec<usize>,
+    /// Data memory accessed by memory operands
+    #[serde(default)]
+    memory: Vec<i32>,
+    /// Places vm pauses at when running
+    #[serde(default)]
+    breakpoints: Vec<Breakpoint>,
+    /// Pc vm was paused at, breakpoint there is skipped when vm continues
+    #[serde(skip)]
+    resume_pc: Option<usize>,
+    /// Places that pause vm when accessed
+    #[serde(default)]
+    watchpoints: Vec<Watchpoint>,
+    /// Watchpoints triggered by executed instruction, with access and values before and after it
+    #[serde(skip)]
+    triggered: Vec<(Watchpoint, Access, i32, i32)>,
+    /// Watchpoints triggered by last executed instruction
+    #[serde(skip)]
+    watchpoint_hits: Vec<WatchpointHit>,
+    /// Side effects of executed instructions, used to step back, None if they are not recorded
+    #[serde(default)]
+    journal: Option<Journal>,
+    /// Executed instructions with values they changed, None if they are not recorded
+    #[serde(default)]
+    trace: Option<Trace>,
+    /// Cycles taken by executed instructions, never decreases
+    #[serde(default)]
+    cycles: u64,
+    /// Number of executed instructions, labels are not counted
+    #[serde(default)]
+    instructions: u64,
+    /// Number of cycles each instruction takes
+    #[serde(default)]
+    costs: CostTable,
+}
+
+/// Profile of saved vm, vm saved before profiles were added has stack settings instead
+#[derive(serde::Deserialize, serde::Serialize)]
+struct SavedProfile {
+    #[serde(default)]
+    profile: Option<MachineProfile>,
+    #[serde(default, skip_serializing)]
+    stack_present: bool,
+    #[serde(default, skip_serializing)]
+    stack_size: usize,
+}
+
+fn serialize_profile<S>(profile: &MachineProfile, serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: serde::Serializer,
+{
+    let saved = SavedProfile {
+        profile: Some(*profile),
+        stack_present: false,
+        stack_size: 0,
+    };
+    serde::Serialize::serialize(&saved, serializer)
+}
+
+fn deserialize_profile<'de, D>(deserializer: D) -> Result<MachineProfile, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    let saved: SavedProfile = serde::Deserialize::deserialize(deserializer)?;
+    Ok(match saved {
+        SavedProfile {
+            profile: Some(profile),
+            ..
+        } => profile,
+        SavedProfile {
+            stack_present: true,
+            stack_size,
+            ..
+        } => MachineProfile::new().with_stack(stack_size),
+        _ => MachineProfile::new(),
+    })
 }
 
+/// Maximal number of nested CALLs on vm without stack
+pub const RETURN_STACK_DEPTH: usize = 256;
+
+/// Time blocked vm on another thread waits before retrying instruction, if it has no delay
+const BLOCKED_RETRY_MS: u32 = 1;
+
 impl Default for VirtualMachine {
     fn default() -> Self {
         Self::new()
@@ -66,30 +220,43 @@ impl VirtualMachine {
     /// Create an instance of VM
     ///
     pub fn new() -> VirtualMachine {
+        VirtualMachine::new_with_profile(MachineProfile::new())
+    }
+
+    /// Create an instance of VM with registers, ports and stack described by profile
+    ///
+    /// ### Arguments
+    ///
+    /// * 'profile' - parameters of machine, the same as used by assembler
+    pub fn new_with_profile(profile: MachineProfile) -> VirtualMachine {
         VirtualMachine {
             pc: 0,
             acc: 0,
-            flag: Flag::EQUAL,
-            r: [0; 4],
-            //TODO:
-            p: [
-                Port::new(0),
-                Port::new(0),
-                Port::new(0),
-                Port::new(0),
-                Port::new(0),
-                Port::new(0),
-            ],
-
+            flag: Flag::new(),
+            r: vec![0; profile.get_registers()],
+            p: vec![Port::new(0); profile.get_ports()],
             labels: HashMap::new(),
             program: Vec::new(),
             status: VmStatus::Initial,
             delay_ms: 0,
-            stack_present: false,
-            stack_size: 0,
-            stack: Vec::new(),
+            profile,
+            stack: Vec::with_capacity(profile.get_stack_depth().unwrap_or(0)),
+            stack_policy: StackPolicy::default(),
+            return_stack: Vec::new(),
+            memory: vec![0; profile.get_memory_size()],
+            breakpoints: Vec::new(),
+            resume_pc: None,
+            watchpoints: Vec::new(),
+            triggered: Vec::new(),
+            watchpoint_hits: Vec::new(),
+            journal: None,
+            trace: None,
+            cycles: 0,
+            instructions: 0,
+            costs: CostTable::new(),
         }
     }
+
     /// Create an instance of VM
     ///
     /// ### Arguments
@@ -98,49 +265,111 @@ impl VirtualMachine {
     ///
     /// ### Returns
     pub fn new_with_program(program: Vec<Instruction>) -> VirtualMachine {
-        let mut vm = VirtualMachine {
-            pc: 0,
-            acc: 0,
-            flag: Flag::EQUAL,
-            r: [0; 4],
-            //TODO:
-            p: [
-                Port::new(0),
-                Port::new(0),
-                Port::new(0),
-                Port::new(0),
-                Port::new(0),
-                Port::new(0),
-            ],
-            program,
-            labels: HashMap::new(),
-            status: VmStatus::Initial,
-            delay_ms: 0,
-            stack_present: false,
-            stack_size: 0,
-            stack: Vec::new(),
-        };
-        vm.set_labels();
+        let mut vm = VirtualMachine::new();
+        vm.load_program(program);
         vm
     }
 
     pub fn with_stack(mut self, size: usize) -> VirtualMachine {
         self.stack = Vec::with_capacity(size);
-        self.stack_present = true;
-        self.stack_size = size;
+        self.profile = self.profile.with_stack(size);
+        self
+    }
+
+    /// Records side effects of executed instructions, so they can be undone by step_back
+    ///
+    /// ### Arguments
+    ///
+    /// * 'budget' - maximal number of bytes used by journal, the oldest entries are dropped
+    ///   when it is exceeded
+    pub fn with_journal(mut self, budget: usize) -> VirtualMachine {
+        self.journal = Some(Journal::new(budget));
+        self
+    }
+
+    /// Sets journal recording side effects of executed instructions, None disables it
+    ///
+    /// Changes of registers, ports or memory cells vm doesn't have are ignored when stepping back
+    pub fn set_journal(&mut self, journal: Option<Journal>) {
+        self.journal = journal;
+    }
+
+    pub fn get_journal(&self) -> Option<&Journal> {
+        self.journal.as_ref()
+    }
+
+    /// Records trace of executed instructions with values they changed
+    pub fn with_trace(mut self) -> VirtualMachine {
+        self.trace = Some(Trace::new());
+        self
+    }
+
+    /// Sets trace executed instructions are added to, None stops recording
+    pub fn set_trace(&mut self, trace: Option<Trace>) {
+        self.trace = trace;
+    }
+
+    pub fn get_trace(&self) -> Option<&Trace> {
+        self.trace.as_ref()
+    }
+
+    /// Takes recorded trace, vm continues recording new one
+    pub fn take_trace(&mut self) -> Option<Trace> {
+        self.trace.as_mut().map(mem::take)
+    }
+
+    /// Sets number of cycles instructions take
+    pub fn with_costs(mut self, costs: CostTable) -> VirtualMachine {
+        self.costs = costs;
+        self
+    }
+
+    pub fn set_costs(&mut self, costs: CostTable) {
+        self.costs = costs;
+    }
+
+    pub fn get_costs(&self) -> CostTable {
+        self.costs.clone()
+    }
+
+    /// Gets number of cycles taken by instructions executed since vm was created
+    /// or counters were reset
+    pub fn get_cycles(&self) -> u64 {
+        self.cycles
+    }
+
+    /// Gets number of instructions executed since vm was created or counters were reset
+    pub fn get_instructions(&self) -> u64 {
+        self.instructions
+    }
+
+    /// Sets cycle and instruction counters to 0, they are not affected by stepping back
+    /// or clearing registers
+    pub fn reset_counters(&mut self) {
+        self.cycles = 0;
+        self.instructions = 0;
+    }
+
+    /// Sets behaviour of PSH on full stack and POP on empty stack
+    pub fn with_stack_policy(mut self, policy: StackPolicy) -> VirtualMachine {
+        self.stack_policy = policy;
         self
     }
 
     pub fn load_program(&mut self, program: Vec<Instruction>) {
         self.labels.clear();
         self.program = program;
+        self.resume_pc = None;
+        if let Some(journal) = &mut self.journal {
+            journal.clear();
+        }
         self.set_labels();
     }
 
     pub fn set_labels(&mut self) {
         for instruction in &self.program {
-            if let Instruction::Label(name, line) = instruction {
-                self.labels.insert(name.clone(), line + 1);
+            if let Instruction::Label(name, _, index) = instruction {
+                self.labels.insert(name.clone(), index + 1);
             }
         }
     }
@@ -165,24 +394,27 @@ impl VirtualMachine {
         self.flag
     }
 
-    pub fn get_registers(&self) -> [i32; 4] {
-        self.r
+    pub fn get_registers(&self) -> Vec<i32> {
+        self.r.clone()
     }
 
-    pub fn get_ports(&self) -> [Port; 6] {
+    pub fn get_ports(&self) -> Vec<Port> {
         self.p.clone()
     }
 
     pub fn get_ports_ref(&self) {}
 
-    //TODO:
-    pub fn get_ports_values(&self) -> [i32; 4] {
-        [0, 0, 0, 0]
+    pub fn get_ports_values(&self) -> Vec<i32> {
+        self.p.iter().map(|port| port.clone().get()).collect()
+    }
+
+    pub fn get_profile(&self) -> MachineProfile {
+        self.profile
     }
 
     /// Gets state of all register (acc, pc, flag, r, p)
-    pub fn get_registers_all(&self) -> (i32, usize, Flag, [i32; 4], [Port; 6]) {
-        (self.acc, self.pc, self.flag, self.r, self.p.clone())
+    pub fn get_registers_all(&self) -> (i32, usize, Flag, Vec<i32>, Vec<Port>) {
+        (self.acc, self.pc, self.flag, self.r.clone(), self.p.clone())
     }
 
     pub fn get_labels(&self) -> HashMap<String, usize> {
@@ -201,6 +433,114 @@ impl VirtualMachine {
         self.stack.clone()
     }
 
+    /// Gets return addresses of CALLs on vm without stack, vm with stack keeps them on stack
+    pub fn get_return_stack(&self) -> Vec<usize> {
+        self.return_stack.clone()
+    }
+
+    pub fn get_stack_policy(&self) -> StackPolicy {
+        self.stack_policy
+    }
+
+    /// Adds breakpoint, unless the same one is already set
+    pub fn add_breakpoint(&mut self, breakpoint: Breakpoint) {
+        if !self.breakpoints.contains(&breakpoint) {
+            self.breakpoints.push(breakpoint);
+        }
+    }
+
+    /// Removes breakpoint
+    ///
+    /// ### Returns
+    /// * false if breakpoint was not set
+    pub fn remove_breakpoint(&mut self, breakpoint: &Breakpoint) -> bool {
+        let count = self.breakpoints.len();
+        self.breakpoints.retain(|set| set != breakpoint);
+        self.breakpoints.len() != count
+    }
+
+    pub fn clear_breakpoints(&mut self) {
+        self.breakpoints.clear();
+    }
+
+    pub fn get_breakpoints(&self) -> Vec<Breakpoint> {
+        self.breakpoints.clone()
+    }
+
+    /// Adds watchpoint, unless the same one is already set
+    pub fn add_watchpoint(&mut self, watchpoint: Watchpoint) {
+        if !self.watchpoints.contains(&watchpoint) {
+            self.watchpoints.push(watchpoint);
+        }
+    }
+
+    /// Removes watchpoint
+    ///
+    /// ### Returns
+    /// * false if watchpoint was not set
+    pub fn remove_watchpoint(&mut self, watchpoint: &Watchpoint) -> bool {
+        let count = self.watchpoints.len();
+        self.watchpoints.retain(|set| set != watchpoint);
+        self.watchpoints.len() != count
+    }
+
+    pub fn clear_watchpoints(&mut self) {
+        self.watchpoints.clear();
+    }
+
+    pub fn get_watchpoints(&self) -> Vec<Watchpoint> {
+        self.watchpoints.clone()
+    }
+
+    /// Gets watchpoints triggered by last executed instruction, with pc and instruction
+    /// that triggered them
+    pub fn get_watchpoint_hits(&self) -> Vec<WatchpointHit> {
+        self.watchpoint_hits.clone()
+    }
+
+    pub fn get_memory(&self) -> Vec<i32> {
+        self.memory.clone()
+    }
+
+    /// Writes values to data memory from outside of program, e.g. to load lookup table
+    ///
+    /// ### Arguments
+    /// * 'address' - address of first written cell
+    /// * 'values' - values written to consecutive cells, wrapped to word size
+    ///
+    /// ### Returns
+    /// * InvalidMemoryAddress fault if values don't fit in memory, nothing is written then
+    pub fn write_memory(&mut self, address: usize, values: &[i32]) -> Result<(), VmFault> {
+        let end = address.saturating_add(values.len());
+        if end > self.memory.len() {
+            let first_invalid = address.max(self.memory.len());
+            return Err(VmFault::InvalidMemoryAddress(first_invalid as i64));
+        }
+        for (cell, value) in self.memory[address..end].iter_mut().zip(values) {
+            *cell = self.profile.wrap(*value);
+        }
+        Ok(())
+    }
+
+    /// Pushes value to stack from outside of program, e.g. by other thread
+    ///
+    /// ### Returns
+    /// * false if vm has no stack or it is full
+    pub fn push_stack(&mut self, value: i32) -> bool {
+        match self.profile.get_stack_depth() {
+            Some(depth) if self.stack.len() < depth => {
+                self.stack.push(self.profile.wrap(value));
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Pops value from stack from outside of program, e.g. by other thread
+    pub fn pop_stack(&mut self) -> Option<i32> {
+        self.stack.pop()
+    }
+
     pub fn get_next_instruction(&self) -> Option<Instruction> {
         self.program.get(self.pc).cloned()
     }
@@ -212,8 +552,8 @@ impl VirtualMachine {
         i32,
         usize,
         Flag,
-        [i32; 4],
-        [Port; 6],
+        Vec<i32>,
+        Vec<Port>,
         HashMap<String, usize>,
         Vec<Instruction>,
     ) {
@@ -221,27 +561,38 @@ impl VirtualMachine {
             self.acc,
             self.pc,
             self.flag,
-            self.r,
+            self.r.clone(),
             self.p.clone(),
             self.labels.clone(),
             self.program.clone(),
         )
     }
 
-    pub fn get_state_for_display(&self) -> (i32, usize, Flag, [i32; 4], [i32; 6], VmStatus, u32) {
-        let mut port_values: [i32; 6] = [0; 6];
-        for (i, p) in self.p.iter().enumerate() {
-            port_values[i] = p.clone().get();
-        }
-
+    #[allow(clippy::type_complexity)]
+    /// Gets state shown to user (acc, pc, flag, r, p, status, delay, cycles, instructions)
+    pub fn get_state_for_display(
+        &self,
+    ) -> (
+        i32,
+        usize,
+        Flag,
+        Vec<i32>,
+        Vec<i32>,
+        VmStatus,
+        u32,
+        u64,
+        u64,
+    ) {
         (
             self.acc,
             self.pc,
             self.flag,
-            self.r,
-            port_values,
+            self.r.clone(),
+            self.get_ports_values(),
             self.status,
             self.delay_ms,
+            self.cycles,
+            self.instructions,
         )
     }
 
@@ -256,11 +607,16 @@ impl VirtualMachine {
     pub fn clear_registers(&mut self) {
         self.pc = 0;
         self.acc = 0;
-        self.flag = Flag::EQUAL;
+        self.flag = Flag::new();
         self.r.iter_mut().for_each(|item| *item = 0);
-        if self.stack_present {
+        if self.profile.has_stack() {
             self.stack.clear();
         }
+        self.return_stack.clear();
+        self.resume_pc = None;
+        if let Some(journal) = &mut self.journal {
+            journal.clear();
+        }
     }
     /// Connects vm with connection to shared data across threads
     ///
@@ -271,6 +627,9 @@ impl VirtualMachine {
     ///
     pub fn connect(&mut self, index: usize, connection: &mut Connection) {
         self.p[index].connect(connection);
+        if let Some(journal) = &mut self.journal {
+            journal.mark_port_irreversible(index);
+        }
     }
 
     /// Connects vm with connection to shared data across threads with id of port
@@ -282,7 +641,7 @@ impl VirtualMachine {
     /// * id of port
     ///
     pub fn connect_with_id(&mut self, index: usize, connection: &mut Connection, id: String) {
-        self.p[index].connect(connection);
+        self.connect(index, connection);
         connection.add_port_id(id);
     }
 
@@ -312,13 +671,7 @@ impl VirtualMachine {
 
     #[allow(dead_code)]
     fn sleep(&mut self, operand: Operand) {
-        let _duration = match operand {
-            Operand::IntegerValue(value) => value,
-            Operand::GeneralRegister(index) => self.r[index],
-            Operand::PortRegister(index) => self.p[index].get(),
-            Operand::ACC => self.acc,
-            Operand::PC => self.pc.try_into().unwrap_or(0),
-        };
+        let _duration = self.read_operand(&operand).unwrap_or(0);
         VirtualMachine::delay(self.delay_ms)
 
         //thread::sleep(Duration::from_millis(duration.try_into().unwrap_or(0)));
@@ -330,62 +683,178 @@ impl VirtualMachine {
     /// * Operand - i32 or register
     /// * Register - register
     ///
-    fn move_operand(&mut self, operand1: Operand, operand2: Operand) {
-        match (operand1, operand2) {
-            (Operand::IntegerValue(_), Operand::IntegerValue(_)) => unreachable!(),
-
-            (Operand::IntegerValue(value), Operand::GeneralRegister(index)) => {
-                self.r[index] = value
-            }
+    fn move_operand(&mut self, operand1: Operand, operand2: Operand) -> Result<(), VmFault> {
+        let value = self.read_operand(&operand1)?;
+        self.write_operand(&operand2, value)
+    }
 
-            (Operand::IntegerValue(value), Operand::PortRegister(index)) => {
-                self.p[index].set(value)
-            }
-            (Operand::IntegerValue(value), Operand::ACC) => self.acc = value,
-            (Operand::IntegerValue(value), Operand::PC) => self.pc = value as usize,
+    /// Gets value of operand, notifying watchpoints about read
+    ///
+    /// ### Returns
+    /// * IllegalOperand fault if vm doesn't have register or port
+    fn read_operand(&mut self, operand: &Operand) -> Result<i32, VmFault> {
+        let value = self.peek_operand(operand)?;
+        self.watch(
+            &Watched::Operand(operand.clone()),
+            Access::Read,
+            value,
+            value,
+        );
+        Ok(value)
+    }
 
-            (Operand::GeneralRegister(_), Operand::IntegerValue(_)) => unreachable!(),
-            (Operand::GeneralRegister(index), Operand::GeneralRegister(index2)) => {
-                self.r[index2] = self.r[index]
+    /// Gets value of operand without notifying watchpoints
+    ///
+    /// ### Returns
+    /// * IllegalOperand fault if vm doesn't have register or port
+    fn peek_operand(&mut self, operand: &Operand) -> Result<i32, VmFault> {
+        match *operand {
+            Operand::IntegerValue(value) => Ok(value),
+            Operand::GeneralRegister(index) => {
+                self.r.get(index).copied().ok_or(VmFault::IllegalOperand)
             }
-
-            (Operand::GeneralRegister(index), Operand::PortRegister(index2)) => {
-                self.p[index2].set(self.r[index])
+            Operand::PortRegister(index) => self
+                .p
+                .get_mut(index)
+                .map(|port| port.get())
+                .ok_or(VmFault::IllegalOperand),
+            Operand::ACC => Ok(self.acc),
+            Operand::PC => Ok(self.pc as i32),
+            Operand::Memory(register, offset) => {
+                let address = self.memory_address(register, offset)?;
+                Ok(self.memory[address])
             }
-            (Operand::GeneralRegister(index), Operand::ACC) => self.acc = self.r[index],
-            (Operand::GeneralRegister(index), Operand::PC) => self.pc = self.r[index] as usize,
+        }
+    }
 
-            (Operand::PortRegister(_), Operand::IntegerValue(_)) => unreachable!(),
+    /// Finds index of data memory cell memory operand refers to
+    ///
+    /// ### Returns
+    /// * IllegalOperand fault if vm doesn't have register
+    /// * InvalidMemoryAddress fault if address is outside of memory
+    fn memory_address(&self, register: usize, offset: i32) -> Result<usize, VmFault> {
+        let base = *self.r.get(register).ok_or(VmFault::IllegalOperand)?;
+        let address = i64::from(base) + i64::from(offset);
+        usize::try_from(address)
+            .ok()
+            .filter(|&address| address < self.memory.len())
+            .ok_or(VmFault::InvalidMemoryAddress(address))
+    }
 
-            (Operand::PortRegister(index), Operand::GeneralRegister(index2)) => {
-                self.r[index2] = self.p[index].get()
+    /// Stores value in register, value is wrapped to word size of machine
+    ///
+    /// ### Returns
+    /// * IllegalOperand fault if operand is integer or vm doesn't have register or port
+    /// * InvalidJumpTarget fault if value written to pc is outside of program
+    fn write_operand(&mut self, operand: &Operand, value: i32) -> Result<(), VmFault> {
+        let value = self.profile.wrap(value);
+        let old_value = match self.watchpoints.is_empty() {
+            true => 0,
+            false => self.peek_operand(operand).unwrap_or(0),
+        };
+        match *operand {
+            Operand::IntegerValue(_) => return Err(VmFault::IllegalOperand),
+            Operand::GeneralRegister(index) => {
+                let register = self.r.get_mut(index).ok_or(VmFault::IllegalOperand)?;
+                let old_value = mem::replace(register, value);
+                self.record(Change::Register(index, old_value));
             }
-
-            (Operand::PortRegister(index), Operand::PortRegister(index2)) => {
-                let new_value = self.p[index].get();
-                self.p[index2].set(new_value);
+            Operand::PortRegister(index) => {
+                let reversible = self
+                    .journal
+                    .as_ref()
+                    .is_some_and(|journal| journal.is_port_reversible(index));
+                let port = self.p.get_mut(index).ok_or(VmFault::IllegalOperand)?;
+                let change = Change::Port {
+                    index,
+                    old_value: port.get(),
+                    connected: matches!(port, Port::Connected(..)) && !reversible,
+                };
+                port.set(value);
+                self.record(change);
             }
+            Operand::ACC => self.acc = value,
+            // pc equal to program length ends program
+            Operand::PC => match usize::try_from(value) {
+                Ok(target) if target <= self.program.len() => self.pc = target,
+                _ => return Err(VmFault::InvalidJumpTarget(value)),
+            },
+            Operand::Memory(register, offset) => {
+                let address = self.memory_address(register, offset)?;
+                let old_value = mem::replace(&mut self.memory[address], value);
+                self.record(Change::Memory(address, old_value));
+            }
+        }
+        // write to pc is reported once for whole instruction by execute
+        if *operand != Operand::PC {
+            self.watch(
+                &Watched::Operand(operand.clone()),
+                Access::Write,
+                old_value,
+                value,
+            );
+        }
+        Ok(())
+    }
 
-            (Operand::PortRegister(index), Operand::ACC) => self.acc = self.p[index].get(),
-
-            (Operand::PortRegister(index), Operand::PC) => self.pc = self.p[index].get() as usize,
-            (Operand::ACC, Operand::IntegerValue(_)) => unreachable!(),
-            (Operand::ACC, Operand::GeneralRegister(index)) => self.r[index] = self.acc,
+    /// Adds side effect of executed instruction to journal, if vm records it
+    fn record(&mut self, change: Change) {
+        if let Some(journal) = &mut self.journal {
+            journal.record(change);
+        }
+    }
 
-            (Operand::ACC, Operand::PortRegister(index)) => self.p[index].set(self.acc),
-            (Operand::ACC, Operand::ACC) => {}
-            (Operand::ACC, Operand::PC) => self.pc = self.acc as usize,
+    /// Notifies watchpoints about PSH or POP changing stack depth
+    fn watch_stack(&mut self, access: Access, old_depth: usize) {
+        let depth = self.stack.len();
+        self.watch(&Watched::StackDepth, access, old_depth as i32, depth as i32);
+    }
 
-            (Operand::PC, Operand::IntegerValue(_)) => unreachable!(),
-            (Operand::PC, Operand::GeneralRegister(index)) => self.r[index] = self.pc as i32,
+    /// Records watchpoints triggered by access to place
+    ///
+    /// ### Arguments
+    /// * 'accessed' - place that was read or written
+    /// * 'access' - Read or Write
+    /// * 'old_value' - value before access
+    /// * 'new_value' - value after access, the same as old one for reads
+    fn watch(&mut self, accessed: &Watched, access: Access, old_value: i32, new_value: i32) {
+        if self.watchpoints.is_empty() {
+            return;
+        }
+        let triggered: Vec<Watchpoint> = self
+            .watchpoints
+            .iter()
+            .filter(|watchpoint| {
+                self.is_same_place(&watchpoint.get_watched(), accessed)
+                    && watchpoint.is_triggered_by(access, old_value, new_value)
+            })
+            .cloned()
+            .collect();
+        self.triggered.extend(
+            triggered
+                .into_iter()
+                .map(|watchpoint| (watchpoint, access, old_value, new_value)),
+        );
+    }
 
-            (Operand::PC, Operand::PortRegister(index)) => self.p[index].set(self.pc as i32),
-            (Operand::PC, Operand::ACC) => self.acc = self.pc as i32,
-            (Operand::PC, Operand::PC) => {}
+    /// Checks if watched place is the accessed one, memory cells are compared by address
+    fn is_same_place(&self, watched: &Watched, accessed: &Watched) -> bool {
+        match (watched, accessed) {
+            (
+                Watched::Operand(Operand::Memory(register1, offset1)),
+                Watched::Operand(Operand::Memory(register2, offset2)),
+            ) => matches!(
+                (
+                    self.memory_address(*register1, *offset1),
+                    self.memory_address(*register2, *offset2),
+                ),
+                (Ok(address1), Ok(address2)) if address1 == address2
+            ),
+            _ => watched == accessed,
         }
     }
 
-    /// apply operation on acc
+    /// apply bitwise, minimum or maximum operation on acc, sets zero and negative flags
     ///
     /// ### Arguments
     ///
@@ -395,22 +864,140 @@ impl VirtualMachine {
     /// ### Example
     ///
     /// ```rs
-    ///     apply_operation(operand, |a, b| a + b)
+    ///     apply_operation(operand, |a, b| a & b)
     /// ```
-    fn apply_operation<F>(&mut self, operand: Operand, operation: F)
+    fn apply_operation<F>(&mut self, operand: Operand, operation: F) -> Result<(), VmFault>
     where
         F: Fn(i32, i32) -> i32,
     {
-        match operand {
-            Operand::IntegerValue(value) => {
-                self.acc = operation(self.acc, value);
-            }
-            Operand::GeneralRegister(index) => self.acc = operation(self.acc, self.r[index]),
+        let value = self.read_operand(&operand)?;
+        let acc = self.read_operand(&Operand::ACC)?;
+        let result = self.profile.wrap(operation(acc, self.profile.wrap(value)));
+        self.flag = Flag::from_result(result, false, false);
+        self.write_operand(&Operand::ACC, result)
+    }
+
+    /// apply arithmetic operation on acc, sets all flags
+    ///
+    /// ### Arguments
+    ///
+    /// * 'Operand' - i32 or Register
+    /// * 'operation' - closure taking to two parameters: acc and operand, computing exact result
+    /// * 'sets_carry' - whether carry is set if unsigned result doesn't fit in word
+    ///
+    /// ### Example
+    ///
+    /// ```rs
+    ///     apply_arithmetic(operand, |a, b| a + b, true)
+    /// ```
+    fn apply_arithmetic<F>(
+        &mut self,
+        operand: Operand,
+        operation: F,
+        sets_carry: bool,
+    ) -> Result<(), VmFault>
+    where
+        F: Fn(i128, i128) -> i128,
+    {
+        let value = self.read_operand(&operand)?;
+        let acc = self.read_operand(&Operand::ACC)?;
+        let (result, flag) = self.calculate(acc, value, operation, sets_carry);
+        self.flag = flag;
+        self.write_operand(&Operand::ACC, result)
+    }
 
-            Operand::PortRegister(index) => self.acc = operation(self.acc, self.p[index].get()),
-            Operand::ACC => self.acc = operation(self.acc, self.acc),
-            Operand::PC => self.acc = operation(self.acc, self.pc as i32),
+    /// apply division or remainder on acc, sets all flags except carry
+    ///
+    /// ### Returns
+    /// * DivideByZero fault if operand is 0
+    fn apply_division<F>(&mut self, operand: Operand, operation: F) -> Result<(), VmFault>
+    where
+        F: Fn(i128, i128) -> i128,
+    {
+        let value = self.read_operand(&operand)?;
+        if self.profile.wrap(value) == 0 {
+            return Err(VmFault::DivideByZero);
         }
+        let acc = self.read_operand(&Operand::ACC)?;
+        let (result, flag) = self.calculate(acc, value, operation, false);
+        self.flag = flag;
+        self.write_operand(&Operand::ACC, result)
+    }
+
+    /// Computes operation on words and flags describing its result
+    ///
+    /// Operation is computed exactly, once on signed and once on unsigned values of words,
+    /// overflow and carry are set if these results don't fit in word
+    fn calculate<F>(&self, value1: i32, value2: i32, operation: F, sets_carry: bool) -> (i32, Flag)
+    where
+        F: Fn(i128, i128) -> i128,
+    {
+        let (value1, value2) = (self.profile.wrap(value1), self.profile.wrap(value2));
+        let signed = operation(value1.into(), value2.into());
+        let unsigned = operation(
+            self.profile.to_unsigned(value1).into(),
+            self.profile.to_unsigned(value2).into(),
+        );
+
+        let result = self.profile.wrap(signed as i32);
+        let max_unsigned = i128::from(self.profile.to_unsigned(-1));
+        let carry = sets_carry && !(0..=max_unsigned).contains(&unsigned);
+        let overflow = i128::from(result) != signed;
+        (result, Flag::from_result(result, carry, overflow))
+    }
+
+    /// Shifts or rotates acc by operand, carry is set to the last bit shifted out
+    ///
+    /// Shift by negative amount doesn't change acc, rotation by negative amount
+    /// rotates in opposite direction
+    fn apply_shift(&mut self, operand: Operand, shift: Shift) -> Result<(), VmFault> {
+        let value = self.read_operand(&operand)?;
+        let acc = self.read_operand(&Operand::ACC)?;
+        let word_size = self.profile.get_word_size();
+        let bits = u64::from(self.profile.to_unsigned(acc));
+        let signed = i64::from(self.profile.wrap(acc));
+
+        let amount = match shift {
+            Shift::RotateLeft | Shift::RotateRight => value.rem_euclid(word_size as i32) as u32,
+            // shifting by more than word size gives the same result and carry as by word size + 1
+            _ => u32::try_from(value).unwrap_or(0).min(word_size + 1),
+        };
+        let (result, carry) = match (shift, amount) {
+            (_, 0) => (bits, false),
+            (Shift::Left, amount) => {
+                let shifted = bits << amount;
+                (shifted, (shifted >> word_size) & 1 == 1)
+            }
+            (Shift::LogicalRight, amount) => (bits >> amount, (bits >> (amount - 1)) & 1 == 1),
+            (Shift::ArithmeticRight, amount) => {
+                ((signed >> amount) as u64, (signed >> (amount - 1)) & 1 == 1)
+            }
+            (Shift::RotateLeft, amount) => {
+                let rotated = bits << amount | bits >> (word_size - amount);
+                (rotated, rotated & 1 == 1)
+            }
+            (Shift::RotateRight, amount) => {
+                let rotated = bits >> amount | bits << (word_size - amount);
+                (rotated, (rotated >> (word_size - 1)) & 1 == 1)
+            }
+        };
+        let result = self.profile.wrap(result as i32);
+        self.flag = Flag::from_result(result, carry, false);
+        self.write_operand(&Operand::ACC, result)
+    }
+
+    /// Limits acc to range given by operands, upper bound wins if bounds are reversed
+    fn clamp(&mut self, lower: Operand, upper: Operand) -> Result<(), VmFault> {
+        let lower = self.read_operand(&lower)?;
+        let upper = self.read_operand(&upper)?;
+        let acc = self.read_operand(&Operand::ACC)?;
+        let result = self
+            .profile
+            .wrap(acc)
+            .max(self.profile.wrap(lower))
+            .min(self.profile.wrap(upper));
+        self.flag = Flag::from_result(result, false, false);
+        self.write_operand(&Operand::ACC, result)
     }
 
     #[allow(dead_code)]
@@ -422,80 +1009,155 @@ impl VirtualMachine {
     }
 
     /// Jumps to label
-    fn jump_to_label(&mut self, label: &str, condition: JMPCondition) {
-        if let Some(&jmp_to) = self.labels.get(label) {
-            match (self.flag, condition) {
-                (Flag::EQUAL, JMPCondition::EQ) => self.pc = jmp_to,
-                (Flag::LESSER | Flag::GREATER, JMPCondition::NEQ) => self.pc = jmp_to,
-                (Flag::GREATER, JMPCondition::GRT) => self.pc = jmp_to,
-                (Flag::LESSER, JMPCondition::LST) => self.pc = jmp_to,
-                (_, JMPCondition::NONE) => self.pc = jmp_to,
-                _ => {}
-            }
+    ///
+    /// Label is looked up by name in index of labels built when program was loaded,
+    /// jump opcodes don't store resolved target themselves
+    ///
+    /// ### Returns
+    /// * UndefinedLabel fault if program doesn't declare label, e.g. hand-built program
+    fn jump_to_label(&mut self, label: &str, condition: JMPCondition) -> Result<(), VmFault> {
+        let &jmp_to = self.labels.get(label).ok_or(VmFault::UndefinedLabel)?;
+        let jump = match condition {
+            JMPCondition::EQ | JMPCondition::ZERO => self.flag.is_zero(),
+            JMPCondition::NEQ | JMPCondition::NZERO => !self.flag.is_zero(),
+            JMPCondition::GRT => self.flag.is_greater(),
+            JMPCondition::LST => self.flag.is_lesser(),
+            JMPCondition::CARRY => self.flag.is_carry(),
+            JMPCondition::OVERFLOW => self.flag.is_overflow(),
+            JMPCondition::SIGN => self.flag.is_negative(),
+            JMPCondition::ABOVE => self.flag.is_above(),
+            JMPCondition::ABOVEEQ => !self.flag.is_below(),
+            JMPCondition::BELOW => self.flag.is_below(),
+            JMPCondition::BELOWEQ => !self.flag.is_above(),
+            JMPCondition::NONE => true,
+        };
+        if jump {
+            self.pc = jmp_to;
         }
+        Ok(())
     }
 
-    /// Compares operands
-    fn compare(&mut self, operand1: Operand, operand2: Operand) {
-        let value1 = match operand1 {
-            Operand::IntegerValue(value) => value,
-            Operand::GeneralRegister(index) => self.r[index],
-
-            Operand::PortRegister(index) => self.p[index].get(),
-            Operand::ACC => self.acc,
-            Operand::PC => self.pc as i32,
-        };
-
-        let value2 = match operand2 {
-            Operand::IntegerValue(value) => value,
-            Operand::GeneralRegister(index) => self.r[index],
+    /// Compares operands, sets flags like subtraction of second operand from first one
+    fn compare(&mut self, operand1: Operand, operand2: Operand) -> Result<(), VmFault> {
+        let value1 = self.read_operand(&operand1)?;
+        let value2 = self.read_operand(&operand2)?;
 
-            Operand::PortRegister(index) => self.p[index].get(),
-            Operand::ACC => self.acc,
-            Operand::PC => self.pc as i32,
-        };
+        let (_, flag) = self.calculate(value1, value2, |a, b| a - b, true);
+        self.flag = flag;
+        Ok(())
+    }
 
-        let result = value1 - value2;
-        match result {
-            0 => self.flag = Flag::EQUAL,
-            n if n < 0 => self.flag = Flag::LESSER,
-            n if n > 0 => self.flag = Flag::GREATER,
-            _ => unreachable!(),
+    /// pushes operand to stack ( if stack is present)
+    ///
+    /// Full stack is handled according to stack policy
+    fn push_to_stack(&mut self, operand: Operand) -> Result<StepResult, VmFault> {
+        let stack_size = self
+            .profile
+            .get_stack_depth()
+            .ok_or(VmFault::StackNotPresent)?;
+        let value = self.read_operand(&operand)?;
+        let value = self.profile.wrap(value);
+        let depth = self.stack.len();
+
+        if depth < stack_size {
+            self.stack.push(value);
+            self.record(Change::StackPush);
+        } else {
+            match self.stack_policy {
+                StackPolicy::Fault => return Err(VmFault::StackOverflow),
+                StackPolicy::OverwriteTop => {
+                    if let Some(top) = self.stack.last_mut() {
+                        let old_value = mem::replace(top, value);
+                        self.record(Change::StackOverwriteTop(old_value));
+                    }
+                }
+                StackPolicy::DropOldest => {
+                    if stack_size > 0 {
+                        let oldest = self.stack.remove(0);
+                        self.stack.push(value);
+                        self.record(Change::StackDropOldest(oldest));
+                        self.record(Change::StackPush);
+                    }
+                }
+                StackPolicy::Block => return Ok(StepResult::Blocked),
+            }
         }
+        self.watch_stack(Access::Write, depth);
+        Ok(StepResult::Continue)
     }
 
-    /// pushes operand to stack ( if stack is present)
-    fn push_to_stack(&mut self, operand: Operand) {
-        if !self.stack_present {
-            return;
+    /// pushes operand from stack ( if stack is present)
+    ///
+    /// Empty stack is handled according to stack policy
+    fn pop_from_stack(&mut self, operand: Operand) -> Result<StepResult, VmFault> {
+        if !self.profile.has_stack() {
+            return Err(VmFault::StackNotPresent);
         }
-        let value = match operand {
-            Operand::IntegerValue(value) => value,
-            Operand::GeneralRegister(index) => self.r[index],
-            Operand::PortRegister(index) => self.p[index].get(),
-            Operand::ACC => self.acc,
-            Operand::PC => self.pc.try_into().unwrap(),
+        let depth = self.stack.len();
+        let value = match (self.stack.pop(), self.stack_policy) {
+            (Some(value), _) => {
+                self.record(Change::StackPop(value));
+                value
+            }
+            (None, StackPolicy::Fault) => return Err(VmFault::StackUnderflow),
+            (None, StackPolicy::Block) => return Ok(StepResult::Blocked),
+            (None, StackPolicy::OverwriteTop | StackPolicy::DropOldest) => 0,
         };
+        self.watch(
+            &Watched::StackDepth,
+            Access::Read,
+            depth as i32,
+            depth as i32,
+        );
+        self.watch_stack(Access::Write, depth);
+        self.write_operand(&operand, value)?;
+        Ok(StepResult::Continue)
+    }
 
-        if self.stack.len() < self.stack_size {
-            self.stack.push(value);
-            return;
+    /// Saves address of next instruction and jumps to label
+    ///
+    /// Address is pushed to stack if vm has one, otherwise to dedicated return stack.
+    /// Full stack is a fault for every stack policy except Block, so return address is never lost,
+    /// nor is it wrapped if it doesn't fit in word of stack
+    fn call(&mut self, label: &str) -> Result<StepResult, VmFault> {
+        let &target = self.labels.get(label).ok_or(VmFault::UndefinedLabel)?;
+        let return_address = self.pc;
+        let stack_depth = self.stack.len();
+        match self.profile.get_stack_depth() {
+            Some(depth) if stack_depth < depth => {
+                let value = i32::try_from(return_address)
+                    .ok()
+                    .filter(|&value| self.profile.wrap(value) == value)
+                    .ok_or(VmFault::ReturnAddressOverflow(return_address))?;
+                self.stack.push(value);
+                self.record(Change::StackPush);
+                self.watch_stack(Access::Write, stack_depth);
+            }
+            Some(_) if self.stack_policy == StackPolicy::Block => return Ok(StepResult::Blocked),
+            None if self.return_stack.len() < RETURN_STACK_DEPTH => {
+                self.return_stack.push(return_address);
+                self.record(Change::ReturnPush);
+            }
+            _ => return Err(VmFault::StackOverflow),
         }
-        *self.stack.index_mut(self.stack_size - 1) = value;
+        self.pc = target;
+        Ok(StepResult::Continue)
     }
 
-    /// pushes operand from stack ( if stack is present)
-    fn pop_from_stack(&mut self, operand: Operand) {
-        if !self.stack_present {
-            return;
+    /// Jumps to address saved by the most recent CALL
+    ///
+    /// Empty stack is a fault for every stack policy except Block
+    fn return_from_call(&mut self) -> Result<StepResult, VmFault> {
+        if !self.profile.has_stack() {
+            let target = self.return_stack.pop().ok_or(VmFault::StackUnderflow)?;
+            self.record(Change::ReturnPop(target));
+            self.pc = target;
+            return Ok(StepResult::Continue);
         }
-        let value = self.stack.pop().unwrap_or(0);
-        match operand {
-            Operand::IntegerValue(_) => unreachable!(),
-            Operand::GeneralRegister(index) => self.r[index] = value,
-            Operand::PortRegister(index) => self.p[index].set(value),
-            Operand::ACC => self.acc = value,
-            Operand::PC => self.pc = value.try_into().unwrap_or(0),
+        match self.stack_policy {
+            StackPolicy::Block => self.pop_from_stack(Operand::PC),
+            _ if self.stack.is_empty() => Err(VmFault::StackUnderflow),
+            _ => self.pop_from_stack(Operand::PC),
         }
     }
 
@@ -505,60 +1167,427 @@ impl VirtualMachine {
         self.pc += 1;
         opcode
     }
+
     /// Executes single instruction
-    pub fn execute(&mut self) -> bool {
+    ///
+    /// If instruction causes fault, vm enters Faulted status and pc is left at that instruction.
+    /// Watchpoints triggered by instruction are reported by get_watchpoint_hits.
+    /// Faulted instruction is recorded in journal too, so its partial side effects can be undone
+    pub fn execute(&mut self) -> StepResult {
+        self.watchpoint_hits.clear();
+        self.skip_labels();
         if self.pc >= self.program.len() {
-            return false;
+            return StepResult::Halted;
         }
-        let instruction = self.fetch();
-
-        match instruction {
-            Instruction::Opcode(opcode) => {
-                match opcode {
-                    // ------------ Control instructions ------------
-                    Opcode::HLT => {
-                        return false;
+        let pc = self.pc;
+        if let Some(journal) = &mut self.journal {
+            journal.begin(pc, self.acc, self.flag);
+        }
+        let before = self.trace.is_some().then(|| self.snapshot());
+        let result = match self.execute_instruction() {
+            Ok(StepResult::Blocked) => {
+                self.pc = pc;
+                if let Some(journal) = &mut self.journal {
+                    journal.discard();
+                }
+                StepResult::Blocked
+            }
+            Ok(result) => {
+                self.resume_pc = None;
+                self.cycles += self.costs.get_cost(&self.program[pc]);
+                self.instructions += 1;
+                if let Some(before) = before {
+                    let deltas = before.deltas(&self.snapshot());
+                    let instruction = self.program[pc].to_string();
+                    if let Some(trace) = &mut self.trace {
+                        trace.record(pc, instruction, deltas);
+                    }
+                }
+                // every executed instruction writes pc, moving it to the next instruction or jumping
+                self.watch(
+                    &Watched::Operand(Operand::PC),
+                    Access::Write,
+                    pc as i32,
+                    self.pc as i32,
+                );
+                self.watchpoint_hits = std::mem::take(&mut self.triggered)
+                    .into_iter()
+                    .map(|(watchpoint, access, old_value, new_value)| {
+                        let instruction = self.program[pc].clone();
+                        WatchpointHit::new(
+                            watchpoint,
+                            pc,
+                            instruction,
+                            access,
+                            (old_value, new_value),
+                        )
+                    })
+                    .collect();
+                result
+            }
+            Err(fault) => {
+                self.pc = pc;
+                self.status = VmStatus::Faulted(fault);
+                StepResult::Faulted(fault)
+            }
+        };
+        // accesses of blocked or faulted instruction don't count, it is not completed
+        self.triggered.clear();
+        if let Some(journal) = &mut self.journal {
+            journal.commit();
+        }
+        self.skip_labels();
+        result
+    }
+
+    /// Moves pc past labels, they take no cycles and are not executed
+    fn skip_labels(&mut self) {
+        self.pc = self.first_executed(self.pc);
+    }
+
+    /// Gets index of first instruction at or after index that is not label
+    fn first_executed(&self, mut index: usize) -> usize {
+        while let Some(Instruction::Label(..)) = self.program.get(index) {
+            index += 1;
+        }
+        index
+    }
+
+    /// Gets values compared by trace before and after instruction
+    fn snapshot(&self) -> Snapshot {
+        Snapshot {
+            acc: self.acc,
+            flag: self.flag,
+            registers: self.r.clone(),
+            ports: self.get_ports_values(),
+            stack: self.stack.clone(),
+            memory: self.memory.clone(),
+        }
+    }
+
+    /// Undoes the most recently executed instruction, vm is Stopped at it afterwards
+    ///
+    /// ### Returns
+    /// * JournalDisabled if vm doesn't record journal
+    /// * JournalEmpty if there is no recorded instruction left
+    /// * IrreversiblePortWrite if instruction wrote to connected port and components connected
+    ///   to it are not all vms journaled together with this one
+    pub fn step_back(&mut self) -> Result<(), ReverseError> {
+        let journal = self.journal.as_mut().ok_or(ReverseError::JournalDisabled)?;
+        let entry = journal.get_last().ok_or(ReverseError::JournalEmpty)?;
+        if entry.has_connected_port_write() {
+            return Err(ReverseError::IrreversiblePortWrite(entry.get_pc()));
+        }
+        if let Some(entry) = journal.pop() {
+            for change in entry.get_changes().into_iter().rev() {
+                self.undo(change);
+            }
+            self.pc = entry.get_pc();
+            self.acc = entry.get_acc();
+            self.flag = entry.get_flag();
+        }
+        self.watchpoint_hits.clear();
+        self.pause(VmStatus::Stopped);
+        Ok(())
+    }
+
+    /// Undoes instructions until the most recent execution of instruction at pc is undone
+    ///
+    /// Nothing is undone if any instruction on the way can't be undone
+    ///
+    /// ### Returns
+    /// * number of undone instructions
+    /// * NotInJournal if instruction at pc is not recorded in journal
+    pub fn run_back_to(&mut self, pc: usize) -> Result<usize, ReverseError> {
+        let journal = self.journal.as_ref().ok_or(ReverseError::JournalDisabled)?;
+        let entries = journal.get_entries();
+        let count = entries
+            .iter()
+            .rev()
+            .position(|entry| entry.get_pc() == pc)
+            .ok_or(ReverseError::NotInJournal(pc))?
+            + 1;
+        if let Some(entry) = entries
+            .iter()
+            .rev()
+            .take(count)
+            .find(|entry| entry.has_connected_port_write())
+        {
+            return Err(ReverseError::IrreversiblePortWrite(entry.get_pc()));
+        }
+        for _ in 0..count {
+            self.step_back()?;
+        }
+        Ok(count)
+    }
+
+    /// Reverts single side effect of instruction
+    ///
+    /// Change of register, port or memory cell vm doesn't have is ignored
+    fn undo(&mut self, change: Change) {
+        match change {
+            Change::Register(index, old_value) => {
+                if let Some(register) = self.r.get_mut(index) {
+                    *register = old_value;
+                }
+            }
+            Change::Port {
+                index, old_value, ..
+            } => {
+                if let Some(port) = self.p.get_mut(index) {
+                    port.set(old_value);
+                }
+            }
+            Change::Memory(address, old_value) => {
+                if let Some(cell) = self.memory.get_mut(address) {
+                    *cell = old_value;
+                }
+            }
+            Change::StackPush => {
+                self.stack.pop();
+            }
+            Change::StackPop(value) => self.stack.push(value),
+            Change::StackDropOldest(value) => self.stack.insert(0, value),
+            Change::StackOverwriteTop(old_value) => {
+                if let Some(top) = self.stack.last_mut() {
+                    *top = old_value;
+                }
+            }
+            Change::ReturnPush => {
+                self.return_stack.pop();
+            }
+            Change::ReturnPop(address) => self.return_stack.push(address),
+        }
+    }
+
+    /// Checks if vm should pause at breakpoint before executing instruction pc points at
+    ///
+    /// Breakpoint at instruction vm was paused at is skipped, so vm can continue from it
+    fn is_at_breakpoint(&mut self) -> bool {
+        self.skip_labels();
+        if self.breakpoints.is_empty() || self.resume_pc == Some(self.pc) {
+            return false;
+        }
+        let breakpoints = std::mem::take(&mut self.breakpoints);
+        let hit = breakpoints.iter().any(|breakpoint| {
+            let index = match breakpoint.get_location() {
+                Location::Instruction(index) => Some(index),
+                Location::Label(name) => self.labels.get(&name).copied(),
+            };
+            // breakpoint at label is hit at first instruction after it
+            index.map(|index| self.first_executed(index)) == Some(self.pc)
+                && breakpoint
+                    .get_condition()
+                    .is_none_or(|condition| self.is_condition_met(&condition))
+        });
+        self.breakpoints = breakpoints;
+        hit
+    }
+
+    /// Checks condition of breakpoint, condition with operand vm can't read is not met
+    fn is_condition_met(&mut self, condition: &Condition) -> bool {
+        let (operand1, operand2) = condition.get_operands();
+        match (self.peek_operand(&operand1), self.peek_operand(&operand2)) {
+            (Ok(value1), Ok(value2)) => condition
+                .get_comparison()
+                .compare(self.profile.wrap(value1), self.profile.wrap(value2)),
+            _ => false,
+        }
+    }
+
+    /// Pauses vm, it continues from current instruction without hitting breakpoint at it
+    fn pause(&mut self, status: VmStatus) {
+        self.status = status;
+        self.resume_pc = Some(self.pc);
+    }
+
+    /// Executes single instruction, ignoring breakpoints
+    ///
+    /// Vm stays paused at next instruction, so breakpoint at it is not hit when vm continues
+    pub fn step(&mut self) -> StepResult {
+        let result = self.execute();
+        if result.is_continue() {
+            self.resume_pc = Some(self.pc);
+        }
+        result
+    }
+
+    /// Executes single instruction, or whole subroutine if instruction is CALL
+    ///
+    /// Subroutine ends when vm gets back to instruction after CALL with no more return
+    /// addresses saved than before it, breakpoints inside of subroutine pause vm
+    pub fn step_over(&mut self) -> StepResult {
+        self.skip_labels();
+        match self.get_next_instruction() {
+            Some(Instruction::Opcode(Opcode::CALL(_))) => {
+                let return_pc = self.first_executed(self.pc + 1);
+                let depth = self.get_call_depth();
+                self.run_until(|vm| vm.pc == return_pc && vm.get_call_depth() <= depth)
+            }
+            _ => self.step(),
+        }
+    }
+
+    /// Runs until vm gets to first instruction after label
+    ///
+    /// ### Returns
+    /// * UndefinedLabel error if label is not in program, nothing is executed and status
+    ///   of vm is not changed then
+    /// * otherwise the same as run_until_breakpoint, with Continue when label was reached
+    pub fn step_until_label(&mut self, label: &str) -> Result<StepResult, VmFault> {
+        let &target = self.labels.get(label).ok_or(VmFault::UndefinedLabel)?;
+        let target = self.first_executed(target);
+        Ok(self.run_until(|vm| vm.pc == target))
+    }
+
+    /// Runs program until it ends or vm pauses at breakpoint
+    ///
+    /// ### Returns
+    /// * Breakpoint if vm paused at breakpoint, it is in AtBreakpoint status then
+    /// * Watchpoint if instruction triggered watchpoint, it is in AtWatchpoint status then
+    /// * result of instruction that ended, blocked or faulted program otherwise
+    pub fn run_until_breakpoint(&mut self) -> StepResult {
+        self.run_until(|_| false)
+    }
+
+    /// Number of return addresses saved by CALLs on vm without stack
+    ///
+    /// Vm with stack keeps return addresses among other values there, so they aren't
+    /// counted, as subroutine may leave its result on stack
+    fn get_call_depth(&self) -> usize {
+        self.return_stack.len()
+    }
+
+    /// Runs instructions until target is reached, after executing at least one of them
+    ///
+    /// Blocked instruction stops vm, as nothing else can change its stack on this thread
+    ///
+    /// ### Returns
+    /// * Continue if target was reached, vm is Stopped then
+    /// * Breakpoint if vm paused at breakpoint
+    /// * Watchpoint if executed instruction triggered watchpoint
+    /// * result of instruction that ended, blocked or faulted program otherwise
+    fn run_until<F>(&mut self, target: F) -> StepResult
+    where
+        F: Fn(&VirtualMachine) -> bool,
+    {
+        self.status = VmStatus::Running;
+        let mut executed = false;
+        loop {
+            if executed && target(self) {
+                self.pause(VmStatus::Stopped);
+                return StepResult::Continue;
+            }
+            if self.is_at_breakpoint() {
+                self.pause(VmStatus::AtBreakpoint);
+                return StepResult::Breakpoint;
+            }
+            let result = self.execute();
+            executed = true;
+            VirtualMachine::delay(self.delay_ms);
+            match result {
+                StepResult::Continue if !self.watchpoint_hits.is_empty() => {
+                    self.status = VmStatus::AtWatchpoint;
+                    return StepResult::Watchpoint;
+                }
+                StepResult::Continue => {}
+                StepResult::Blocked => {
+                    self.status = VmStatus::Stopped;
+                    return result;
+                }
+                StepResult::Halted => {
+                    self.status = VmStatus::Finished;
+                    return result;
+                }
+                StepResult::Faulted(_) | StepResult::Breakpoint | StepResult::Watchpoint => {
+                    return result
+                }
+            }
+        }
+    }
+
+    /// Fetches and executes instruction
+    fn execute_instruction(&mut self) -> Result<StepResult, VmFault> {
+        let instruction = self.fetch();
+
+        match instruction {
+            Instruction::Opcode(opcode) => {
+                match opcode {
+                    // ------------ Control instructions ------------
+                    Opcode::HLT => {
+                        return Ok(StepResult::Halted);
                     }
                     Opcode::NOP => {}
                     // ------------ Moving operations ------------
-                    Opcode::MOV(operand1, operand2) => self.move_operand(operand1, operand2),
+                    Opcode::MOV(operand1, operand2) => self.move_operand(operand1, operand2)?,
 
                     // ------------  Arithmetic operations ------------
-                    Opcode::ADD(operand) => self.apply_operation(operand, |a, b| a.wrapping_add(b)),
-                    Opcode::SUB(operand) => self.apply_operation(operand, |a, b| a.wrapping_sub(b)),
-                    Opcode::MUL(operand) => self.apply_operation(operand, |a, b| a.wrapping_mul(b)),
-                    Opcode::DIV(operand) => self.apply_operation(operand, |a, b| a.wrapping_div(b)),
-                    Opcode::MOD(operand) => self.apply_operation(operand, |a, b| a.wrapping_rem(b)),
-                    Opcode::INC => self.acc = self.acc.wrapping_add(1),
-                    Opcode::DEC => self.acc = self.acc.wrapping_sub(1),
+                    Opcode::ADD(operand) => self.apply_arithmetic(operand, |a, b| a + b, true)?,
+                    Opcode::SUB(operand) => self.apply_arithmetic(operand, |a, b| a - b, true)?,
+                    Opcode::MUL(operand) => self.apply_arithmetic(operand, |a, b| a * b, true)?,
+                    Opcode::DIV(operand) => self.apply_division(operand, |a, b| a / b)?,
+                    Opcode::MOD(operand) => self.apply_division(operand, |a, b| a % b)?,
+                    Opcode::INC => {
+                        self.apply_arithmetic(Operand::IntegerValue(1), |a, b| a + b, true)?
+                    }
+                    Opcode::DEC => {
+                        self.apply_arithmetic(Operand::IntegerValue(1), |a, b| a - b, true)?
+                    }
+                    Opcode::NEG => {
+                        let value = self.read_operand(&Operand::ACC)?;
+                        let (result, flag) = self.calculate(0, value, |a, b| a - b, true);
+                        self.flag = flag;
+                        self.write_operand(&Operand::ACC, result)?
+                    }
+                    Opcode::ABS => {
+                        self.apply_arithmetic(Operand::IntegerValue(0), |a, _| a.abs(), false)?
+                    }
+                    Opcode::MIN(operand) => self.apply_operation(operand, |a, b| a.min(b))?,
+                    Opcode::MAX(operand) => self.apply_operation(operand, |a, b| a.max(b))?,
+                    Opcode::CLAMP(lower, upper) => self.clamp(lower, upper)?,
 
                     // ------------  Bit operations ------------
-                    Opcode::OR(operand) => self.apply_operation(operand, |a, b| a | b),
-                    Opcode::XOR(operand) => self.apply_operation(operand, |a, b| a ^ b),
-                    Opcode::AND(operand) => self.apply_operation(operand, |a, b| a & b),
-                    Opcode::NOT => self.acc = !self.acc,
-                    Opcode::SHL(operand) => self
-                        .apply_operation(operand, |a, b| a.wrapping_shl(b.try_into().unwrap_or(0))),
-                    Opcode::SHR(operand) => self
-                        .apply_operation(operand, |a, b| a.wrapping_shr(b.try_into().unwrap_or(0))),
+                    Opcode::OR(operand) => self.apply_operation(operand, |a, b| a | b)?,
+                    Opcode::XOR(operand) => self.apply_operation(operand, |a, b| a ^ b)?,
+                    Opcode::AND(operand) => self.apply_operation(operand, |a, b| a & b)?,
+                    Opcode::NOT => self.apply_operation(Operand::IntegerValue(0), |a, _| !a)?,
+                    Opcode::SHL(operand) => self.apply_shift(operand, Shift::Left)?,
+                    Opcode::SHR(operand) | Opcode::SAR(operand) => {
+                        self.apply_shift(operand, Shift::ArithmeticRight)?
+                    }
+                    Opcode::LSR(operand) => self.apply_shift(operand, Shift::LogicalRight)?,
+                    Opcode::ROL(operand) => self.apply_shift(operand, Shift::RotateLeft)?,
+                    Opcode::ROR(operand) => self.apply_shift(operand, Shift::RotateRight)?,
 
                     // ------------ Jumping logic ------------
-                    Opcode::CMP(operand1, operand2) => self.compare(operand1, operand2),
-                    Opcode::JE(name) => self.jump_to_label(&name, JMPCondition::EQ),
-                    Opcode::JL(name) => self.jump_to_label(&name, JMPCondition::LST),
-                    Opcode::JG(name) => self.jump_to_label(&name, JMPCondition::GRT),
-                    Opcode::JMP(name) => self.jump_to_label(&name, JMPCondition::NONE),
-
-                    Opcode::JNE(name) => self.jump_to_label(&name, JMPCondition::NEQ),
+                    Opcode::CMP(operand1, operand2) => self.compare(operand1, operand2)?,
+                    Opcode::JE(name) => self.jump_to_label(&name, JMPCondition::EQ)?,
+                    Opcode::JL(name) => self.jump_to_label(&name, JMPCondition::LST)?,
+                    Opcode::JG(name) => self.jump_to_label(&name, JMPCondition::GRT)?,
+                    Opcode::JMP(name) => self.jump_to_label(&name, JMPCondition::NONE)?,
+
+                    Opcode::JNE(name) => self.jump_to_label(&name, JMPCondition::NEQ)?,
+                    Opcode::JZ(name) => self.jump_to_label(&name, JMPCondition::ZERO)?,
+                    Opcode::JNZ(name) => self.jump_to_label(&name, JMPCondition::NZERO)?,
+                    Opcode::JC(name) => self.jump_to_label(&name, JMPCondition::CARRY)?,
+                    Opcode::JO(name) => self.jump_to_label(&name, JMPCondition::OVERFLOW)?,
+                    Opcode::JS(name) => self.jump_to_label(&name, JMPCondition::SIGN)?,
+                    Opcode::JA(name) => self.jump_to_label(&name, JMPCondition::ABOVE)?,
+                    Opcode::JAE(name) => self.jump_to_label(&name, JMPCondition::ABOVEEQ)?,
+                    Opcode::JB(name) => self.jump_to_label(&name, JMPCondition::BELOW)?,
+                    Opcode::JBE(name) => self.jump_to_label(&name, JMPCondition::BELOWEQ)?,
+                    // ------------ Subroutines ------------
+                    Opcode::CALL(name) => return self.call(&name),
+                    Opcode::RET => return self.return_from_call(),
                     // ------------ Stack operations ------------
-                    Opcode::PSH(operand) => self.push_to_stack(operand),
-                    Opcode::POP(operand) => self.pop_from_stack(operand),
+                    Opcode::PSH(operand) => return self.push_to_stack(operand),
+                    Opcode::POP(operand) => return self.pop_from_stack(operand),
                 }
             }
-            Instruction::Label(_, _) => {}
+            Instruction::Label(_, _, _) => {}
         }
 
-        true
+        Ok(StepResult::Continue)
     }
 
     /// Used to delay execution by sleeping current thread
@@ -568,15 +1597,11 @@ impl VirtualMachine {
         thread::sleep(Duration::from_millis(ms.into()));
     }
 
-    /// Runs all instructions in given program
+    /// Runs all instructions in given program, pausing at breakpoints
+    ///
+    /// Blocked instruction stops vm, as nothing else can change its stack on this thread
     pub fn run(&mut self) {
-        let mut running = true;
-        self.status = VmStatus::Running;
-        while running {
-            running = self.execute();
-            VirtualMachine::delay(self.delay_ms)
-        }
-        self.status = VmStatus::Finished;
+        self.run_until_breakpoint();
     }
 
     /// Starts vm on another thread
@@ -591,10 +1616,22 @@ impl VirtualMachine {
             while running {
                 {
                     let mut vm: std::sync::MutexGuard<'_, VirtualMachine> = vm.lock().unwrap();
-                    if vm.status == VmStatus::Running {
-                        running = vm.execute();
-
-                        delay = vm.get_delay();
+                    if vm.status == VmStatus::Running && vm.is_at_breakpoint() {
+                        // thread waits until vm is resumed
+                        vm.pause(VmStatus::AtBreakpoint);
+                        delay = vm.get_delay().max(BLOCKED_RETRY_MS);
+                    } else if vm.status == VmStatus::Running {
+                        let result = vm.execute();
+                        running = matches!(result, StepResult::Continue | StepResult::Blocked);
+
+                        delay = match result {
+                            StepResult::Blocked => vm.get_delay().max(BLOCKED_RETRY_MS),
+                            _ => vm.get_delay(),
+                        };
+                        if result == StepResult::Continue && !vm.watchpoint_hits.is_empty() {
+                            // thread waits until vm is resumed
+                            vm.status = VmStatus::AtWatchpoint;
+                        }
                     } else if vm.status == VmStatus::Finished {
                         break;
                     }
@@ -603,11 +1640,107 @@ impl VirtualMachine {
             }
             {
                 let mut vm = vm.lock().unwrap();
-                vm.status = VmStatus::Finished;
+                if !matches!(vm.status, VmStatus::Faulted(_)) {
+                    vm.status = VmStatus::Finished;
+                }
             }
         });
         handle
     }
+    /// Starts recording journals of vms together, so writes to ports connecting them
+    /// can be undone by step_back_together
+    ///
+    /// Write to port is reversible only if everything sharing its connection is one of vms
+    /// or connections given, so ports connected to Ram or to vm outside of group stay
+    /// irreversible. Ports connected later are irreversible too
+    ///
+    /// ### Arguments
+    ///
+    /// * 'vms' - vms connected with each other
+    /// * 'connections' - connections between vms, including their clones
+    /// * 'budget' - maximal number of bytes used by journal of each vm
+    pub fn journal_together(
+        vms: &[Arc<Mutex<VirtualMachine>>],
+        connections: &[&Connection],
+        budget: usize,
+    ) {
+        let mut group: Vec<&Arc<Mutex<VirtualMachine>>> = Vec::new();
+        for vm in vms {
+            if !group.iter().any(|other| Arc::ptr_eq(other, vm)) {
+                group.push(vm);
+            }
+        }
+
+        // connected ports of each vm, with shared value and number of its owners
+        let ports: Vec<Vec<(usize, *const Mutex<i32>, usize)>> = group
+            .iter()
+            .map(|vm| {
+                vm.lock()
+                    .unwrap()
+                    .p
+                    .iter()
+                    .enumerate()
+                    .filter_map(|(index, port)| match port {
+                        Port::Connected(value, _) => {
+                            Some((index, Arc::as_ptr(value), Arc::strong_count(value)))
+                        }
+                        Port::Disconnected(_) => None,
+                    })
+                    .collect()
+            })
+            .collect();
+
+        // owners of shared values that are in group
+        let mut owners: HashMap<*const Mutex<i32>, usize> = HashMap::new();
+        for &(_, value, _) in ports.iter().flatten() {
+            *owners.entry(value).or_default() += 1;
+        }
+        let mut counted: Vec<&Connection> = Vec::new();
+        for &connection in connections {
+            if counted.iter().any(|other| std::ptr::eq(*other, connection)) {
+                continue;
+            }
+            counted.push(connection);
+            if let Some(count) = owners.get_mut(&Arc::as_ptr(&connection.get())) {
+                *count += 1;
+            }
+        }
+
+        let clock = Arc::new(AtomicUsize::new(0));
+        for (vm, ports) in group.into_iter().zip(ports) {
+            let reversible = ports
+                .into_iter()
+                .filter(|(_, value, strong_count)| owners.get(value) == Some(strong_count))
+                .map(|(index, _, _)| index)
+                .collect();
+            vm.lock().unwrap().journal = Some(
+                Journal::new(budget)
+                    .with_clock(clock.clone())
+                    .with_reversible_ports(reversible),
+            );
+        }
+    }
+
+    /// Undoes the most recently executed instruction among vms journaled together
+    ///
+    /// ### Returns
+    /// * index of vm that was stepped back
+    /// * JournalEmpty if none of vms has recorded instruction left
+    pub fn step_back_together(vms: &[Arc<Mutex<VirtualMachine>>]) -> Result<usize, ReverseError> {
+        let latest = vms
+            .iter()
+            .enumerate()
+            .filter_map(|(index, vm)| {
+                let vm = vm.lock().unwrap();
+                let sequence = vm.journal.as_ref()?.get_last()?.get_sequence();
+                Some((sequence, index))
+            })
+            .max();
+        let (_, index) = latest.ok_or(ReverseError::JournalEmpty)?;
+        vms[index].lock().unwrap().step_back()?;
+        Ok(index)
+    }
+
     /// Stops vm running on another thread
     pub fn stop(vm: Arc<Mutex<VirtualMachine>>) {
         vm.lock().unwrap().status = VmStatus::Stopped;
@@ -660,6 +1793,14 @@ impl fmt::Display for VirtualMachine {
         writeln!(f, "FLG: {:?}", self.flag)?;
         write!(f, "Registers: {:?}\t", self.r)?;
         writeln!(f, "Ports: {:?}", self.p)?;
+        if !self.memory.is_empty() {
+            writeln!(f, "Memory: {:?}", self.memory)?;
+        }
+        writeln!(
+            f,
+            "Cycles: {}\tInstructions: {}",
+            self.cycles, self.instructions
+        )?;
 
         // Separate the vectors from the rest
         writeln!(f, "Program Instructions:")?;
@@ -685,6 +1826,7 @@ mod tests {
     use std::vec;
 
     use super::*;
+    use crate::vm::breakpoint::Comparison;
 
     #[test]
     fn test_create_vm() {
@@ -864,15 +2006,692 @@ mod tests {
         println!("_________________________________________");
         vm.execute();
         println!("{:?}", vm.flag);
-        assert_eq!(vm.flag, Flag::LESSER);
+        assert!(vm.flag.is_lesser());
 
         vm.execute();
         println!("{:?}", vm.flag);
-        assert_eq!(vm.flag, Flag::EQUAL);
+        assert!(vm.flag.is_equal());
 
         vm.execute();
         println!("{:?}", vm.flag);
-        assert_eq!(vm.flag, Flag::GREATER);
+        assert!(vm.flag.is_greater());
+    }
+
+    #[test]
+    fn test_vm_arithmetic_flags() {
+        let cases = [
+            // (acc, opcode, result, flags)
+            (i32::MAX, Opcode::INC, i32::MIN, "--ON"),
+            (
+                i32::MAX,
+                Opcode::ADD(Operand::IntegerValue(1)),
+                i32::MIN,
+                "--ON",
+            ),
+            (-1, Opcode::ADD(Operand::IntegerValue(1)), 0, "ZC--"),
+            (
+                i32::MIN,
+                Opcode::ADD(Operand::IntegerValue(-1)),
+                i32::MAX,
+                "-CO-",
+            ),
+            (0, Opcode::DEC, -1, "-C-N"),
+            (
+                i32::MIN,
+                Opcode::DIV(Operand::IntegerValue(-1)),
+                i32::MIN,
+                "--ON",
+            ),
+            (1 << 31, Opcode::SHL(Operand::IntegerValue(1)), 0, "ZC--"),
+            (3, Opcode::SHR(Operand::IntegerValue(1)), 1, "-C--"),
+            (-1, Opcode::NOT, 0, "Z---"),
+        ];
+        for (acc, opcode, result, flags) in cases {
+            let mut vm = VirtualMachine::new_with_program(vec![Instruction::new(opcode.clone())]);
+            vm.acc = acc;
+            vm.flag = Flag::from_result(0, true, true);
+            assert_eq!(vm.execute(), StepResult::Continue, "{:?}", opcode);
+            assert_eq!(vm.acc, result, "{:?}", opcode);
+            assert_eq!(vm.flag.to_string(), flags, "{:?}", opcode);
+        }
+        // MOV keeps flags
+        let mut vm = VirtualMachine::new_with_program(vec![Instruction::new(Opcode::MOV(
+            Operand::IntegerValue(5),
+            Operand::ACC,
+        ))]);
+        vm.execute();
+        assert_eq!(vm.flag, Flag::new());
+    }
+
+    #[test]
+    fn test_vm_extended_alu() {
+        let int = Operand::IntegerValue;
+        let cases = [
+            // (acc, opcode, result, flags)
+            (5, Opcode::NEG, -5, "-C-N"),
+            (0, Opcode::NEG, 0, "Z---"),
+            (i32::MIN, Opcode::NEG, i32::MIN, "-CON"),
+            (-7, Opcode::ABS, 7, "----"),
+            (i32::MIN, Opcode::ABS, i32::MIN, "--ON"),
+            (-8, Opcode::SHR(int(1)), -4, "---N"),
+            (-8, Opcode::LSR(int(1)), i32::MAX - 3, "----"),
+            (-8, Opcode::SAR(int(1)), -4, "---N"),
+            (-7, Opcode::SAR(int(1)), -4, "-C-N"),
+            (i32::MIN + 1, Opcode::ROL(int(1)), 3, "-C--"),
+            (3, Opcode::ROR(int(1)), i32::MIN + 1, "-C-N"),
+            (3, Opcode::ROR(int(-1)), 6, "----"),
+            (3, Opcode::ROL(int(32)), 3, "----"),
+            (-3, Opcode::MIN(int(2)), -3, "---N"),
+            (-3, Opcode::MAX(int(2)), 2, "----"),
+            (-3, Opcode::CLAMP(int(0), int(10)), 0, "Z---"),
+            (12, Opcode::CLAMP(int(0), int(10)), 10, "----"),
+            (5, Opcode::CLAMP(int(0), int(10)), 5, "----"),
+            (5, Opcode::CLAMP(int(10), int(0)), 0, "Z---"),
+        ];
+        for (acc, opcode, result, flags) in cases {
+            let mut vm = VirtualMachine::new_with_program(vec![Instruction::new(opcode.clone())]);
+            vm.acc = acc;
+            assert_eq!(vm.execute(), StepResult::Continue, "{:?}", opcode);
+            assert_eq!(vm.acc, result, "{:?}", opcode);
+            assert_eq!(vm.flag.to_string(), flags, "{:?}", opcode);
+        }
+
+        // rotations and logical shifts work on bits of word
+        let profile = MachineProfile::new().with_word_size(8);
+        let cases = [
+            (Opcode::ROL(int(1)), 0b0000_0011),
+            (Opcode::ROR(int(2)), 0b0110_0000),
+            (Opcode::SHR(int(1)), -64),
+            (Opcode::LSR(int(1)), 0b0100_0000),
+            (Opcode::SAR(int(1)), -64),
+            (Opcode::MAX(int(200)), -56),
+        ];
+        for (opcode, result) in cases {
+            let mut vm = VirtualMachine::new_with_profile(profile);
+            vm.load_program(vec![Instruction::new(opcode.clone())]);
+            vm.acc = -127;
+            vm.execute();
+            assert_eq!(vm.acc, profile.wrap(result), "{:?}", opcode);
+        }
+
+        // shifts by word size or more move all bits out of word
+        let cases = [
+            (Opcode::SHL(int(8)), 0, "ZC--"),
+            (Opcode::SHL(int(9)), 0, "Z---"),
+            (Opcode::SHL(int(40)), 0, "Z---"),
+            (Opcode::LSR(int(8)), 0, "ZC--"),
+            (Opcode::LSR(int(40)), 0, "Z---"),
+            (Opcode::SAR(int(8)), -1, "-C-N"),
+            (Opcode::SAR(int(40)), -1, "-C-N"),
+        ];
+        for (opcode, result, flags) in cases {
+            let mut vm = VirtualMachine::new_with_profile(profile);
+            vm.load_program(vec![Instruction::new(opcode.clone())]);
+            vm.acc = -127;
+            vm.execute();
+            assert_eq!(vm.acc, result, "{:?}", opcode);
+            assert_eq!(vm.flag.to_string(), flags, "{:?}", opcode);
+        }
+    }
+
+    #[test]
+    fn test_vm_cmp_does_not_overflow() {
+        let program = vec![
+            Instruction::new(Opcode::CMP(
+                Operand::IntegerValue(i32::MIN),
+                Operand::IntegerValue(1),
+            )),
+            Instruction::new(Opcode::CMP(
+                Operand::IntegerValue(i32::MAX),
+                Operand::IntegerValue(-1),
+            )),
+            Instruction::new(Opcode::CMP(
+                Operand::IntegerValue(-1),
+                Operand::IntegerValue(1),
+            )),
+        ];
+        let mut vm = VirtualMachine::new_with_program(program);
+        vm.acc = 7;
+
+        vm.execute();
+        assert!(vm.flag.is_lesser() && vm.flag.is_overflow());
+        vm.execute();
+        assert!(vm.flag.is_greater() && vm.flag.is_overflow());
+        vm.execute();
+        // -1 is lesser than 1 as signed number, but as unsigned it is greater, so no borrow
+        assert!(vm.flag.is_lesser() && !vm.flag.is_carry());
+        assert_eq!(vm.acc, 7);
+    }
+
+    #[test]
+    fn test_vm_flags_follow_word_size() {
+        let profile = MachineProfile::new().with_word_size(8);
+        let program = vec![
+            Instruction::new(Opcode::MOV(Operand::IntegerValue(127), Operand::ACC)),
+            Instruction::new(Opcode::INC),
+            Instruction::new(Opcode::ADD(Operand::IntegerValue(128))),
+            Instruction::new(Opcode::CMP(
+                Operand::IntegerValue(-128),
+                Operand::IntegerValue(1),
+            )),
+        ];
+        let mut vm = VirtualMachine::new_with_profile(profile);
+        vm.load_program(program);
+
+        vm.execute();
+        vm.execute();
+        assert_eq!(vm.acc, -128);
+        assert_eq!(vm.flag.to_string(), "--ON");
+        vm.execute();
+        assert_eq!(vm.acc, 0);
+        assert_eq!(vm.flag.to_string(), "ZCO-");
+        vm.execute();
+        assert!(vm.flag.is_lesser());
+    }
+
+    #[test]
+    fn test_vm_flag_jumps() {
+        let end = || "end".to_string();
+        // jumps after adding 1 to acc
+        let jumps = [
+            (Opcode::JZ(end()), -1, true),
+            (Opcode::JZ(end()), 0, false),
+            (Opcode::JNZ(end()), 0, true),
+            (Opcode::JNZ(end()), -1, false),
+            (Opcode::JC(end()), -1, true),
+            (Opcode::JC(end()), 0, false),
+            (Opcode::JO(end()), i32::MAX, true),
+            (Opcode::JO(end()), 0, false),
+            (Opcode::JS(end()), -5, true),
+            (Opcode::JS(end()), 5, false),
+        ];
+        for (jump, acc, taken) in jumps {
+            let program = vec![
+                Instruction::new(Opcode::ADD(Operand::IntegerValue(1))),
+                Instruction::new(jump.clone()),
+                Instruction::new(Opcode::NOP),
+                Instruction::new_label(end(), 0, 3),
+            ];
+            let mut vm = VirtualMachine::new_with_program(program);
+            vm.acc = acc;
+            vm.execute();
+            vm.execute();
+            let expected_pc = if taken { 4 } else { 2 };
+            assert_eq!(vm.pc, expected_pc, "{:?} after {} + 1", jump, acc);
+        }
+    }
+
+    #[test]
+    fn test_vm_unsigned_jumps() {
+        let end = || "end".to_string();
+        // jumps after comparing given operands
+        let jumps = [
+            (Opcode::JA(end()), -1, 1, true),
+            (Opcode::JA(end()), 1, -1, false),
+            (Opcode::JA(end()), 7, 7, false),
+            (Opcode::JAE(end()), 7, 7, true),
+            (Opcode::JAE(end()), 0, i32::MIN, false),
+            (Opcode::JB(end()), 1, -1, true),
+            (Opcode::JB(end()), 7, 7, false),
+            (Opcode::JBE(end()), 7, 7, true),
+            (Opcode::JBE(end()), i32::MIN, i32::MAX, false),
+        ];
+        for (jump, value1, value2, taken) in jumps {
+            let program = vec![
+                Instruction::new(Opcode::CMP(
+                    Operand::IntegerValue(value1),
+                    Operand::IntegerValue(value2),
+                )),
+                Instruction::new(jump.clone()),
+                Instruction::new(Opcode::NOP),
+                Instruction::new_label(end(), 0, 3),
+            ];
+            let mut vm = VirtualMachine::new_with_program(program);
+            vm.execute();
+            vm.execute();
+            let expected_pc = if taken { 4 } else { 2 };
+            assert_eq!(
+                vm.pc, expected_pc,
+                "{:?} after CMP {} {}",
+                jump, value1, value2
+            );
+        }
+
+        // with 8 bit words 200 is stored as -56, it is still above 100
+        let profile = MachineProfile::new().with_word_size(8);
+        let mut vm = VirtualMachine::new_with_profile(profile);
+        vm.load_program(vec![
+            Instruction::new(Opcode::MOV(Operand::IntegerValue(200), Operand::ACC)),
+            Instruction::new(Opcode::CMP(Operand::ACC, Operand::IntegerValue(100))),
+        ]);
+        vm.run();
+        assert!(vm.flag.is_above() && vm.flag.is_lesser());
+    }
+
+    #[test]
+    fn test_vm_memory_operands() {
+        let profile = MachineProfile::new().with_memory(8).with_word_size(16);
+        let program = vec![
+            Instruction::new(Opcode::MOV(
+                Operand::IntegerValue(2),
+                Operand::GeneralRegister(1),
+            )),
+            Instruction::new(Opcode::MOV(
+                Operand::IntegerValue(70000),
+                Operand::Memory(1, 3),
+            )),
+            Instruction::new(Opcode::MOV(Operand::Memory(1, 0), Operand::ACC)),
+            Instruction::new(Opcode::ADD(Operand::Memory(1, -2))),
+            Instruction::new(Opcode::MOV(Operand::Memory(1, 3), Operand::Memory(1, 5))),
+            Instruction::new(Opcode::HLT),
+        ];
+        let mut vm = VirtualMachine::new_with_profile(profile);
+        vm.load_program(program);
+        vm.write_memory(0, &[10, 20, 30]).unwrap();
+        vm.run();
+
+        assert_eq!(vm.get_acc(), 40);
+        assert_eq!(vm.get_memory(), vec![10, 20, 30, 0, 0, 4464, 0, 4464]);
+        assert_eq!(vm.get_status(), VmStatus::Finished);
+
+        assert_eq!(
+            vm.write_memory(6, &[1, 2, 3]),
+            Err(VmFault::InvalidMemoryAddress(8))
+        );
+        assert_eq!(vm.get_memory()[6], 0);
+    }
+
+    #[test]
+    fn test_vm_memory_faults() {
+        let faulting_operands = [
+            (Operand::Memory(0, 4), VmFault::InvalidMemoryAddress(4)),
+            (Operand::Memory(0, -1), VmFault::InvalidMemoryAddress(-1)),
+            (Operand::Memory(7, 0), VmFault::IllegalOperand),
+        ];
+        for (operand, fault) in faulting_operands {
+            let profile = MachineProfile::new().with_memory(4);
+            let mut vm = VirtualMachine::new_with_profile(profile);
+            vm.load_program(vec![Instruction::new(Opcode::MOV(
+                operand.clone(),
+                Operand::ACC,
+            ))]);
+            assert_eq!(vm.execute(), StepResult::Faulted(fault), "{}", operand);
+            assert_eq!(vm.get_pc(), 0);
+        }
+
+        // vm without memory faults on every memory operand
+        let mut vm = VirtualMachine::new_with_program(vec![Instruction::new(Opcode::MOV(
+            Operand::ACC,
+            Operand::Memory(0, 0),
+        ))]);
+        assert_eq!(
+            vm.execute(),
+            StepResult::Faulted(VmFault::InvalidMemoryAddress(0))
+        );
+    }
+
+    #[test]
+    fn test_vm_breakpoints() {
+        let program = vec![
+            Instruction::new(Opcode::INC),
+            Instruction::new_label("loop".to_string(), 1, 1),
+            Instruction::new(Opcode::INC),
+            Instruction::new(Opcode::CMP(Operand::ACC, Operand::IntegerValue(6))),
+            Instruction::new(Opcode::JL("loop".to_string())),
+            Instruction::new(Opcode::HLT),
+        ];
+        let mut vm = VirtualMachine::new_with_program(program);
+        vm.add_breakpoint(Breakpoint::at_instruction(0));
+        vm.add_breakpoint(Breakpoint::at_label("loop").with_condition(Condition::new(
+            Operand::ACC,
+            Comparison::Equal,
+            Operand::IntegerValue(3),
+        )));
+        vm.add_breakpoint(Breakpoint::at_instruction(0));
+        assert_eq!(vm.get_breakpoints().len(), 2);
+
+        // breakpoint at first instruction is hit before anything is executed
+        assert_eq!(vm.run_until_breakpoint(), StepResult::Breakpoint);
+        assert_eq!((vm.get_pc(), vm.get_acc()), (0, 0));
+        assert_eq!(vm.get_status(), VmStatus::AtBreakpoint);
+
+        // continuing doesn't hit the same breakpoint again
+        assert_eq!(vm.run_until_breakpoint(), StepResult::Breakpoint);
+        assert_eq!((vm.get_pc(), vm.get_acc()), (2, 3));
+
+        assert!(vm.remove_breakpoint(&Breakpoint::at_instruction(0)));
+        assert!(!vm.remove_breakpoint(&Breakpoint::at_instruction(0)));
+        vm.run();
+        assert_eq!(vm.get_acc(), 6);
+        assert_eq!(vm.get_status(), VmStatus::Finished);
+    }
+
+    #[test]
+    fn test_vm_stepping() {
+        let program = vec![
+            Instruction::new(Opcode::CALL("double".to_string())),
+            Instruction::new(Opcode::CALL("double".to_string())),
+            Instruction::new_label("done".to_string(), 2, 2),
+            Instruction::new(Opcode::HLT),
+            Instruction::new_label("double".to_string(), 4, 4),
+            Instruction::new(Opcode::INC),
+            Instruction::new(Opcode::ADD(Operand::ACC)),
+            Instruction::new(Opcode::RET),
+        ];
+        let mut vm = VirtualMachine::new_with_program(program);
+        vm.add_breakpoint(Breakpoint::at_instruction(1));
+
+        // stepping ignores breakpoints and enters subroutine
+        assert_eq!(vm.step(), StepResult::Continue);
+        assert_eq!(vm.get_pc(), 5);
+        assert_eq!(vm.step_over(), StepResult::Continue);
+        assert_eq!((vm.get_pc(), vm.get_acc()), (6, 1));
+
+        // subroutine finishes at breakpoint after CALL
+        assert_eq!(vm.run_until_breakpoint(), StepResult::Breakpoint);
+        assert_eq!((vm.get_pc(), vm.get_acc()), (1, 2));
+
+        // step over whole subroutine, stopping right after it, past label
+        assert_eq!(vm.step_over(), StepResult::Continue);
+        assert_eq!((vm.get_pc(), vm.get_acc()), (3, 6));
+        assert_eq!(vm.get_status(), VmStatus::Stopped);
+
+        assert_eq!(vm.step_until_label("missing"), Err(VmFault::UndefinedLabel));
+        assert_eq!((vm.get_pc(), vm.get_status()), (3, VmStatus::Stopped));
+        assert_eq!(vm.step_until_label("done"), Ok(StepResult::Halted));
+        assert_eq!(vm.get_status(), VmStatus::Finished);
+    }
+
+    #[test]
+    fn test_vm_step_over_subroutine_leaving_result_on_stack() {
+        let program = vec![
+            Instruction::new(Opcode::CALL("answer".to_string())),
+            Instruction::new(Opcode::POP(Operand::ACC)),
+            Instruction::new(Opcode::HLT),
+            Instruction::new_label("answer".to_string(), 3, 3),
+            Instruction::new(Opcode::POP(Operand::GeneralRegister(0))),
+            Instruction::new(Opcode::PSH(Operand::IntegerValue(42))),
+            Instruction::new(Opcode::PSH(Operand::GeneralRegister(0))),
+            Instruction::new(Opcode::RET),
+        ];
+        let mut vm = VirtualMachine::new_with_program(program).with_stack(4);
+
+        // subroutine returns with one more value on stack than before CALL
+        assert_eq!(vm.step_over(), StepResult::Continue);
+        assert_eq!(vm.get_pc(), 1);
+        assert_eq!(vm.get_stack(), vec![42]);
+        assert_eq!(vm.get_status(), VmStatus::Stopped);
+    }
+
+    #[test]
+    fn test_vm_watchpoints() {
+        let program = vec![
+            Instruction::new(Opcode::MOV(
+                Operand::IntegerValue(5),
+                Operand::PortRegister(1),
+            )),
+            Instruction::new(Opcode::MOV(
+                Operand::IntegerValue(5),
+                Operand::PortRegister(1),
+            )),
+            Instruction::new(Opcode::MOV(Operand::PortRegister(1), Operand::ACC)),
+            Instruction::new(Opcode::HLT),
+        ];
+        let port = Watched::Operand(Operand::PortRegister(1));
+        let mut vm = VirtualMachine::new_with_program(program.clone());
+        vm.add_watchpoint(Watchpoint::new(port.clone(), Access::Change));
+        vm.add_watchpoint(Watchpoint::new(port.clone(), Access::Change));
+        assert_eq!(vm.get_watchpoints().len(), 1);
+
+        // vm pauses after instruction that changed port
+        assert_eq!(vm.run_until_breakpoint(), StepResult::Watchpoint);
+        assert_eq!(vm.get_status(), VmStatus::AtWatchpoint);
+        assert_eq!(vm.get_pc(), 1);
+        let hits = vm.get_watchpoint_hits();
+        assert_eq!(hits.len(), 1);
+        assert_eq!(hits[0].get_pc(), 0);
+        assert_eq!(hits[0].get_instruction(), program[0]);
+        assert_eq!(hits[0].get_access(), Access::Write);
+        assert_eq!(hits[0].get_values(), (0, 5));
+
+        // writing the same value again is not a change
+        vm.run();
+        assert_eq!(vm.get_status(), VmStatus::Finished);
+        assert!(vm.get_watchpoint_hits().is_empty());
+
+        let mut vm = VirtualMachine::new_with_program(program);
+        vm.add_watchpoint(Watchpoint::new(port.clone(), Access::Read));
+        assert_eq!(vm.run_until_breakpoint(), StepResult::Watchpoint);
+        assert_eq!(vm.get_pc(), 3);
+        assert_eq!(vm.get_watchpoint_hits()[0].get_values(), (5, 5));
+
+        // breakpoint conditions don't read operands
+        let mut vm = VirtualMachine::new_with_program(vec![Instruction::new(Opcode::HLT)]);
+        vm.add_watchpoint(Watchpoint::new(port, Access::Read));
+        vm.add_breakpoint(Breakpoint::at_instruction(0).with_condition(Condition::new(
+            Operand::PortRegister(1),
+            Comparison::Equal,
+            Operand::IntegerValue(1),
+        )));
+        assert_eq!(vm.run_until_breakpoint(), StepResult::Halted);
+    }
+
+    #[test]
+    fn test_vm_watchpoints_on_acc_and_pc() {
+        let program = vec![
+            Instruction::new(Opcode::MOV(Operand::IntegerValue(3), Operand::ACC)),
+            Instruction::new(Opcode::ADD(Operand::IntegerValue(2))),
+            Instruction::new(Opcode::SHL(Operand::IntegerValue(1))),
+            Instruction::new(Opcode::HLT),
+        ];
+
+        // arithmetic and shifts read acc without naming it as operand
+        let mut vm = VirtualMachine::new_with_program(program.clone());
+        vm.add_watchpoint(Watchpoint::new(
+            Watched::Operand(Operand::ACC),
+            Access::Read,
+        ));
+        assert_eq!(vm.run_until_breakpoint(), StepResult::Watchpoint);
+        let hits = vm.get_watchpoint_hits();
+        assert_eq!(hits.len(), 1);
+        assert_eq!((hits[0].get_pc(), hits[0].get_values()), (1, (3, 3)));
+        assert_eq!(vm.run_until_breakpoint(), StepResult::Watchpoint);
+        assert_eq!(vm.get_watchpoint_hits()[0].get_values(), (5, 5));
+
+        // sequential execution writes pc too
+        let mut vm = VirtualMachine::new_with_program(program);
+        vm.add_watchpoint(
+            Watchpoint::new(Watched::Operand(Operand::PC), Access::Change)
+                .with_value(Comparison::Equal, 2),
+        );
+        assert_eq!(vm.run_until_breakpoint(), StepResult::Watchpoint);
+        let hits = vm.get_watchpoint_hits();
+        assert_eq!(hits.len(), 1);
+        assert_eq!((hits[0].get_pc(), hits[0].get_values()), (1, (1, 2)));
+        assert_eq!(vm.get_acc(), 5);
+    }
+
+    #[test]
+    fn test_vm_stack_depth_watchpoint() {
+        let program = vec![
+            Instruction::new(Opcode::PSH(Operand::IntegerValue(1))),
+            Instruction::new(Opcode::PSH(Operand::IntegerValue(2))),
+            Instruction::new(Opcode::POP(Operand::GeneralRegister(0))),
+            Instruction::new(Opcode::POP(Operand::GeneralRegister(1))),
+            Instruction::new(Opcode::HLT),
+        ];
+        let mut vm = VirtualMachine::new_with_program(program).with_stack(4);
+        let depth = Watchpoint::new(Watched::StackDepth, Access::Write)
+            .with_value(Comparison::GreaterOrEqual, 2);
+        vm.add_watchpoint(depth.clone());
+        vm.add_watchpoint(
+            Watchpoint::new(Watched::StackDepth, Access::Change).with_value(Comparison::Equal, 0),
+        );
+
+        assert_eq!(vm.run_until_breakpoint(), StepResult::Watchpoint);
+        assert_eq!(vm.get_pc(), 2);
+        let hit = &vm.get_watchpoint_hits()[0];
+        assert_eq!((hit.get_pc(), hit.get_values()), (1, (1, 2)));
+        assert_eq!(hit.get_watchpoint(), depth);
+
+        // stack is emptied by last POP
+        assert!(vm.remove_watchpoint(&depth));
+        assert!(!vm.remove_watchpoint(&depth));
+        assert_eq!(vm.run_until_breakpoint(), StepResult::Watchpoint);
+        assert_eq!(vm.get_pc(), 4);
+        assert_eq!(vm.get_registers()[..2], [2, 1]);
+
+        vm.clear_watchpoints();
+        vm.run();
+        assert_eq!(vm.get_status(), VmStatus::Finished);
+    }
+
+    #[test]
+    fn test_vm_step_back() {
+        let program = vec![
+            Instruction::new(Opcode::MOV(
+                Operand::IntegerValue(7),
+                Operand::GeneralRegister(0),
+            )),
+            Instruction::new(Opcode::MOV(
+                Operand::IntegerValue(3),
+                Operand::Memory(0, -5),
+            )),
+            Instruction::new(Opcode::PSH(Operand::GeneralRegister(0))),
+            Instruction::new(Opcode::CALL("sub".to_string())),
+            Instruction::new(Opcode::POP(Operand::GeneralRegister(1))),
+            Instruction::new(Opcode::HLT),
+            Instruction::new_label("sub".to_string(), 6, 6),
+            Instruction::new(Opcode::SUB(Operand::IntegerValue(10))),
+            Instruction::new(Opcode::RET),
+        ];
+        let profile = MachineProfile::new().with_stack(4).with_memory(4);
+        let mut vm = VirtualMachine::new_with_profile(profile).with_journal(4096);
+        vm.load_program(program);
+        vm.run();
+        assert_eq!(vm.get_journal().map(Journal::len), Some(8));
+        assert_eq!((vm.get_acc(), vm.get_registers()[1]), (-10, 7));
+
+        assert_eq!(vm.step_back(), Ok(()));
+        assert_eq!((vm.get_pc(), vm.get_status()), (5, VmStatus::Stopped));
+
+        // back inside subroutine, before SUB
+        assert_eq!(vm.run_back_to(7), Ok(3));
+        assert_eq!((vm.get_acc(), vm.get_flag()), (0, Flag::new()));
+        assert_eq!(vm.get_stack(), vec![7, 4]);
+        assert_eq!(vm.get_registers()[1], 0);
+
+        assert_eq!(vm.run_back_to(2), Ok(2));
+        assert!(vm.get_stack().is_empty());
+        assert_eq!(vm.get_memory()[2], 3);
+        assert_eq!(vm.step_back(), Ok(()));
+        assert_eq!(vm.step_back(), Ok(()));
+        assert_eq!((vm.get_pc(), vm.get_registers()[0]), (0, 0));
+        assert_eq!(vm.get_memory(), vec![0; 4]);
+        assert_eq!(vm.step_back(), Err(ReverseError::JournalEmpty));
+        assert_eq!(vm.run_back_to(5), Err(ReverseError::NotInJournal(5)));
+
+        // program replays the same way after being undone
+        vm.run();
+        assert_eq!((vm.get_acc(), vm.get_registers()[1]), (-10, 7));
+        assert_eq!(vm.get_status(), VmStatus::Finished);
+    }
+
+    #[test]
+    fn test_vm_step_back_limits() {
+        let program = vec![
+            Instruction::new(Opcode::MOV(
+                Operand::IntegerValue(1),
+                Operand::PortRegister(0),
+            )),
+            Instruction::new(Opcode::POP(Operand::ACC)),
+        ];
+        let mut vm = VirtualMachine::new_with_program(program.clone());
+        vm.step();
+        assert_eq!(vm.step_back(), Err(ReverseError::JournalDisabled));
+
+        // faulted instruction is undone too
+        let mut vm = VirtualMachine::new_with_program(program.clone()).with_journal(4096);
+        vm.run();
+        assert_eq!(vm.get_status(), VmStatus::Faulted(VmFault::StackNotPresent));
+        assert_eq!(vm.step_back(), Ok(()));
+        assert_eq!((vm.get_pc(), vm.get_status()), (1, VmStatus::Stopped));
+
+        // journal without budget keeps nothing
+        let mut vm = VirtualMachine::new_with_program(program.clone()).with_journal(0);
+        vm.step();
+        assert_eq!(vm.step_back(), Err(ReverseError::JournalEmpty));
+
+        // other vms may have already read connected port
+        let mut vm = VirtualMachine::new_with_program(program).with_journal(4096);
+        vm.connect(0, &mut Connection::new());
+        vm.step();
+        assert_eq!(vm.step_back(), Err(ReverseError::IrreversiblePortWrite(0)));
+        assert_eq!(
+            vm.run_back_to(0),
+            Err(ReverseError::IrreversiblePortWrite(0))
+        );
+        assert_eq!(vm.get_ports_values()[0], 1);
+
+        // journal set from outside may refer to registers vm doesn't have
+        let mut journal = Journal::new(4096);
+        journal.begin(0, 0, Flag::new());
+        journal.record(Change::Register(9, 1));
+        journal.record(Change::Port {
+            index: 9,
+            old_value: 1,
+            connected: false,
+        });
+        journal.record(Change::Memory(100, 1));
+        journal.commit();
+        let mut vm = VirtualMachine::new();
+        vm.set_journal(Some(journal));
+        assert_eq!(vm.step_back(), Ok(()));
+        assert_eq!(vm.get_registers(), vec![0; 4]);
+    }
+
+    #[test]
+    fn test_vm_cycle_counting() {
+        let program = vec![
+            Instruction::new_label("start".to_string(), 0, 0),
+            Instruction::new(Opcode::ADD(Operand::IntegerValue(6))),
+            Instruction::new_label("multiply".to_string(), 2, 2),
+            Instruction::new_label("again".to_string(), 3, 3),
+            Instruction::new(Opcode::MUL(Operand::IntegerValue(4))),
+            Instruction::new(Opcode::DIV(Operand::IntegerValue(3))),
+            Instruction::new(Opcode::HLT),
+        ];
+        let costs = CostTable::new()
+            .with_cost("MUL", 3)
+            .and_then(|costs| costs.with_cost("DIV", 8))
+            .unwrap();
+        let mut vm = VirtualMachine::new_with_program(program)
+            .with_costs(costs)
+            .with_journal(4096);
+
+        // labels don't take execute call
+        assert_eq!(vm.execute(), StepResult::Continue);
+        assert_eq!((vm.get_pc(), vm.get_acc()), (4, 6));
+        assert_eq!((vm.get_cycles(), vm.get_instructions()), (1, 1));
+
+        vm.run();
+        assert_eq!(vm.get_acc(), 8);
+        assert_eq!((vm.get_cycles(), vm.get_instructions()), (13, 4));
+        let state = vm.get_state_for_display();
+        assert_eq!((state.7, state.8), (13, 4));
+
+        // counters only grow, even when vm steps back
+        assert_eq!(vm.run_back_to(4), Ok(3));
+        vm.clear_registers();
+        assert_eq!((vm.get_cycles(), vm.get_instructions()), (13, 4));
+
+        let serialized = serde_json::to_value(&vm).unwrap();
+        assert_eq!(serialized["cycles"], 13);
+        assert_eq!(serialized["instructions"], 4);
+        assert_eq!(serialized["costs"]["costs"]["DIV"], 8);
+
+        vm.reset_counters();
+        assert_eq!((vm.get_cycles(), vm.get_instructions()), (0, 0));
     }
 
     #[test]
@@ -937,7 +2756,7 @@ mod tests {
                 Operand::IntegerValue(0),
                 Operand::GeneralRegister(2),
             )),
-            Instruction::new_label("loop".to_string(), 3),
+            Instruction::new_label("loop".to_string(), 3, 3),
             Instruction::new(Opcode::MOV(Operand::GeneralRegister(0), Operand::ACC)),
             Instruction::new(Opcode::SUB(Operand::GeneralRegister(1))),
             Instruction::new(Opcode::MOV(Operand::ACC, Operand::GeneralRegister(0))),
@@ -992,4 +2811,276 @@ mod tests {
         assert_eq!(vm.get_acc(), 20);
         //println!("{}", vm);
     }
+
+    #[test]
+    fn test_vm_with_profile() {
+        let profile = MachineProfile::new()
+            .with_registers(2)
+            .with_ports(8)
+            .with_word_size(8);
+        let program = vec![
+            Instruction::new(Opcode::MOV(Operand::IntegerValue(127), Operand::ACC)),
+            Instruction::new(Opcode::INC),
+            Instruction::new(Opcode::MOV(Operand::ACC, Operand::PortRegister(7))),
+            Instruction::new(Opcode::MOV(
+                Operand::IntegerValue(300),
+                Operand::GeneralRegister(1),
+            )),
+            Instruction::new(Opcode::HLT),
+        ];
+
+        let mut vm = VirtualMachine::new_with_profile(profile);
+        vm.load_program(program);
+        vm.run();
+
+        // values wrap to 8 bit words
+        assert_eq!(vm.get_acc(), -128);
+        assert_eq!(vm.get_registers(), vec![0, 44]);
+        assert_eq!(vm.get_ports_values(), vec![0, 0, 0, 0, 0, 0, 0, -128]);
+        assert_eq!(vm.get_profile(), profile);
+    }
+
+    #[test]
+    fn test_vm_faults() {
+        let faulting_instructions = [
+            (Opcode::DIV(Operand::IntegerValue(0)), VmFault::DivideByZero),
+            (
+                Opcode::MOD(Operand::GeneralRegister(0)),
+                VmFault::DivideByZero,
+            ),
+            (
+                Opcode::MOV(Operand::IntegerValue(-1), Operand::PC),
+                VmFault::InvalidJumpTarget(-1),
+            ),
+            (
+                Opcode::MOV(Operand::IntegerValue(5), Operand::PC),
+                VmFault::InvalidJumpTarget(5),
+            ),
+            (
+                Opcode::MOV(Operand::ACC, Operand::IntegerValue(1)),
+                VmFault::IllegalOperand,
+            ),
+            (
+                Opcode::ADD(Operand::GeneralRegister(9)),
+                VmFault::IllegalOperand,
+            ),
+            (Opcode::JMP("missing".to_string()), VmFault::UndefinedLabel),
+            (Opcode::PSH(Operand::ACC), VmFault::StackNotPresent),
+        ];
+
+        for (opcode, fault) in faulting_instructions {
+            let program = vec![
+                Instruction::new(Opcode::INC),
+                Instruction::new(opcode),
+                Instruction::new(Opcode::HLT),
+            ];
+            let mut vm = VirtualMachine::new_with_program(program);
+
+            assert_eq!(vm.execute(), StepResult::Continue);
+            assert_eq!(vm.execute(), StepResult::Faulted(fault));
+            // pc points at instruction that caused fault
+            assert_eq!(vm.get_pc(), 1);
+            assert_eq!(vm.get_status(), VmStatus::Faulted(fault));
+        }
+    }
+
+    #[test]
+    fn test_vm_run_stops_at_fault() {
+        let program = vec![
+            Instruction::new(Opcode::MOV(Operand::IntegerValue(3), Operand::PC)),
+            Instruction::new(Opcode::INC),
+            Instruction::new(Opcode::HLT),
+            Instruction::new(Opcode::DIV(Operand::IntegerValue(0))),
+        ];
+        let mut vm = VirtualMachine::new_with_program(program);
+
+        vm.run();
+
+        assert_eq!(vm.get_status(), VmStatus::Faulted(VmFault::DivideByZero));
+        assert_eq!(vm.get_pc(), 3);
+        assert_eq!(vm.get_acc(), 0);
+    }
+
+    /// Pushes 1, 2, 3 on stack of depth 2, then pops three values to r0, r1, r2
+    fn run_with_stack_policy(policy: StackPolicy) -> VirtualMachine {
+        let mut program: Vec<Instruction> = (1..=3)
+            .map(|value| Instruction::new(Opcode::PSH(Operand::IntegerValue(value))))
+            .collect();
+        program.extend(
+            (0..3).map(|index| Instruction::new(Opcode::POP(Operand::GeneralRegister(index)))),
+        );
+        let mut vm = VirtualMachine::new_with_program(program)
+            .with_stack(2)
+            .with_stack_policy(policy);
+        vm.run();
+        vm
+    }
+
+    #[test]
+    fn test_stack_policy_overwrite_top() {
+        let vm = run_with_stack_policy(StackPolicy::OverwriteTop);
+
+        assert_eq!(vm.get_status(), VmStatus::Finished);
+        assert_eq!(vm.get_registers(), vec![3, 1, 0, 0]);
+    }
+
+    #[test]
+    fn test_stack_policy_drop_oldest() {
+        let vm = run_with_stack_policy(StackPolicy::DropOldest);
+
+        assert_eq!(vm.get_status(), VmStatus::Finished);
+        assert_eq!(vm.get_registers(), vec![3, 2, 0, 0]);
+    }
+
+    #[test]
+    fn test_stack_policy_fault() {
+        let vm = run_with_stack_policy(StackPolicy::Fault);
+
+        assert_eq!(vm.get_status(), VmStatus::Faulted(VmFault::StackOverflow));
+        assert_eq!(vm.get_pc(), 2);
+        assert_eq!(vm.get_stack(), vec![1, 2]);
+
+        let program = vec![Instruction::new(Opcode::POP(Operand::ACC))];
+        let mut vm = VirtualMachine::new_with_program(program)
+            .with_stack(2)
+            .with_stack_policy(StackPolicy::Fault);
+        vm.run();
+        assert_eq!(vm.get_status(), VmStatus::Faulted(VmFault::StackUnderflow));
+    }
+
+    #[test]
+    fn test_stack_policy_block() {
+        let mut vm = run_with_stack_policy(StackPolicy::Block);
+
+        // vm stops at PSH waiting for space on stack
+        assert_eq!(vm.get_status(), VmStatus::Stopped);
+        assert_eq!(vm.get_pc(), 2);
+        assert_eq!(vm.execute(), StepResult::Blocked);
+
+        assert_eq!(vm.pop_stack(), Some(2));
+        vm.run();
+        assert_eq!(vm.get_status(), VmStatus::Stopped);
+        assert_eq!(vm.get_pc(), 5);
+        assert_eq!(vm.get_registers(), vec![3, 1, 0, 0]);
+
+        assert!(vm.push_stack(7));
+        vm.run();
+        assert_eq!(vm.get_status(), VmStatus::Finished);
+        assert_eq!(vm.get_registers(), vec![3, 1, 7, 0]);
+    }
+
+    #[test]
+    fn test_vm_call_and_ret() {
+        // main: CALL double, CALL double, HLT / double: ADD acc, RET
+        let program = vec![
+            Instruction::new(Opcode::INC),
+            Instruction::new(Opcode::CALL("double".to_string())),
+            Instruction::new(Opcode::CALL("double".to_string())),
+            Instruction::new(Opcode::HLT),
+            Instruction::new_label("double".to_string(), 4, 4),
+            Instruction::new(Opcode::ADD(Operand::ACC)),
+            Instruction::new(Opcode::RET),
+        ];
+
+        let mut vm = VirtualMachine::new_with_program(program.clone());
+        vm.execute();
+        vm.execute();
+        assert_eq!(vm.get_return_stack(), vec![2]);
+        vm.run();
+        assert_eq!(vm.get_acc(), 4);
+        assert_eq!(vm.get_status(), VmStatus::Finished);
+
+        // vm with stack keeps return addresses on it
+        let mut vm = VirtualMachine::new_with_program(program).with_stack(4);
+        vm.execute();
+        vm.execute();
+        assert_eq!(vm.get_stack(), vec![2]);
+        assert!(vm.get_return_stack().is_empty());
+        vm.run();
+        assert_eq!(vm.get_acc(), 4);
+    }
+
+    #[test]
+    fn test_vm_call_and_ret_faults() {
+        let program = vec![Instruction::new(Opcode::RET)];
+        let mut vm = VirtualMachine::new_with_program(program.clone());
+        assert_eq!(vm.execute(), StepResult::Faulted(VmFault::StackUnderflow));
+
+        // empty stack would give 0 as return address, so it is a fault
+        let mut vm = VirtualMachine::new_with_program(program).with_stack(4);
+        assert_eq!(vm.execute(), StepResult::Faulted(VmFault::StackUnderflow));
+
+        let program = vec![
+            Instruction::new_label("recurse".to_string(), 0, 0),
+            Instruction::new(Opcode::CALL("recurse".to_string())),
+        ];
+        let mut vm = VirtualMachine::new_with_program(program.clone());
+        vm.run();
+        assert_eq!(vm.get_status(), VmStatus::Faulted(VmFault::StackOverflow));
+        assert_eq!(vm.get_return_stack().len(), RETURN_STACK_DEPTH);
+
+        let mut vm = VirtualMachine::new_with_program(program).with_stack(3);
+        vm.run();
+        assert_eq!(vm.get_status(), VmStatus::Faulted(VmFault::StackOverflow));
+        assert_eq!(vm.get_stack(), vec![2, 2, 2]);
+    }
+
+    #[test]
+    fn test_vm_call_with_return_address_outside_of_word() {
+        let mut program = vec![Instruction::new(Opcode::NOP); 200];
+        program.push(Instruction::new(Opcode::CALL("end".to_string())));
+        program.push(Instruction::new_label("end".to_string(), 201, 201));
+        program.push(Instruction::new(Opcode::RET));
+        let profile = MachineProfile::new().with_word_size(8).with_stack(4);
+
+        let mut vm = VirtualMachine::new_with_profile(profile);
+        vm.load_program(program);
+        vm.run();
+
+        // return address 201 doesn't fit in 8 bit word, so it is not wrapped
+        assert_eq!(
+            vm.get_status(),
+            VmStatus::Faulted(VmFault::ReturnAddressOverflow(201))
+        );
+        assert_eq!(vm.get_pc(), 200);
+        assert!(vm.get_stack().is_empty());
+    }
+
+    #[test]
+    fn test_vm_deserialize_state_saved_before_profiles() {
+        // state of vm with stack of 8 values, saved before profiles, status bits of flag,
+        // label indexes and other fields were added
+        let state = r#"{"pc":7,"acc":4,"flag":"GREATER","r":[0,4,0,0],
+            "p":[{"Disconnected":0},{"Disconnected":0},{"Disconnected":0},
+                {"Disconnected":0},{"Disconnected":0},{"Disconnected":0}],
+            "labels":{"loop":2},
+            "program":[{"Opcode":{"MOV":[{"IntegerValue":5},{"GeneralRegister":1}]}},
+                {"Label":["loop",1]},{"Opcode":{"PSH":{"GeneralRegister":1}}},
+                {"Opcode":{"MOV":[{"GeneralRegister":1},"ACC"]}},
+                {"Opcode":{"SUB":{"IntegerValue":1}}},
+                {"Opcode":{"MOV":["ACC",{"GeneralRegister":1}]}},
+                {"Opcode":{"CMP":[{"GeneralRegister":1},{"IntegerValue":3}]}},
+                {"Opcode":{"JG":"loop"}},{"Opcode":"HLT"}],
+            "status":"Initial","delay_ms":0,"stack_present":true,"stack_size":8,"stack":[5]}"#;
+
+        let mut vm: VirtualMachine = serde_json::from_str(state).unwrap();
+        assert_eq!((vm.get_pc(), vm.get_acc()), (7, 4));
+        assert!(vm.get_flag().is_greater());
+        assert_eq!(vm.get_profile(), MachineProfile::new().with_stack(8));
+        assert_eq!(
+            vm.get_program()[1],
+            Instruction::new_label("loop".to_string(), 1, 1)
+        );
+        assert_eq!(vm.get_cycles(), 0);
+
+        vm.run();
+        assert_eq!(vm.get_status(), VmStatus::Finished);
+        assert_eq!(vm.get_stack(), vec![5, 4]);
+        assert_eq!(vm.get_registers()[1], 3);
+
+        // state saved now keeps profile
+        let json = serde_json::to_string(&vm).unwrap();
+        let vm: VirtualMachine = serde_json::from_str(&json).unwrap();
+        assert_eq!(vm.get_profile(), MachineProfile::new().with_stack(8));
+    }
 }
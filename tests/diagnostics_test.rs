@@ -0,0 +1,66 @@
+use simple_virtual_assembler::assembler::assembler::Assembler;
+
+#[test]
+fn rendering_diagnostics_in_both_languages() {
+    // Locale is global, both languages are checked in one test so they don't interfere
+    let program_text = "MOV 1 acc\n    ADD r7   # comment\nHLT";
+
+    rust_i18n::set_locale("en");
+    let (_, diagnostics) = Assembler::new().parse_with_diagnostics(program_text);
+    assert_eq!(diagnostics.len(), 1);
+    assert_eq!(
+        diagnostics[0].render(program_text),
+        "Error: Invalid register number\n \
+          --> line 2:9\n  \
+          |\n\
+         2 |     ADD r7   # comment\n  \
+           |         ^^ expected register r0..r3, found r7\n"
+    );
+
+    rust_i18n::set_locale("pl");
+    let (_, diagnostics) = Assembler::new().parse_with_diagnostics(program_text);
+    assert_eq!(
+        diagnostics[0].render(program_text),
+        "Błąd: Nieprawidłowy numer rejestru\n \
+          --> linia 2:9\n  \
+          |\n\
+         2 |     ADD r7   # comment\n  \
+           |         ^^ oczekiwano rejestr r0..r3, znaleziono r7\n"
+    );
+
+    rust_i18n::set_locale("en");
+}
+
+#[test]
+fn rendering_error_pointing_at_unknown_instruction() {
+    let program_text = "\n\tMVO 1 acc\n";
+
+    let error = Assembler::new().parse(program_text).unwrap_err();
+    let data = error.get_data();
+
+    assert_eq!(data.get_line(), 1);
+    assert_eq!(data.get_span(), Some((1, 4)));
+    assert_eq!(data.get_token(), "MVO");
+
+    let rendered = error.render(program_text);
+
+    // tab is kept so underline stays aligned
+    assert!(rendered.contains("2 | \tMVO 1 acc\n"));
+    assert!(rendered.contains("  | \t^^^ "));
+}
+
+#[test]
+fn rendering_error_in_macro_expansion() {
+    let program_text = ".macro load value\n    MOV value r7\n.endm\nload 5\n";
+
+    let error = Assembler::new().parse(program_text).unwrap_err();
+
+    let rendered = error.render(program_text);
+
+    // call site is underlined, line in macro body is named in note
+    assert!(rendered.contains("4 | load 5\n"));
+    assert!(rendered.contains("  | ^^^^^^ "));
+    let note = rendered.lines().last().unwrap();
+    assert!(note.starts_with("  = "));
+    assert!(note.contains("'load'") && note.ends_with('2'));
+}
@@ -0,0 +1,256 @@
+use proptest::prelude::*;
+
+use simple_virtual_assembler::{
+    assembler::{
+        assembler::Assembler,
+        disassembler::{disassemble, Disassembler, Radix},
+    },
+    vm::{instruction::Instruction, opcodes::Opcode, operand::Operand},
+};
+
+/// Element of generated program, labels and jump targets are named after program is generated
+#[derive(Debug, Clone)]
+enum Item {
+    Opcode(Opcode),
+    /// Label preceded by given number of empty lines
+    Label(usize),
+    Jump(fn(String) -> Opcode, usize),
+}
+
+fn operand() -> impl Strategy<Value = Operand> {
+    prop_oneof![any::<i32>().prop_map(Operand::IntegerValue), destination(),]
+}
+
+fn destination() -> impl Strategy<Value = Operand> {
+    prop_oneof![
+        (0..4usize).prop_map(Operand::GeneralRegister),
+        (0..6usize).prop_map(Operand::PortRegister),
+        Just(Operand::ACC),
+        Just(Operand::PC),
+        (0..4usize, any::<i32>()).prop_map(|(register, offset)| Operand::Memory(register, offset)),
+    ]
+}
+
+fn opcode() -> impl Strategy<Value = Opcode> {
+    let unary: Vec<fn(Operand) -> Opcode> = vec![
+        Opcode::ADD,
+        Opcode::SUB,
+        Opcode::MUL,
+        Opcode::DIV,
+        Opcode::MOD,
+        Opcode::AND,
+        Opcode::OR,
+        Opcode::XOR,
+        Opcode::SHL,
+        Opcode::SHR,
+        Opcode::SAR,
+        Opcode::LSR,
+        Opcode::ROL,
+        Opcode::ROR,
+        Opcode::MIN,
+        Opcode::MAX,
+        Opcode::PSH,
+        Opcode::POP,
+    ];
+    prop_oneof![
+        prop_oneof![
+            Just(Opcode::NOP),
+            Just(Opcode::HLT),
+            Just(Opcode::INC),
+            Just(Opcode::DEC),
+            Just(Opcode::NOT),
+            Just(Opcode::RET),
+            Just(Opcode::NEG),
+            Just(Opcode::ABS),
+        ],
+        (operand(), destination()).prop_map(|(op1, op2)| Opcode::MOV(op1, op2)),
+        (operand(), operand()).prop_map(|(op1, op2)| Opcode::CMP(op1, op2)),
+        (operand(), operand()).prop_map(|(op1, op2)| Opcode::CLAMP(op1, op2)),
+        (prop::sample::select(unary), operand()).prop_map(|(opcode, op)| opcode(op)),
+    ]
+}
+
+fn item() -> impl Strategy<Value = Item> {
+    let jumps: Vec<fn(String) -> Opcode> = vec![
+        Opcode::JMP,
+        Opcode::JE,
+        Opcode::JNE,
+        Opcode::JL,
+        Opcode::JG,
+        Opcode::JZ,
+        Opcode::JNZ,
+        Opcode::JC,
+        Opcode::JO,
+        Opcode::JS,
+        Opcode::JA,
+        Opcode::JAE,
+        Opcode::JB,
+        Opcode::JBE,
+        Opcode::CALL,
+    ];
+    prop_oneof![
+        4 => opcode().prop_map(Item::Opcode),
+        1 => (0..3usize).prop_map(Item::Label),
+        1 => (prop::sample::select(jumps), any::<usize>())
+            .prop_map(|(jump, target)| Item::Jump(jump, target)),
+    ]
+}
+
+/// Generates valid program - labels are unique, every jump targets existing label
+/// and labels are on lines they would be on in source with one instruction per line
+fn program() -> impl Strategy<Value = Vec<Instruction>> {
+    prop::collection::vec(item(), 1..40).prop_map(|items| {
+        let label_count = items
+            .iter()
+            .filter(|item| matches!(item, Item::Label(_)))
+            .count();
+        let mut program = Vec::new();
+        let mut label = 0;
+        let mut line = 0;
+        for item in items {
+            let instruction = match item {
+                Item::Opcode(opcode) => Instruction::new(opcode),
+                Item::Label(empty_lines) => {
+                    label += 1;
+                    line += empty_lines;
+                    Instruction::new_label(format!("label_{}", label), line, program.len())
+                }
+                Item::Jump(_, _) if label_count == 0 => continue,
+                Item::Jump(jump, target) => {
+                    Instruction::new(jump(format!("label_{}", target % label_count + 1)))
+                }
+            };
+            program.push(instruction);
+            line += 1;
+        }
+        if program.is_empty() {
+            program.push(Instruction::new(Opcode::HLT));
+        }
+        program
+    })
+}
+
+fn literal() -> impl Strategy<Value = String> {
+    let radixes = vec![Radix::Decimal, Radix::Binary, Radix::Hex, Radix::Char];
+    (any::<i32>(), 0..128i32, prop::sample::select(radixes)).prop_map(
+        |(value, small_value, radix)| match radix {
+            Radix::Char => radix.format(small_value),
+            radix => radix.format(value),
+        },
+    )
+}
+
+proptest! {
+    #[test]
+    fn disassembled_program_assembles_to_same_program(program in program()) {
+        let text = disassemble(&program);
+
+        let result = Assembler::new().with_stack().parse(&text);
+
+        prop_assert_eq!(result, Ok(program));
+    }
+
+    #[test]
+    fn disassembling_preserves_literal_radix(literals in prop::collection::vec(literal(), 1..20)) {
+        let text: String = literals
+            .iter()
+            .map(|literal| format!("    CMP {} acc\n", literal))
+            .collect();
+
+        let mut assembler = Assembler::new();
+        let program = assembler.parse(&text).unwrap();
+        let disassembled = Disassembler::new()
+            .with_radixes(assembler.get_literal_radixes())
+            .disassemble(&program);
+
+        prop_assert_eq!(disassembled, text);
+    }
+}
+
+#[test]
+fn disassembling_assembled_source() {
+    let program_text = r#"
+    # Masks lower byte
+    MOV 0x1234 acc
+    loop:               # comment
+        AND 0xFF
+        CMP acc 'A'
+        JL loop
+    HLT
+    "#;
+
+    let mut assembler = Assembler::new();
+    let program = assembler.parse(program_text).unwrap();
+
+    let text = Disassembler::new()
+        .with_radixes(assembler.get_literal_radixes())
+        .disassemble(&program);
+
+    assert_eq!(
+        text,
+        "    MOV 0x1234 acc\n\n\nloop:\n    AND 0xFF\n    CMP acc 'A'\n    JL loop\n    HLT\n"
+    );
+    assert_eq!(assembler.parse(&text), Ok(program));
+}
+
+#[test]
+fn disassembling_local_labels() {
+    let program_text = r#"
+    outer:
+        .loop:
+            INC
+            CMP acc 3
+            JL .loop
+    inner:
+        .loop:
+            DEC
+            JG .loop
+            JMP outer
+    "#;
+
+    let mut assembler = Assembler::new();
+    let program = assembler.parse(program_text).unwrap();
+
+    let text = disassemble(&program);
+
+    // local labels are qualified with their scope, so both loops stay distinct
+    assert_eq!(
+        text,
+        "\nouter:\nouter.loop:\n    INC\n    CMP acc 3\n    JL outer.loop\n\
+         inner:\ninner.loop:\n    DEC\n    JG inner.loop\n    JMP outer\n"
+    );
+    assert_eq!(assembler.parse(&text), Ok(program));
+}
+
+#[test]
+fn disassembling_with_aliases() {
+    let program_text = r#"
+    .alias ready p0
+    main:
+        .alias counter r1
+        MOV ready counter
+        JMP next
+    next:
+        .alias counter r2
+        ADD counter
+        MOV acc ready
+    "#;
+
+    let mut assembler = Assembler::new();
+    let program = assembler.parse(program_text).unwrap();
+
+    let text = Disassembler::new()
+        .with_aliases(assembler.get_operand_aliases())
+        .disassemble(&program);
+
+    // directives are placed where aliases are first used in their scope
+    assert_eq!(
+        text,
+        "\n\nmain:\n    .alias ready p0\n    .alias counter r1\n    MOV ready counter\n    JMP next\n\
+         next:\n    .alias counter r2\n    ADD counter\n    .alias ready p0\n    MOV acc ready\n"
+    );
+    // global alias is defined again in scope of main, which moves next one line down
+    let mut expected = program;
+    expected[3] = Instruction::new_label("next".to_string(), 7, 3);
+    assert_eq!(assembler.parse(&text), Ok(expected));
+}
@@ -6,12 +6,15 @@ use std::{
 
 use simple_virtual_assembler::{
     assembler::assembler::Assembler,
-    components::connection::Connection,
+    components::{connection::Connection, ram::Ram},
     vm::{
+        breakpoint::{Access, Breakpoint, Comparison, Watched, Watchpoint},
+        fault::VmFault,
         instruction::Instruction,
+        journal::{ReverseError, DEFAULT_JOURNAL_BUDGET},
         opcodes::Opcode,
         operand::Operand,
-        virtual_machine::{VirtualMachine, VmStatus},
+        virtual_machine::{StackPolicy, VirtualMachine, VmStatus},
     },
 };
 
@@ -311,3 +314,201 @@ fn test_basic_communication_between_vms_with_simpler_api() {
 
     println!("{}", vm2_copy.lock().unwrap());
 }
+
+#[test]
+fn faulting_vm_on_thread_does_not_panic() {
+    let program = Assembler::new().parse("MOV 0 r0\nDIV r0\nHLT").unwrap();
+    let (vm, vm_copy) = VirtualMachine::new_shared_with_program(program);
+
+    VirtualMachine::start(vm).join().unwrap();
+
+    let vm = vm_copy.lock().unwrap();
+    assert_eq!(vm.get_status(), VmStatus::Faulted(VmFault::DivideByZero));
+    assert_eq!(vm.get_pc(), 1);
+}
+
+#[test]
+fn blocked_vm_on_thread_waits_for_space_on_stack() {
+    let program = Assembler::new()
+        .with_stack()
+        .parse("PSH 1\nPSH 2\nPOP r0")
+        .unwrap();
+    let vm = VirtualMachine::new()
+        .with_stack(1)
+        .with_stack_policy(StackPolicy::Block);
+    let vm = Arc::new(Mutex::new(vm));
+    vm.lock().unwrap().load_program(program);
+
+    let handle = VirtualMachine::start(vm.clone());
+
+    // vm waits at second PSH until value is taken from stack
+    let mut popped = None;
+    while popped.is_none() {
+        thread::sleep(Duration::from_millis(5));
+        let mut vm = vm.lock().unwrap();
+        if vm.get_pc() == 1 {
+            popped = vm.pop_stack();
+        }
+    }
+    handle.join().unwrap();
+
+    let vm = vm.lock().unwrap();
+    assert_eq!(popped, Some(1));
+    assert_eq!(vm.get_registers()[0], 2);
+    assert_eq!(vm.get_status(), VmStatus::Finished);
+}
+
+#[test]
+fn vm_on_thread_pauses_at_breakpoint() {
+    let mut assembler = Assembler::new();
+    let program = assembler
+        .parse("loop:\n    INC\n    CMP acc 10\n    JL loop\nHLT")
+        .unwrap();
+    let condition = assembler.parse_breakpoint_condition("acc == 5").unwrap();
+    let (vm, vm_copy) = VirtualMachine::new_shared_with_program(program);
+    vm.lock()
+        .unwrap()
+        .add_breakpoint(Breakpoint::at_label("loop").with_condition(condition));
+
+    let handle = VirtualMachine::start(vm);
+
+    // vm waits at breakpoint until it is resumed, unlike after stop it is not running
+    loop {
+        thread::sleep(Duration::from_millis(5));
+        if vm_copy.lock().unwrap().get_status() == VmStatus::AtBreakpoint {
+            break;
+        }
+    }
+    thread::sleep(Duration::from_millis(20));
+    assert_eq!(vm_copy.lock().unwrap().get_acc(), 5);
+    assert_eq!(vm_copy.lock().unwrap().get_pc(), 1);
+
+    VirtualMachine::resume(vm_copy.clone());
+    handle.join().unwrap();
+
+    let vm = vm_copy.lock().unwrap();
+    assert_eq!(vm.get_acc(), 10);
+    assert_eq!(vm.get_status(), VmStatus::Finished);
+}
+
+#[test]
+fn vm_on_thread_pauses_at_watchpoint() {
+    let mut assembler = Assembler::new();
+    let program = assembler
+        .parse("loop:\n    INC\n    MOV acc p1\n    CMP acc 10\n    JL loop\nHLT")
+        .unwrap();
+    let (vm, vm_copy) = VirtualMachine::new_shared_with_program(program);
+    vm.lock().unwrap().add_watchpoint(
+        Watchpoint::new(Watched::Operand(Operand::PortRegister(1)), Access::Write)
+            .with_value(Comparison::Equal, 7),
+    );
+
+    let handle = VirtualMachine::start(vm);
+
+    // vm waits after instruction that wrote to p1 until it is resumed
+    loop {
+        thread::sleep(Duration::from_millis(5));
+        if vm_copy.lock().unwrap().get_status() == VmStatus::AtWatchpoint {
+            break;
+        }
+    }
+    thread::sleep(Duration::from_millis(20));
+    {
+        let vm = vm_copy.lock().unwrap();
+        let hits = vm.get_watchpoint_hits();
+        assert_eq!(hits.len(), 1);
+        assert_eq!(hits[0].get_pc(), 2);
+        assert_eq!(hits[0].get_instruction().to_string(), "MOV acc p1");
+        assert_eq!(hits[0].get_values(), (6, 7));
+        assert_eq!(vm.get_pc(), 3);
+    }
+
+    VirtualMachine::resume(vm_copy.clone());
+    handle.join().unwrap();
+
+    let vm = vm_copy.lock().unwrap();
+    assert_eq!(vm.get_ports_values()[1], 10);
+    assert_eq!(vm.get_status(), VmStatus::Finished);
+}
+
+#[test]
+fn connected_vms_step_back_together() {
+    let mut assembler = Assembler::new();
+    let sender = assembler.parse("MOV 5 p0\nMOV 6 p0\nHLT").unwrap();
+    let receiver = assembler.parse("MOV p0 r0\nADD p0\nHLT").unwrap();
+    let (vm1, _) = VirtualMachine::new_shared_with_program(sender);
+    let (vm2, _) = VirtualMachine::new_shared_with_program(receiver);
+    let mut connection = Connection::new();
+    vm1.lock().unwrap().connect(0, &mut connection);
+    vm2.lock().unwrap().connect(0, &mut connection);
+    let vms = [vm1.clone(), vm2.clone()];
+    VirtualMachine::journal_together(&vms, &[&connection], DEFAULT_JOURNAL_BUDGET);
+
+    // vms take turns: vm1 sends 5, vm2 reads it, vm1 sends 6, vm2 adds it
+    for _ in 0..2 {
+        vm1.lock().unwrap().step();
+        vm2.lock().unwrap().step();
+    }
+    assert_eq!(vm2.lock().unwrap().get_registers()[0], 5);
+    assert_eq!(vm2.lock().unwrap().get_acc(), 6);
+
+    // instructions are undone in reverse order of execution across vms
+    let order: Vec<usize> = (0..4)
+        .map(|_| VirtualMachine::step_back_together(&vms).unwrap())
+        .collect();
+    assert_eq!(order, vec![1, 0, 1, 0]);
+    assert_eq!(
+        VirtualMachine::step_back_together(&vms),
+        Err(ReverseError::JournalEmpty)
+    );
+    assert_eq!(vm1.lock().unwrap().get_ports_values()[0], 0);
+    assert_eq!(vm2.lock().unwrap().get_registers()[0], 0);
+    assert_eq!(vm2.lock().unwrap().get_pc(), 0);
+}
+
+#[test]
+fn writes_to_ports_shared_outside_of_group_are_irreversible() {
+    let mut assembler = Assembler::new();
+    let program = assembler
+        .parse("MOV 5 p0\nMOV 6 p1\nMOV 7 p2\nHLT")
+        .unwrap();
+    let (vm1, _) = VirtualMachine::new_shared_with_program(program.clone());
+    let (vm2, _) = VirtualMachine::new_shared_with_program(program.clone());
+    let (outsider, _) = VirtualMachine::new_shared_with_program(program);
+
+    // p0 connects vms in group, p1 connects vm1 to ram, p2 connects vm1 to vm outside of group
+    let mut between_vms = Connection::new();
+    let mut to_ram = Connection::new();
+    let mut to_outsider = Connection::new();
+    vm1.lock().unwrap().connect(0, &mut between_vms);
+    vm2.lock().unwrap().connect(0, &mut between_vms);
+    vm1.lock().unwrap().connect(1, &mut to_ram);
+    let mut ram = Ram::new();
+    ram.connect_data_port(&mut to_ram);
+    vm1.lock().unwrap().connect(2, &mut to_outsider);
+    outsider.lock().unwrap().connect(2, &mut to_outsider);
+
+    let vms = [vm1.clone(), vm2.clone()];
+    let connections = [&between_vms, &to_ram, &to_outsider];
+    VirtualMachine::journal_together(&vms, &connections, DEFAULT_JOURNAL_BUDGET);
+
+    let mut vm1 = vm1.lock().unwrap();
+    for _ in 0..3 {
+        vm1.step();
+    }
+    assert_eq!(vm1.step_back(), Err(ReverseError::IrreversiblePortWrite(2)));
+    assert_eq!(
+        vm1.run_back_to(0),
+        Err(ReverseError::IrreversiblePortWrite(2))
+    );
+
+    // after disconnecting vm outside of group, only ram port is irreversible
+    vm1.disconnect(2);
+    vm1.run();
+    vm1.clear_registers();
+    for _ in 0..3 {
+        vm1.step();
+    }
+    assert_eq!(vm1.step_back(), Ok(()));
+    assert_eq!(vm1.step_back(), Err(ReverseError::IrreversiblePortWrite(1)));
+}
@@ -3,8 +3,10 @@ extern crate simple_virtual_assembler;
 use simple_virtual_assembler::assembler::assembler::Assembler;
 use simple_virtual_assembler::assembler::parsing_err::ParsingError;
 use simple_virtual_assembler::vm;
+use simple_virtual_assembler::vm::profile::MachineProfile;
+use simple_virtual_assembler::vm::trace::Trace;
 
-use simple_virtual_assembler::vm::virtual_machine::VirtualMachine;
+use simple_virtual_assembler::vm::virtual_machine::{VirtualMachine, VmStatus};
 
 /// Parses and runs program on vm
 fn assembler_and_run(program_text: &str) -> Result<VirtualMachine, ParsingError> {
@@ -37,7 +39,7 @@ fn assembler_and_with_prints(program_text: &str) -> Result<VirtualMachine, Parsi
         );
         println!("{}", vm);
         println!("______________________________________________________");
-        running = vm.execute();
+        running = vm.execute().is_continue();
         counter += 1;
     }
 
@@ -158,3 +160,313 @@ fn test_stack() {
         Err(err) => println!("{}", err),
     };
 }
+
+#[test]
+fn assembling_and_running_program_with_comments_above_label() {
+    // Comments and blank lines are not part of assembled program,
+    // jumps have to target index of label in program, not its line in source
+    let program = r#"
+    # Counts to 5
+    #
+    # result is stored in acc
+
+    MOV 0 acc           # Set counter to 0
+
+    # Looping
+    loop:
+        INC
+        CMP acc 5
+        JL loop
+    HLT
+    "#;
+
+    let vm = assembler_and_run(program).unwrap();
+
+    assert_eq!(vm.get_acc(), 5);
+}
+
+#[test]
+fn assembling_and_running_program_with_comments_between_labels() {
+    // Skips adding 100 by jumping forward over commented block
+    let program = r#"
+    MOV 1 acc
+    JMP skip
+
+    # This block is never executed
+    # ...
+    # ...
+    ADD 100
+
+    skip:
+        # Doubling acc
+        MUL 2
+    HLT
+    "#;
+
+    let vm = assembler_and_run(program).unwrap();
+
+    assert_eq!(vm.get_acc(), 2);
+}
+
+#[test]
+fn assembling_and_running_program_with_macros() {
+    // Each expansion gets its own copy of `next` label
+    let program = r#"
+    .macro add_until value, limit
+        next:
+            ADD value
+            CMP acc limit
+            JL next
+    .endm
+
+    MOV 0 acc
+    add_until 3, 10
+    add_until 5, 30
+    HLT
+    "#;
+
+    let vm = assembler_and_run(program).unwrap();
+
+    assert_eq!(vm.get_acc(), 32);
+}
+
+#[test]
+fn assembling_one_program_for_vms_with_and_without_stack() {
+    // Swaps r0 and r1, through stack if vm has one
+    let program_text = r#"
+    MOV 1 r0
+    MOV 2 r1
+    .if __STACK__
+        PSH r0
+        MOV r1 r0
+        POP r1
+    .else
+        MOV r0 r2
+        MOV r1 r0
+        MOV r2 r1
+    .endif
+    HLT
+    "#;
+
+    let program = Assembler::new().with_stack().parse(program_text).unwrap();
+    let mut vm = VirtualMachine::new_with_program(program).with_stack(4);
+    vm.run();
+    assert_eq!(vm.get_registers(), [2, 1, 0, 0]);
+
+    let program = Assembler::new().parse(program_text).unwrap();
+    let mut vm = VirtualMachine::new_with_program(program);
+    vm.run();
+    assert_eq!(vm.get_registers(), [2, 1, 1, 0]);
+}
+
+#[test]
+fn assembling_and_running_program_on_custom_profile() {
+    let profile = MachineProfile::new()
+        .with_registers(2)
+        .with_ports(8)
+        .with_stack(2);
+    let program_text = r#"
+    MOV 5 p7
+    PSH p7
+    POP r1
+    "#;
+
+    let program = Assembler::new_with_profile(profile)
+        .parse(program_text)
+        .unwrap();
+    let mut vm = VirtualMachine::new_with_profile(profile);
+    vm.load_program(program);
+    vm.run();
+
+    assert_eq!(vm.get_registers(), [0, 5]);
+    assert_eq!(vm.get_ports().len(), 8);
+    assert!(Assembler::new().parse(program_text).is_err());
+}
+
+#[test]
+fn assembling_and_running_program_with_subroutines() {
+    // Computes 3 * 4 with subroutine adding r0 to acc r1 times
+    let program_text = r#"
+        MOV 3 r0
+        MOV 4 r1
+        CALL multiply
+        MOV acc r2
+        HLT
+    multiply:
+        MOV 0 acc
+        .loop:
+            ADD r0
+            CALL decrement
+            CMP r1 0
+            JG .loop
+        RET
+    decrement:
+        MOV acc r3
+        MOV r1 acc
+        DEC
+        MOV acc r1
+        MOV r3 acc
+        RET
+    "#;
+
+    let vm = assembler_and_run(program_text).unwrap();
+
+    assert_eq!(vm.get_registers()[2], 12);
+    assert_eq!(vm.get_status(), VmStatus::Finished);
+}
+
+#[test]
+fn assembling_and_running_multi_word_arithmetic() {
+    // Adds 64 bit numbers stored as pairs of words (high, low): 0x1_FFFFFFFF + 0x2_00000001
+    let program_text = r#"
+        MOV 0x1 r0
+        MOV -1 r1
+        MOV 0x2 r2
+        MOV 0x00000001 r3
+
+        MOV r1 acc
+        ADD r3
+        MOV acc p1
+        MOV r0 acc
+        JC carry
+        ADD r2
+        JMP done
+    carry:
+        ADD r2
+        INC
+    done:
+        MOV acc p0
+        CMP p1 0
+        JNZ end
+        MOV 1 p2
+    end:
+        HLT
+    "#;
+
+    let vm = assembler_and_run(program_text).unwrap();
+
+    assert_eq!(vm.get_ports_values()[..3], [4, 0, 1]);
+    assert_eq!(vm.get_status(), VmStatus::Finished);
+}
+
+#[test]
+fn assembling_and_running_unsigned_comparison() {
+    // Counts values in ports above 0x7FFFFFFF, as unsigned numbers
+    let program_text = r#"
+        MOV 0x80000000 p0
+        MOV -1 p1
+        MOV 0x7FFFFFFF p2
+        MOV 0 acc
+
+        CMP p0 0x7FFFFFFF
+        JBE .p1
+        INC
+    .p1:
+        CMP p1 0x7FFFFFFF
+        JBE .p2
+        INC
+    .p2:
+        CMP p2 0x7FFFFFFF
+        JBE .end
+        INC
+    .end:
+        HLT
+    "#;
+
+    let vm = assembler_and_run(program_text).unwrap();
+
+    assert_eq!(vm.get_acc(), 2);
+}
+
+#[test]
+fn assembling_and_running_table_lookup() {
+    // Sums table loaded into data memory, walking it with indexed operand
+    let program_text = r#"
+    .equ LENGTH 4
+    .alias index r0
+    .alias sum r1
+    loop:
+        MOV sum acc
+        ADD [index]
+        MOV acc sum
+        MOV acc [index + LENGTH]
+        MOV index acc
+        INC
+        MOV acc index
+        CMP index LENGTH
+        JL loop
+    "#;
+
+    let profile = MachineProfile::new().with_memory(8);
+    let program = Assembler::new_with_profile(profile)
+        .parse(program_text)
+        .unwrap();
+    let mut vm = VirtualMachine::new_with_profile(profile);
+    vm.load_program(program);
+    vm.write_memory(0, &[1, 2, 3, 4]).unwrap();
+    vm.run();
+
+    // running sums are stored after the table
+    assert_eq!(vm.get_memory(), [1, 2, 3, 4, 1, 3, 6, 10]);
+    assert_eq!(vm.get_status(), VmStatus::Finished);
+}
+
+#[test]
+fn comparing_traces_of_student_and_reference_runs() {
+    let run_traced = |program_text: &str| {
+        let program = Assembler::new().parse(program_text).unwrap();
+        let mut vm = VirtualMachine::new_with_program(program).with_trace();
+        vm.run();
+        vm.take_trace().unwrap()
+    };
+    // counts down from 3, storing counter in p0
+    let reference = run_traced("MOV 3 acc\nloop:\nMOV acc p0\nDEC\nJNZ loop\nHLT");
+    // student decrements after comparing and loops once more
+    let student = run_traced("MOV 3 acc\nloop:\nMOV acc p0\nCMP acc 0\nDEC\nJNE loop\nHLT");
+
+    assert_eq!(reference.len(), 11);
+    assert_eq!(reference.diff(&reference.clone()), None);
+    let divergence = reference.diff(&student).unwrap();
+    assert_eq!(divergence.get_step(), 2);
+    let expected = divergence.get_expected().unwrap();
+    let actual = divergence.get_actual().unwrap();
+    assert_eq!(
+        (expected.get_pc(), expected.get_instruction()),
+        (3, "DEC".to_string())
+    );
+    assert_eq!(
+        (actual.get_pc(), actual.get_instruction()),
+        (3, "CMP acc 0".to_string())
+    );
+
+    // traces survive export to both formats
+    let jsonl = student.to_jsonl();
+    assert_eq!(Trace::from_jsonl(&jsonl), Ok(student.clone()));
+    let binary = student.to_binary();
+    assert!(binary.len() < jsonl.len() / 4);
+    assert_eq!(Trace::from_binary(&binary), Ok(student));
+}
+
+#[test]
+fn comparing_traces_of_runs_writing_different_memory() {
+    let profile = MachineProfile::new().with_memory(4);
+    let run_traced = |program_text: &str| {
+        let program = Assembler::new_with_profile(profile)
+            .parse(program_text)
+            .unwrap();
+        let mut vm = VirtualMachine::new_with_profile(profile).with_trace();
+        vm.load_program(program);
+        vm.run();
+        vm.take_trace().unwrap()
+    };
+    let reference = run_traced("MOV 1 r1\nMOV 5 [r1]\nHLT");
+    let student = run_traced("MOV 1 r1\nMOV 6 [r1]\nHLT");
+
+    // both runs execute the same instructions, only stored values differ
+    let divergence = reference.diff(&student).unwrap();
+    assert_eq!(divergence.get_step(), 1);
+    assert_eq!(
+        divergence.get_actual().unwrap().to_string(),
+        "#1 1: MOV 6 [r1] mem[1]=6"
+    );
+}